@@ -70,7 +70,7 @@ function addInterpretLoxCommand(context: vscode.ExtensionContext, diagnostics: v
 		const contents = activeEditor.document.getText();
 		diagnostics.clear();
 		const diagnosticCollection : vscode.Diagnostic[] = [];
-		wasm.interpret(contents, messageAdder(), diagnosticAdder(diagnosticCollection));
+		interpretWithLimits(contents, messageAdder(), diagnosticAdder(diagnosticCollection));
 		diagnostics.set(activeEditor.document.uri, diagnosticCollection);
 	});
 }
@@ -137,11 +137,23 @@ function addInterpretSelectedLoxCommand(context: vscode.ExtensionContext, diagno
 		}
 		diagnostics.clear();
 		const diagnosticCollection : vscode.Diagnostic[] = [];
-		wasm.interpret(contents, messageAdder(), diagnosticAdder(diagnosticCollection, selection.start.line, selection.start.character));
+		interpretWithLimits(contents, messageAdder(), diagnosticAdder(diagnosticCollection, selection.start.line, selection.start.character));
 		diagnostics.set(activeEditor.document.uri, diagnosticCollection);
 	});
 }
 
+function interpretWithLimits(contents: string, addMessage: (message: string) => void, addDiagnostic: (start: FileLocation, end: FileLocation, message: string) => void) {
+	const configuration = vscode.workspace.getConfiguration();
+	const maxSteps = configuration.get<number>('lox.maxSteps') || undefined;
+	const timeout = configuration.get<number>('lox.timeout') || 0;
+	const deadline = Date.now() + timeout;
+	const isCancelled = timeout > 0 ? () => Date.now() > deadline : undefined;
+	const reproducible = configuration.get<boolean>('lox.reproducible') || false;
+	const randomSeed = reproducible ? 0 : undefined;
+	const fixedClock = reproducible ? 0 : undefined;
+	wasm.interpret_with_limits(contents, addMessage, addDiagnostic, maxSteps, isCancelled, randomSeed, fixedClock);
+}
+
 function defineCommand(context: vscode.ExtensionContext, commandName: string, callback: () => void) {
 	const command = vscode.commands.registerCommand(commandName, callback);
 	context.subscriptions.push(command);
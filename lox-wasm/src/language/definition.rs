@@ -18,6 +18,27 @@ pub fn provide_completions(
 
 const COMPLETION_TYPE_METHOD: u32 = 1;
 const COMPLETION_TYPE_PROPERTY: u32 = 9;
+const COMPLETION_TYPE_ENUM_MEMBER: u32 = 19;
+
+// Recorded as the type of variables initialised from literals; `#` keeps them
+// apart from class names.
+const STRING_TYPE: &str = "#string";
+const NUMBER_TYPE: &str = "#number";
+
+const STRING_METHODS: [&str; 11] = [
+    "chars",
+    "indexOf",
+    "iterator",
+    "length",
+    "lower",
+    "replace",
+    "split",
+    "startsWith",
+    "substring",
+    "trim",
+    "upper",
+];
+const NUMBER_METHODS: [&str; 4] = ["floor", "round", "toFixed", "toString"];
 
 fn definition_for_position(
     position: &lox::FileLocation,
@@ -104,14 +125,19 @@ impl<'t> Class<'t> {
         }
     }
 
+    /// Private (`#name`) members are only offered on `this` inside the class
+    /// that declares them, so they are never inherited by subclasses.
     fn get_completions(
         &self,
         scopes: &'t Scopes,
         include_properties: bool,
+        include_private: bool,
     ) -> LinkedList<(String, u32)> {
+        let is_visible = |name: &&&str| include_private || !name.starts_with('#');
         let mut completions: LinkedList<(String, u32)> = self
             .methods
             .keys()
+            .filter(is_visible)
             .map(|k| (k.to_string(), COMPLETION_TYPE_METHOD))
             .collect();
 
@@ -119,6 +145,7 @@ impl<'t> Class<'t> {
             let mut other: LinkedList<(String, u32)> = self
                 .properties
                 .keys()
+                .filter(is_visible)
                 .map(|k| (k.to_string(), COMPLETION_TYPE_PROPERTY))
                 .collect();
             completions.append(&mut other);
@@ -126,7 +153,7 @@ impl<'t> Class<'t> {
 
         if let Some(superclass) = self.superclass {
             if let Some(superclass) = scopes.find_class(superclass) {
-                completions.append(&mut superclass.get_completions(scopes, false))
+                completions.append(&mut superclass.get_completions(scopes, false, false))
             }
         }
 
@@ -138,6 +165,7 @@ struct Scope<'t> {
     identifiers: HashMap<&'t str, &'t lox::Token>,
     types: HashMap<&'t str, &'t str>,
     classes: HashMap<&'t str, Class<'t>>,
+    enums: HashMap<&'t str, &'t LinkedList<lox::Token>>,
 }
 
 impl<'t> Scope<'t> {
@@ -146,6 +174,7 @@ impl<'t> Scope<'t> {
             identifiers: HashMap::new(),
             types: HashMap::new(),
             classes: HashMap::new(),
+            enums: HashMap::new(),
         }
     }
 }
@@ -188,6 +217,12 @@ impl<'t> Scopes<'t> {
         });
     }
 
+    fn define_enum(&mut self, name: &'t lox::Token, members: &'t LinkedList<lox::Token>) {
+        self.scopes
+            .front_mut()
+            .and_then(|m| m.enums.insert(&name.lexeme, members));
+    }
+
     fn add_method_to_class(&mut self, class_name: &str, method: &'t lox::Token) {
         for scope in self.scopes.iter_mut() {
             if let Some(class) = scope.classes.get_mut(class_name) {
@@ -218,7 +253,7 @@ impl<'t> Scopes<'t> {
         None
     }
 
-    fn find_class(&self, name: &str) -> Option<&Class> {
+    fn find_class(&self, name: &str) -> Option<&Class<'_>> {
         for scope in self.scopes.iter() {
             if scope.classes.contains_key(name) {
                 return scope.classes.get(name);
@@ -227,6 +262,15 @@ impl<'t> Scopes<'t> {
         None
     }
 
+    fn find_enum(&self, name: &str) -> Option<&'t LinkedList<lox::Token>> {
+        for scope in self.scopes.iter() {
+            if scope.enums.contains_key(name) {
+                return scope.enums.get(name).cloned();
+            }
+        }
+        None
+    }
+
     fn find_type_for_identifier(&self, name: &str) -> Option<&str> {
         for scope in self.scopes.iter() {
             if scope.types.contains_key(name) {
@@ -273,7 +317,13 @@ impl<'a> Resolver<'a> {
                 methods,
                 ..
             } => self.resolve_stmt_class(name, superclass, methods),
+            lox::Stmt::Enum { name, members } => self.resolve_stmt_enum(name, members),
             lox::Stmt::Expression { expression } => self.resolve_stmt_expression(expression),
+            lox::Stmt::ForIn {
+                name,
+                iterable,
+                body,
+            } => self.resolve_stmt_for_in(name, iterable, body),
             lox::Stmt::Function { function } => self.resolve_stmt_function(function),
             lox::Stmt::If {
                 condition,
@@ -284,6 +334,7 @@ impl<'a> Resolver<'a> {
             lox::Stmt::Return { value, .. } => self.resolve_stmt_return(value),
             lox::Stmt::Var { name, initialiser } => self.resolve_stmt_var(name, initialiser),
             lox::Stmt::While { condition, body } => self.resolve_stmt_while(condition, body),
+            lox::Stmt::Yield { value, .. } => self.resolve_stmt_yield(value),
         }
     }
 
@@ -296,18 +347,28 @@ impl<'a> Resolver<'a> {
             } => self.resolve_expr_call(callee, arguments),
             lox::Expr::Get { object, name, .. } => self.resolve_expr_get(object, name),
             lox::Expr::Grouping { expression, .. } => self.resolve_expr_grouping(expression),
+            lox::Expr::Index { object, index, .. } => self.resolve_expr_index(object, index),
+            lox::Expr::Inner { .. } => (),
             lox::Expr::InvalidGet { object, name, .. } => {
                 self.resolve_invalid_expr_get(object, name)
             }
             lox::Expr::InvalidSuper { method, .. } => self.resolve_expr_invalid_super(method),
+            lox::Expr::List { elements, .. } => self.resolve_expr_list(elements),
             lox::Expr::Literal { value, .. } => self.resolve_expr_literal(value),
             lox::Expr::Logical { left, right, .. } => self.resolve_expr_logical(left, right),
+            lox::Expr::Map { entries, .. } => self.resolve_expr_map(entries),
             lox::Expr::Set {
                 object,
                 name,
                 value,
                 ..
             } => self.resolve_expr_set(object, name, value),
+            lox::Expr::SetIndex {
+                object,
+                index,
+                value,
+                ..
+            } => self.resolve_expr_set_index(object, index, value),
             lox::Expr::Super { method, .. } => self.resolve_expr_super(method),
             lox::Expr::This { .. } => self.resolve_expr_this(),
             lox::Expr::Unary { right, .. } => self.resolve_expr_unary(right),
@@ -367,10 +428,28 @@ impl<'a> Resolver<'a> {
         self.resolve_expr(superclass);
     }
 
+    fn resolve_stmt_enum(&mut self, name: &'a lox::Token, members: &'a LinkedList<lox::Token>) {
+        self.scopes.define_identifier(name);
+        self.scopes.define_enum(name, members);
+    }
+
     fn resolve_stmt_expression(&mut self, expression: &'a lox::Expr) {
         self.resolve_expr(expression);
     }
 
+    fn resolve_stmt_for_in(
+        &mut self,
+        name: &'a lox::Token,
+        iterable: &'a lox::Expr,
+        body: &'a lox::Stmt,
+    ) {
+        self.resolve_expr(iterable);
+        self.scopes.begin();
+        self.scopes.define_identifier(name);
+        self.resolve_stmt(body);
+        self.scopes.end();
+    }
+
     fn resolve_stmt_function(&mut self, function: &'a lox::Function) {
         self.scopes.define_identifier(function.name());
         self.resolve_function(function);
@@ -400,6 +479,17 @@ impl<'a> Resolver<'a> {
     fn resolve_stmt_var(&mut self, name: &'a lox::Token, initialiser: &'a Option<lox::Expr>) {
         self.scopes.define_identifier(name);
         if let Some(initialiser) = initialiser {
+            if let lox::Expr::Literal { value, .. } = initialiser {
+                match value.token_type {
+                    lox::TokenType::String => self
+                        .scopes
+                        .define_type_for_identifier(&name.lexeme, STRING_TYPE),
+                    lox::TokenType::Number => self
+                        .scopes
+                        .define_type_for_identifier(&name.lexeme, NUMBER_TYPE),
+                    _ => (),
+                }
+            }
             if let lox::Expr::Call { callee, .. } = initialiser {
                 if let lox::Expr::Variable { name: class, .. } = callee.as_ref() {
                     if self.scopes.find_class(&class.lexeme).is_some() {
@@ -417,6 +507,12 @@ impl<'a> Resolver<'a> {
         self.resolve_stmt(body);
     }
 
+    fn resolve_stmt_yield(&mut self, value: &'a Option<lox::Expr>) {
+        if let Some(value) = value {
+            self.resolve_expr(value);
+        }
+    }
+
     fn resolve_expr_assign(&mut self, name: &lox::Token, value: &'a lox::Expr) {
         self.resolve_expr(value);
         self.resolve_local(name);
@@ -432,7 +528,7 @@ impl<'a> Resolver<'a> {
         arguments.iter().for_each(|a| self.resolve_expr(a));
     }
 
-    fn find_class_for_expr(&self, expr: &lox::Expr) -> Option<&Class> {
+    fn find_class_for_expr(&self, expr: &lox::Expr) -> Option<&Class<'_>> {
         match expr {
             lox::Expr::Variable { name: target, .. } => self
                 .scopes
@@ -450,8 +546,41 @@ impl<'a> Resolver<'a> {
         }
     }
 
+    fn find_enum_for_expr(&self, expr: &lox::Expr) -> Option<&'a LinkedList<lox::Token>> {
+        if let lox::Expr::Variable { name, .. } = expr {
+            self.scopes.find_enum(&name.lexeme)
+        } else {
+            None
+        }
+    }
+
+    fn find_primitive_methods_for_expr(&self, expr: &lox::Expr) -> Option<&'static [&'static str]> {
+        let typ = match expr {
+            lox::Expr::Literal { value, .. } => match value.token_type {
+                lox::TokenType::String => STRING_TYPE,
+                lox::TokenType::Number => NUMBER_TYPE,
+                _ => return None,
+            },
+            lox::Expr::Variable { name, .. } => {
+                self.scopes.find_type_for_identifier(&name.lexeme)?
+            }
+            _ => return None,
+        };
+        match typ {
+            STRING_TYPE => Some(&STRING_METHODS),
+            NUMBER_TYPE => Some(&NUMBER_METHODS),
+            _ => None,
+        }
+    }
+
     fn resolve_expr_get(&mut self, object: &'a lox::Expr, name: &lox::Token) {
         if self.is_at_position(name) {
+            if let Some(members) = self.find_enum_for_expr(object) {
+                if let Some(member) = members.iter().find(|m| m.lexeme == name.lexeme) {
+                    self.definitions.push_back(member.clone());
+                }
+                return;
+            }
             let class: Option<&Class> = self.find_class_for_expr(object);
             if let Some(class) = class {
                 if let Some(method) = class.find_definition(&self.scopes, &name.lexeme) {
@@ -466,11 +595,28 @@ impl<'a> Resolver<'a> {
 
     fn resolve_invalid_expr_get(&mut self, object: &'a lox::Expr, name: &lox::Token) {
         if self.is_at_position(name) {
+            if let Some(members) = self.find_enum_for_expr(object) {
+                self.completions_for_position.extend(
+                    members
+                        .iter()
+                        .map(|m| (m.lexeme.clone(), COMPLETION_TYPE_ENUM_MEMBER)),
+                );
+                return;
+            }
+            if let Some(methods) = self.find_primitive_methods_for_expr(object) {
+                self.completions_for_position.extend(
+                    methods
+                        .iter()
+                        .map(|m| (m.to_string(), COMPLETION_TYPE_METHOD)),
+                );
+                return;
+            }
             {
+                let include_private = matches!(object, lox::Expr::This { .. });
                 let class: Option<&Class> = self.find_class_for_expr(object);
                 if let Some(class) = class {
                     self.completions_for_position
-                        .append(&mut class.get_completions(&self.scopes, true));
+                        .append(&mut class.get_completions(&self.scopes, true, include_private));
                 }
             }
         } else {
@@ -483,6 +629,15 @@ impl<'a> Resolver<'a> {
         self.resolve_expr(expression);
     }
 
+    fn resolve_expr_index(&mut self, object: &'a lox::Expr, index: &'a lox::Expr) {
+        self.resolve_expr(object);
+        self.resolve_expr(index);
+    }
+
+    fn resolve_expr_list(&mut self, elements: &'a [lox::Expr]) {
+        elements.iter().for_each(|e| self.resolve_expr(e));
+    }
+
     fn resolve_expr_literal(&mut self, _: &lox::Token) {}
 
     fn resolve_expr_logical(&mut self, left: &'a lox::Expr, right: &'a lox::Expr) {
@@ -490,6 +645,13 @@ impl<'a> Resolver<'a> {
         self.resolve_expr(right);
     }
 
+    fn resolve_expr_map(&mut self, entries: &'a [(lox::Expr, lox::Expr)]) {
+        entries.iter().for_each(|(key, value)| {
+            self.resolve_expr(key);
+            self.resolve_expr(value);
+        });
+    }
+
     fn resolve_expr_set(
         &mut self,
         object: &'a lox::Expr,
@@ -517,6 +679,17 @@ impl<'a> Resolver<'a> {
         }
     }
 
+    fn resolve_expr_set_index(
+        &mut self,
+        object: &'a lox::Expr,
+        index: &'a lox::Expr,
+        value: &'a lox::Expr,
+    ) {
+        self.resolve_expr(object);
+        self.resolve_expr(index);
+        self.resolve_expr(value);
+    }
+
     fn resolve_expr_super(&mut self, method: &lox::Token) {
         if self.is_at_position(method) {
             if let Some(class) = self.current_class.and_then(|c| self.scopes.find_class(c)) {
@@ -535,7 +708,7 @@ impl<'a> Resolver<'a> {
             if let Some(class) = self.current_class.and_then(|c| self.scopes.find_class(c)) {
                 if let Some(superclass) = class.superclass.and_then(|s| self.scopes.find_class(s)) {
                     self.completions_for_position
-                        .append(&mut superclass.get_completions(&self.scopes, true));
+                        .append(&mut superclass.get_completions(&self.scopes, true, false));
                 }
             }
         }
@@ -599,6 +772,18 @@ mod test {
     fn test_definitions() {
         let tests = vec![
             ("fred = 1;", (0, 0), vec![]),
+            (
+                "enum Color { Red, Green }
+                |print Color.Green;",
+                (1, 13),
+                vec![((0, 18), (0, 23))],
+            ),
+            (
+                "enum Color { Red, Green }
+                |print Color.Green;",
+                (1, 7),
+                vec![((0, 5), (0, 10))],
+            ),
             (
                 "var fred;
                 |fred = 1;",
@@ -765,6 +950,14 @@ mod test {
                 (2, 15),
                 vec![((1, 4), (1, 5))],
             ),
+            (
+                "var values = [1, 2];
+                |for (value in values) {
+                |  print value;
+                |}",
+                (2, 9),
+                vec![((1, 5), (1, 10))],
+            ),
         ];
         for (source, (line_number, line_offset), expected_locations) in tests {
             let result = provide_definition(
@@ -928,6 +1121,74 @@ mod test {
                 (8, 1),
                 vec![("test_property", COMPLETION_TYPE_PROPERTY)],
             ),
+            (
+                "class Test {
+                |  init() { this.#count = 0; }
+                |  #helper() {}
+                |  visible() {
+                |    this.
+                |  }
+                |}
+                |Test().",
+                (4, 8),
+                vec![
+                    ("#count", COMPLETION_TYPE_PROPERTY),
+                    ("#helper", COMPLETION_TYPE_METHOD),
+                    ("init", COMPLETION_TYPE_METHOD),
+                    ("visible", COMPLETION_TYPE_METHOD),
+                ],
+            ),
+            (
+                "class Test {
+                |  init() { this.#count = 0; }
+                |  #helper() {}
+                |  visible() {}
+                |}
+                |Test().",
+                (5, 6),
+                vec![
+                    ("init", COMPLETION_TYPE_METHOD),
+                    ("visible", COMPLETION_TYPE_METHOD),
+                ],
+            ),
+            (
+                "enum Color { Red, Green }
+                |Color.",
+                (1, 5),
+                vec![
+                    ("Green", COMPLETION_TYPE_ENUM_MEMBER),
+                    ("Red", COMPLETION_TYPE_ENUM_MEMBER),
+                ],
+            ),
+            (
+                "var n = 1.5;
+                |n.",
+                (1, 1),
+                vec![
+                    ("floor", COMPLETION_TYPE_METHOD),
+                    ("round", COMPLETION_TYPE_METHOD),
+                    ("toFixed", COMPLETION_TYPE_METHOD),
+                    ("toString", COMPLETION_TYPE_METHOD),
+                ],
+            ),
+            (
+                "var s = \"text\";
+                |s.",
+                (1, 1),
+                vec![
+                    ("chars", COMPLETION_TYPE_METHOD),
+                    ("indexOf", COMPLETION_TYPE_METHOD),
+                    ("iterator", COMPLETION_TYPE_METHOD),
+                    ("length", COMPLETION_TYPE_METHOD),
+                    ("lower", COMPLETION_TYPE_METHOD),
+                    ("replace", COMPLETION_TYPE_METHOD),
+                    ("split", COMPLETION_TYPE_METHOD),
+                    ("startsWith", COMPLETION_TYPE_METHOD),
+                    ("substring", COMPLETION_TYPE_METHOD),
+                    ("trim", COMPLETION_TYPE_METHOD),
+                    ("upper", COMPLETION_TYPE_METHOD),
+                ],
+            ),
         ];
         for (source, (line_number, line_offset), expected_completions) in tests {
             let result = provide_completions(
@@ -3,8 +3,15 @@ mod language;
 use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
 use std::convert;
+use std::rc::Rc;
 use wasm_bindgen::prelude::*;
 
+/// Wasm gets a 1MB stack, and the engine running it keeps its own, smaller
+/// stack for wasm calls, so scripts get far less room than the defaults in
+/// `lox::Options` assume.
+const MAX_CALL_DEPTH: usize = 200;
+const MAX_STACK_SIZE: usize = 256 * 1024;
+
 #[wasm_bindgen(module = "vscode")]
 extern "C" {
     type Window;
@@ -154,7 +161,89 @@ pub fn interpret(
     let reporter = build_reporter(js_report_message, js_report_diagnostic);
 
     console_log(&format!("interpreting: {text}"));
-    lox::interpret(&reporter, text);
+    lox::interpret_with_options(&reporter, text, options());
+}
+
+/// Interprets `text`, stopping after `max_steps` evaluations or as soon as
+/// `js_is_cancelled` returns `true`. Either limit is skipped when not given.
+/// `random_seed` and `fixed_clock`, when given, make `random()` and `clock()`
+/// return the same values on every run. Any number is a seed, as with the
+/// `seed()` native.
+#[wasm_bindgen]
+pub fn interpret_with_limits(
+    text: &str,
+    js_report_message: js_sys::Function,
+    js_report_diagnostic: js_sys::Function,
+    max_steps: Option<u32>,
+    js_is_cancelled: Option<js_sys::Function>,
+    random_seed: Option<f64>,
+    fixed_clock: Option<f64>,
+) {
+    let reporter = build_reporter(js_report_message, js_report_diagnostic);
+
+    let is_cancelled = js_is_cancelled.map(|js_is_cancelled| {
+        Rc::new(move || {
+            js_is_cancelled
+                .call0(&JsValue::null())
+                .map(|cancelled| cancelled.as_bool() == Some(true))
+                .unwrap_or(false)
+        }) as Rc<dyn Fn() -> bool>
+    });
+    let options = lox::Options {
+        max_steps: max_steps.map(|max_steps| max_steps as usize),
+        is_cancelled,
+        clock: fixed_clock.map(|seconds| Rc::new(move || seconds) as Rc<dyn Fn() -> f64>),
+        random_seed: random_seed.map(f64::to_bits),
+        ..options()
+    };
+
+    console_log(&format!("interpreting: {text}"));
+    lox::interpret_with_options(&reporter, text, options);
+}
+
+/// Evaluates successive pieces of source against the same globals.
+#[wasm_bindgen]
+pub struct Session {
+    session: lox::Session,
+}
+
+#[wasm_bindgen]
+impl Session {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self {
+            session: lox::Interpreter::builder()
+                .options(options())
+                .build()
+                .session(),
+        }
+    }
+
+    pub fn evaluate(
+        &mut self,
+        text: &str,
+        js_report_message: js_sys::Function,
+        js_report_diagnostic: js_sys::Function,
+    ) {
+        let reporter = build_reporter(js_report_message, js_report_diagnostic);
+
+        console_log(&format!("evaluating: {text}"));
+        self.session.evaluate(&reporter, text);
+    }
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn options() -> lox::Options {
+    lox::Options {
+        max_call_depth: MAX_CALL_DEPTH,
+        max_stack_size: MAX_STACK_SIZE,
+        ..lox::Options::default()
+    }
 }
 
 fn build_reporter(
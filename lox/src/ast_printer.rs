@@ -23,7 +23,13 @@ mod internal {
                 superclass,
                 methods,
             } => print_stmt_class(indent, name, superclass, methods),
+            stmt::Stmt::Enum { name, members } => print_stmt_enum(indent, name, members),
             stmt::Stmt::Expression { expression } => print_stmt_expr(indent, expression),
+            stmt::Stmt::ForIn {
+                name,
+                iterable,
+                body,
+            } => print_stmt_for_in(indent, name, iterable, body),
             stmt::Stmt::Function { function } => print_stmt_function(indent, function),
             stmt::Stmt::If {
                 condition,
@@ -34,6 +40,7 @@ mod internal {
             stmt::Stmt::Return { keyword, value } => print_stmt_return(indent, keyword, value),
             stmt::Stmt::Var { name, initialiser } => print_stmt_variable(indent, name, initialiser),
             stmt::Stmt::While { condition, body } => print_stmt_while(indent, condition, body),
+            stmt::Stmt::Yield { keyword, value } => print_stmt_yield(indent, keyword, value),
         }
     }
 
@@ -48,6 +55,19 @@ mod internal {
         result
     }
 
+    fn print_stmt_enum(
+        indent: usize,
+        name: &token::Token,
+        members: &LinkedList<token::Token>,
+    ) -> String {
+        let mut result = format!("{}(enum {}", indent_string(indent), name.lexeme);
+        for member in members {
+            result.push_str(&format!(" {}", member.lexeme));
+        }
+        result.push_str(")\n");
+        result
+    }
+
     fn print_stmt_class(
         indent: usize,
         name: &token::Token,
@@ -82,10 +102,28 @@ mod internal {
         )
     }
 
+    fn print_stmt_for_in(
+        indent: usize,
+        name: &token::Token,
+        iterable: &expr::Expr,
+        body: &stmt::Stmt,
+    ) -> String {
+        let mut result = format!(
+            "{}(for-in {} {}\n",
+            indent_string(indent),
+            name.lexeme,
+            print_expr(iterable),
+        );
+        result.push_str(&print_stmt(indent + 1, body));
+        result.push_str(&format!("{})\n", indent_string(indent)));
+        result
+    }
+
     fn print_stmt_function(indent: usize, function: &stmt::function::Function) -> String {
         let mut result = format!(
-            "{}(fun {}({})\n",
+            "{}(fun{} {}({})\n",
             indent_string(indent),
+            if function.is_generator() { "*" } else { "" },
             function.name().lexeme,
             function
                 .params()
@@ -176,6 +214,22 @@ mod internal {
         result
     }
 
+    fn print_stmt_yield(
+        indent: usize,
+        _keyword: &token::Token,
+        value: &Option<expr::Expr>,
+    ) -> String {
+        format!(
+            "{}{}\n",
+            indent_string(indent),
+            if let Some(value) = value {
+                parenthesize("yield", vec![value])
+            } else {
+                "(yield)".to_string()
+            }
+        )
+    }
+
     pub fn print_expr(expr: &expr::Expr) -> String {
         match expr {
             expr::Expr::Assign { name, value, .. } => print_expr_assign(name, value),
@@ -190,10 +244,13 @@ mod internal {
             } => print_expr_call(callee, arguments),
             expr::Expr::Get { object, name, .. } => print_expr_get(object, name),
             expr::Expr::Grouping { expression, .. } => print_expr_grouping(expression),
+            expr::Expr::Index { object, index, .. } => print_expr_index(object, index),
+            expr::Expr::Inner { .. } => print_expr_inner(),
             expr::Expr::InvalidGet { object, name, .. } => print_expr_get(object, name),
             expr::Expr::InvalidSuper {
                 keyword, method, ..
             } => print_expr_super(keyword, method),
+            expr::Expr::List { elements, .. } => print_expr_list(elements),
             expr::Expr::Literal { value, .. } => print_expr_literal(value),
             expr::Expr::Logical {
                 left,
@@ -201,12 +258,19 @@ mod internal {
                 right,
                 ..
             } => print_expr_logical(left, operator, right),
+            expr::Expr::Map { entries, .. } => print_expr_map(entries),
             expr::Expr::Set {
                 object,
                 name,
                 value,
                 ..
             } => print_expr_set(object, name, value),
+            expr::Expr::SetIndex {
+                object,
+                index,
+                value,
+                ..
+            } => print_expr_set_index(object, index, value),
             expr::Expr::Super {
                 keyword, method, ..
             } => print_expr_super(keyword, method),
@@ -248,6 +312,18 @@ mod internal {
         parenthesize("group", vec![expression])
     }
 
+    fn print_expr_inner() -> String {
+        "inner".to_string()
+    }
+
+    fn print_expr_index(object: &expr::Expr, index: &expr::Expr) -> String {
+        parenthesize("index", vec![object, index])
+    }
+
+    fn print_expr_list(elements: &[expr::Expr]) -> String {
+        parenthesize("list", elements.iter().collect())
+    }
+
     fn print_expr_literal(value: &token::Token) -> String {
         match &value.literal {
             Some(token::Literal::Number(n)) => n.to_string(),
@@ -276,6 +352,27 @@ mod internal {
         )
     }
 
+    fn print_expr_map(entries: &[(expr::Expr, expr::Expr)]) -> String {
+        format!(
+            "(map{}{})",
+            if !entries.is_empty() { " " } else { "" },
+            entries
+                .iter()
+                .map(|(key, value)| parenthesize(&print_expr(key), vec![value]))
+                .collect::<Vec<String>>()
+                .as_slice()
+                .join(" ")
+        )
+    }
+
+    fn print_expr_set_index(object: &expr::Expr, index: &expr::Expr, value: &expr::Expr) -> String {
+        format!(
+            "(= {} {})",
+            print_expr_index(object, index),
+            print_expr(value)
+        )
+    }
+
     fn print_expr_super(_keyword: &token::Token, method: &token::Token) -> String {
         format!("(super {})", method.lexeme,)
     }
@@ -0,0 +1,276 @@
+use crate::{interpreter, location, parser, reporter, resolver, scanner};
+use std::any::Any;
+use std::fmt::{Display, Formatter};
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+/// A class whose instances each hold a `T`, made by its constructor, and
+/// whose methods and properties are Rust closures working on that `T`. Lox
+/// classes can inherit from it; their `init` must call `super.init(...)`.
+///
+/// Methods looked up on an instance are bound to it, and the cycle collector
+/// frees an instance that stores its own bound method. It cannot see into the
+/// `T` itself, so values the Rust side holds there keep their cycles alive.
+pub struct NativeClass<T> {
+    name: String,
+    host: interpreter::HostClass,
+    data: PhantomData<T>,
+}
+
+impl<T: 'static> NativeClass<T> {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            host: interpreter::HostClass::default(),
+            data: PhantomData,
+        }
+    }
+
+    /// Builds the data for a new instance from the arguments the class is
+    /// called with. Calling a class without one fails with "'Name' has no
+    /// constructor", unless a Lox subclass defines `init`.
+    pub fn constructor<F>(mut self, arity: usize, constructor: F) -> Self
+    where
+        F: Fn(Vec<interpreter::Value>) -> Result<T, String> + 'static,
+    {
+        self.host.constructor = Some((
+            arity,
+            Rc::new(move |arguments| {
+                constructor(arguments).map(|data| Box::new(data) as Box<dyn Any>)
+            }),
+        ));
+        self
+    }
+
+    pub fn method<F>(mut self, name: &str, arity: usize, method: F) -> Self
+    where
+        F: Fn(&mut T, Vec<interpreter::Value>) -> Result<interpreter::Value, String> + 'static,
+    {
+        self.host.methods.insert(
+            name.to_string(),
+            (
+                arity,
+                Rc::new(move |data, arguments| method(downcast_mut(data)?, arguments)),
+            ),
+        );
+        self
+    }
+
+    /// A property read as `instance.name`. It is read-only unless it also
+    /// has a setter.
+    pub fn getter<F>(mut self, name: &str, getter: F) -> Self
+    where
+        F: Fn(&T) -> interpreter::Value + 'static,
+    {
+        self.host.getters.insert(
+            name.to_string(),
+            Rc::new(move |data| Ok(getter(downcast(data)?))),
+        );
+        self
+    }
+
+    pub fn setter<F>(mut self, name: &str, setter: F) -> Self
+    where
+        F: Fn(&mut T, interpreter::Value) -> Result<(), String> + 'static,
+    {
+        self.host.setters.insert(
+            name.to_string(),
+            Rc::new(move |data, value| setter(downcast_mut(data)?, value)),
+        );
+        self
+    }
+}
+
+fn downcast<T: 'static>(data: &dyn Any) -> Result<&T, String> {
+    data.downcast_ref::<T>()
+        .ok_or_else(|| "Instance holds data of another class".to_string())
+}
+
+fn downcast_mut<T: 'static>(data: &mut dyn Any) -> Result<&mut T, String> {
+    data.downcast_mut::<T>()
+        .ok_or_else(|| "Instance holds data of another class".to_string())
+}
+
+/// Configures an [`Interpreter`] for an application embedding Lox.
+#[derive(Default)]
+pub struct InterpreterBuilder {
+    options: interpreter::Options,
+    globals: Vec<(String, interpreter::Value)>,
+}
+
+impl InterpreterBuilder {
+    pub fn options(mut self, options: interpreter::Options) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Registers `function` as a global native called `name`. Lox code must
+    /// call it with exactly `arity` arguments, and an `Err` it returns fails
+    /// the call with that message. A native named after a built-in, such as
+    /// `clock`, replaces it.
+    pub fn native<F>(mut self, name: &str, arity: usize, function: F) -> Self
+    where
+        F: Fn(Vec<interpreter::Value>) -> Result<interpreter::Value, String> + 'static,
+    {
+        let native = interpreter::host_function(name, arity, Rc::new(function));
+        self.globals.push((name.to_string(), native));
+        self
+    }
+
+    /// Registers `class` as a global under its name.
+    pub fn class<T: 'static>(mut self, class: NativeClass<T>) -> Self {
+        let value = interpreter::host_class(&class.name, class.host);
+        self.globals.push((class.name, value));
+        self
+    }
+
+    pub fn build(self) -> Interpreter {
+        Interpreter {
+            options: self.options,
+            globals: self.globals,
+        }
+    }
+}
+
+/// Runs Lox source with the natives and options it was built with.
+pub struct Interpreter {
+    options: interpreter::Options,
+    globals: Vec<(String, interpreter::Value)>,
+}
+
+impl Interpreter {
+    pub fn builder() -> InterpreterBuilder {
+        InterpreterBuilder::default()
+    }
+
+    /// Runs `source` from fresh globals, reporting any errors to `reporter`.
+    pub fn interpret(&self, reporter: &dyn reporter::Reporter, source: &str) {
+        self.session().evaluate(reporter, source);
+    }
+
+    /// Starts a session, whose globals carry over from one evaluation to the
+    /// next.
+    pub fn session(&self) -> Session {
+        Session {
+            resolver: resolver::Globals::default(),
+            state: interpreter::State::new(self.options.clone(), &self.globals),
+        }
+    }
+}
+
+/// Evaluates successive pieces of Lox source, such as the lines of a REPL,
+/// against the same globals.
+pub struct Session {
+    resolver: resolver::Globals,
+    state: interpreter::State,
+}
+
+impl Session {
+    /// A session with the default options and built-in natives only.
+    pub fn new() -> Self {
+        Interpreter::builder().build().session()
+    }
+
+    /// Scans, parses, resolves and runs `source`, reporting any errors to
+    /// `reporter`. Nothing runs if an earlier phase reports an error, and
+    /// declarations that fail to resolve are not kept.
+    pub fn evaluate(&mut self, reporter: &dyn reporter::Reporter, source: &str) {
+        let tokens = scanner::scan_tokens(reporter, source);
+        if reporter.has_diagnostics() {
+            reporter.add_message("[parser] not parsing due to scan errors");
+            return;
+        }
+
+        let statements = parser::parse(reporter, tokens);
+        if reporter.has_diagnostics() {
+            reporter.add_message("[interpreter] not interpreting due to parsing errors");
+            return;
+        }
+
+        resolver::resolve_with_globals(reporter, &mut self.resolver, &statements);
+        if reporter.has_diagnostics() {
+            reporter.add_message("[interpreter] not interpreting due to resolver errors");
+            return;
+        }
+
+        self.state.run(reporter, statements);
+    }
+
+    /// The value of the global `name`, e.g. a function declared by an
+    /// earlier evaluation.
+    pub fn global(&self, name: &str) -> Option<interpreter::Value> {
+        self.state.global(name)
+    }
+
+    /// Calls a Lox function, class or native with `arguments`, such as a hook
+    /// a script declared.
+    pub fn call(
+        &mut self,
+        reporter: &dyn reporter::Reporter,
+        callee: &interpreter::Value,
+        arguments: Vec<interpreter::Value>,
+    ) -> Result<interpreter::Value, CallError> {
+        self.state
+            .call(reporter, callee, arguments)
+            .map_err(CallError::from)
+    }
+
+    /// Calls the method `name` of `object`.
+    pub fn call_method(
+        &mut self,
+        reporter: &dyn reporter::Reporter,
+        object: &interpreter::Value,
+        name: &str,
+        arguments: Vec<interpreter::Value>,
+    ) -> Result<interpreter::Value, CallError> {
+        let method = self.state.property(object, name).map_err(CallError::from)?;
+        self.call(reporter, &method, arguments)
+    }
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Why a call made from Rust failed.
+#[derive(Debug, PartialEq)]
+pub struct CallError {
+    pub message: String,
+    /// Where in the source the error was raised, or `None` when the call
+    /// itself failed, e.g. with the wrong number of arguments.
+    pub location: Option<(location::FileLocation, location::FileLocation)>,
+    /// The Lox calls the error unwound through, innermost first.
+    pub stack: Vec<interpreter::Frame>,
+}
+
+impl From<interpreter::Unwind> for CallError {
+    fn from(unwind: interpreter::Unwind) -> Self {
+        match unwind {
+            interpreter::Unwind::WithRuntimeError(error) => Self {
+                message: error.message,
+                location: Some((error.start, error.end)),
+                stack: error.stack,
+            },
+            interpreter::Unwind::WithError(message) => Self {
+                message,
+                location: None,
+                stack: Vec::new(),
+            },
+            _ => Self {
+                message: "Unexpected return or yield".to_string(),
+                location: None,
+                stack: Vec::new(),
+            },
+        }
+    }
+}
+
+impl Display for CallError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for CallError {}
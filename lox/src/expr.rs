@@ -1,90 +1,103 @@
-use crate::{expr, location, token};
-use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::{expr, location, resolver, token};
+use std::cell::Cell;
 
-static ID_SRC: AtomicUsize = AtomicUsize::new(1);
+/// Where the resolver found the variable an expression refers to, or `None`
+/// for a global. Filled in by the resolver after parsing.
+pub type Resolution = Cell<Option<resolver::Local>>;
 
 #[derive(Debug)]
 pub enum Expr {
     Assign {
-        id: usize,
+        local: Resolution,
         name: token::Token,
         value: Box<Expr>,
     },
     Binary {
-        id: usize,
         left: Box<Expr>,
         operator: token::Token,
         right: Box<Expr>,
     },
     Call {
-        id: usize,
         callee: Box<Expr>,
         paren: token::Token,
         arguments: Vec<Expr>,
     },
     Get {
-        id: usize,
         object: Box<Expr>,
         name: token::Token,
     },
     Grouping {
-        id: usize,
         expression: Box<Expr>,
     },
+    Index {
+        object: Box<Expr>,
+        bracket: token::Token,
+        index: Box<Expr>,
+    },
+    Inner {
+        local: Resolution,
+        keyword: token::Token,
+    },
     InvalidGet {
-        id: usize,
         object: Box<Expr>,
         name: token::Token,
     },
     InvalidSuper {
-        id: usize,
         keyword: token::Token,
         method: token::Token,
     },
+    List {
+        left_bracket: token::Token,
+        elements: Vec<Expr>,
+        right_bracket: token::Token,
+    },
     Literal {
-        id: usize,
         value: token::Token,
     },
     Logical {
-        id: usize,
         left: Box<Expr>,
         operator: token::Token,
         right: Box<Expr>,
     },
+    Map {
+        left_brace: token::Token,
+        entries: Vec<(Expr, Expr)>,
+        right_brace: token::Token,
+    },
     Set {
-        id: usize,
         object: Box<Expr>,
         name: token::Token,
         value: Box<Expr>,
     },
+    SetIndex {
+        object: Box<Expr>,
+        bracket: token::Token,
+        index: Box<Expr>,
+        value: Box<Expr>,
+    },
     Super {
-        id: usize,
+        local: Resolution,
         keyword: token::Token,
         method: token::Token,
     },
     This {
-        id: usize,
+        local: Resolution,
         keyword: token::Token,
     },
     Unary {
-        id: usize,
         operator: token::Token,
         right: Box<Expr>,
     },
     Variable {
-        id: usize,
+        local: Resolution,
         name: token::Token,
     },
 }
 
 impl Expr {
-    fn get_id() -> usize {
-        ID_SRC.fetch_add(1, Ordering::Relaxed)
-    }
-
     pub fn new_assign(name: token::Token, value: Expr) -> Self {
         Expr::Assign {
-            id: Expr::get_id(),
+            local: Resolution::default(),
             name,
             value: Box::new(value),
         }
@@ -92,7 +105,6 @@ impl Expr {
 
     pub fn new_binary(left: Expr, operator: token::Token, right: Expr) -> Self {
         Expr::Binary {
-            id: Expr::get_id(),
             left: Box::new(left),
             operator,
             right: Box::new(right),
@@ -101,7 +113,6 @@ impl Expr {
 
     pub fn new_call(callee: Expr, paren: token::Token, arguments: Vec<Expr>) -> Self {
         Expr::Call {
-            id: Expr::get_id(),
             callee: Box::new(callee),
             paren,
             arguments,
@@ -110,7 +121,6 @@ impl Expr {
 
     pub fn new_get(object: Expr, name: token::Token) -> Self {
         Expr::Get {
-            id: Expr::get_id(),
             object: Box::new(object),
             name,
         }
@@ -118,55 +128,92 @@ impl Expr {
 
     pub fn new_grouping(expression: Expr) -> Self {
         Expr::Grouping {
-            id: Expr::get_id(),
             expression: Box::new(expression),
         }
     }
 
+    pub fn new_index(object: Expr, bracket: token::Token, index: Expr) -> Self {
+        Expr::Index {
+            object: Box::new(object),
+            bracket,
+            index: Box::new(index),
+        }
+    }
+
+    pub fn new_inner(keyword: token::Token) -> Self {
+        Expr::Inner {
+            local: Resolution::default(),
+            keyword,
+        }
+    }
+
     pub fn new_invalid_get(object: Expr, name: token::Token) -> Self {
         Expr::InvalidGet {
-            id: Expr::get_id(),
             object: Box::new(object),
             name,
         }
     }
 
     pub fn new_invalid_super(keyword: token::Token, method: token::Token) -> Self {
-        Expr::InvalidSuper {
-            id: Expr::get_id(),
-            keyword,
-            method,
+        Expr::InvalidSuper { keyword, method }
+    }
+
+    pub fn new_list(
+        left_bracket: token::Token,
+        elements: Vec<Expr>,
+        right_bracket: token::Token,
+    ) -> Self {
+        Expr::List {
+            left_bracket,
+            elements,
+            right_bracket,
         }
     }
 
     pub fn new_literal(value: token::Token) -> Self {
-        Expr::Literal {
-            id: Expr::get_id(),
-            value,
-        }
+        Expr::Literal { value }
     }
 
     pub fn new_logical(left: Expr, operator: token::Token, right: Expr) -> Self {
         Expr::Logical {
-            id: Expr::get_id(),
             left: Box::new(left),
             operator,
             right: Box::new(right),
         }
     }
 
+    pub fn new_map(
+        left_brace: token::Token,
+        entries: Vec<(Expr, Expr)>,
+        right_brace: token::Token,
+    ) -> Self {
+        Expr::Map {
+            left_brace,
+            entries,
+            right_brace,
+        }
+    }
+
     pub fn new_set(object: Expr, name: token::Token, value: Expr) -> Self {
         Expr::Set {
-            id: Expr::get_id(),
             object: Box::new(object),
             name,
             value: Box::new(value),
         }
     }
 
+    pub fn new_set_index(object: Expr, bracket: token::Token, index: Expr, value: Expr) -> Self {
+        Expr::SetIndex {
+            object: Box::new(object),
+            bracket,
+            index: Box::new(index),
+            value: Box::new(value),
+        }
+    }
+
     pub fn new_super(keyword: token::Token, method: token::Token) -> Self {
         Expr::Super {
-            id: Expr::get_id(),
+            local: Resolution::default(),
             keyword,
             method,
         }
@@ -174,14 +221,13 @@ impl Expr {
 
     pub fn new_this(keyword: token::Token) -> Self {
         Expr::This {
-            id: Expr::get_id(),
+            local: Resolution::default(),
             keyword,
         }
     }
 
     pub fn new_unary(operator: token::Token, right: Expr) -> Self {
         Expr::Unary {
-            id: Expr::get_id(),
             operator,
             right: Box::new(right),
         }
@@ -189,7 +235,7 @@ impl Expr {
 
     pub fn new_variable(name: token::Token) -> Self {
         Expr::Variable {
-            id: Expr::get_id(),
+            local: Resolution::default(),
             name,
         }
     }
@@ -202,11 +248,16 @@ fn get_start_location(expr: &expr::Expr) -> &location::FileLocation {
         expr::Expr::Call { callee, .. } => get_start_location(callee),
         expr::Expr::Get { object, .. } => get_start_location(object),
         expr::Expr::Grouping { expression, .. } => get_start_location(expression),
+        expr::Expr::Index { object, .. } => get_start_location(object),
+        expr::Expr::Inner { keyword, .. } => &keyword.start,
         expr::Expr::InvalidGet { object, .. } => get_start_location(object),
         expr::Expr::InvalidSuper { keyword, .. } => &keyword.start,
+        expr::Expr::List { left_bracket, .. } => &left_bracket.start,
         expr::Expr::Literal { value, .. } => &value.start,
         expr::Expr::Logical { left, .. } => get_start_location(left),
+        expr::Expr::Map { left_brace, .. } => &left_brace.start,
         expr::Expr::Set { object, .. } => get_start_location(object),
+        expr::Expr::SetIndex { object, .. } => get_start_location(object),
         expr::Expr::Super { keyword, .. } => &keyword.start,
         expr::Expr::This { keyword, .. } => &keyword.start,
         expr::Expr::Unary { operator, .. } => &operator.start,
@@ -221,11 +272,16 @@ fn get_end_location(expr: &expr::Expr) -> &location::FileLocation {
         expr::Expr::Call { paren, .. } => &paren.end,
         expr::Expr::Get { name, .. } => &name.end,
         expr::Expr::Grouping { expression, .. } => get_end_location(expression),
+        expr::Expr::Index { bracket, .. } => &bracket.end,
+        expr::Expr::Inner { keyword, .. } => &keyword.end,
         expr::Expr::InvalidGet { name, .. } => &name.end,
         expr::Expr::InvalidSuper { method, .. } => &method.end,
+        expr::Expr::List { right_bracket, .. } => &right_bracket.end,
         expr::Expr::Literal { value, .. } => &value.end,
         expr::Expr::Logical { right, .. } => get_end_location(right),
+        expr::Expr::Map { right_brace, .. } => &right_brace.end,
         expr::Expr::Set { value, .. } => get_end_location(value),
+        expr::Expr::SetIndex { value, .. } => get_end_location(value),
         expr::Expr::Super { method, .. } => &method.end,
         expr::Expr::This { keyword, .. } => &keyword.end,
         expr::Expr::Unary { right, .. } => get_end_location(right),
@@ -243,7 +299,7 @@ impl location::ProvideLocation for Expr {
     }
 }
 
-impl<'a> location::ProvideLocation for &'a Expr {
+impl location::ProvideLocation for &Expr {
     fn start(&self) -> &location::FileLocation {
         get_start_location(self)
     }
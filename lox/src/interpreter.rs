@@ -1,79 +1,263 @@
 mod class;
+mod enumeration;
 mod environment;
 mod function;
+mod gc;
+mod generator;
+mod host_class;
 mod instance;
+mod iterator;
+mod json;
+mod list;
 mod lox_type;
+mod map;
 mod native_functions;
+mod native_methods;
+mod runtime;
 mod unwind;
+mod value;
+#[cfg(feature = "serde")]
+mod value_serde;
+
+pub use host_class::HostClass;
+pub use native_functions::HostFunction;
+pub use runtime::Capability;
+pub use runtime::Options;
+pub use unwind::Frame;
+pub use unwind::Unwind;
+pub use value::Value;
+#[cfg(feature = "serde")]
+pub use value_serde::{from_value, to_value, Error as ValueError};
 
 use crate::interpreter::lox_type::Callable;
-use crate::{expr, location, reporter, stmt, token};
+use crate::{expr, location, reporter, resolver, stmt, token};
+use std::cell::RefCell;
 use std::collections::{HashMap, LinkedList};
 
-pub fn interpret(
-    reporter: &dyn reporter::Reporter,
-    depths: &HashMap<usize, usize>,
-    statements: LinkedList<stmt::Stmt>,
-) {
-    let mut environment = environment::Environment::new();
-    Interpreter::define_native_functions(&mut environment);
+/// Defines a native backed by a Rust closure, for use as a global.
+pub fn host_function(name: &str, arity: usize, function: HostFunction) -> Value {
+    native_functions::host(name, arity, function)
+}
+
+/// Defines a class implemented by the embedding application, for use as a
+/// global.
+pub fn host_class(name: &str, host: HostClass) -> Value {
+    Value(lox_type::LoxType::Class {
+        class: class::Class::new_host(name, host),
+    })
+}
+
+/// The global environment and runtime kept between runs, so that
+/// later input can use what earlier input declared.
+pub struct State {
+    runtime: runtime::Runtime,
+    environment: environment::Environment,
+}
+
+impl State {
+    pub fn new(options: Options, globals: &[(String, Value)]) -> Self {
+        let mut environment = environment::Environment::new();
+        Interpreter::define_native_functions(&mut environment);
+        for (name, value) in globals {
+            environment.define(name, value.0.clone());
+        }
+        Self {
+            runtime: runtime::Runtime::new(options),
+            environment,
+        }
+    }
+
+    pub fn run(&mut self, reporter: &dyn reporter::Reporter, statements: LinkedList<stmt::Stmt>) {
+        self.runtime.begin();
+        match interpret_with_environment(
+            reporter,
+            &self.runtime,
+            &mut self.environment,
+            &statements,
+        ) {
+            Err(unwind::Unwind::WithRuntimeError(error)) => report_runtime_error(reporter, &error),
+            Err(unwind::Unwind::WithError(message)) => reporter.add_message(&message),
+            _ => (),
+        }
+    }
+
+    pub fn global(&self, name: &str) -> Option<Value> {
+        self.environment.get_at(None, name).ok().map(Value)
+    }
+
+    /// Calls `callee` from outside any Lox code, with a fresh step budget.
+    pub fn call(
+        &mut self,
+        reporter: &dyn reporter::Reporter,
+        callee: &Value,
+        arguments: Vec<Value>,
+    ) -> Result<Value, unwind::Unwind> {
+        self.runtime.begin();
+        let arguments = arguments.into_iter().map(|argument| argument.0).collect();
+        call(reporter, &self.runtime, &callee.0, arguments).map(Value)
+    }
+
+    /// Looks up `name` on `object`, as `object.name` would.
+    pub fn property(&self, object: &Value, name: &str) -> Result<Value, unwind::Unwind> {
+        lox_type::LoxType::get_property(&object.0, name).map(Value)
+    }
+}
 
-    let _ = interpret_with_environment(reporter, depths, &mut environment, &statements);
+/// Reports the error where it was raised, followed by the call site of each
+/// function it unwound through. Runs of the same frame, as left by deep
+/// recursion, are reported once.
+fn report_runtime_error(reporter: &dyn reporter::Reporter, error: &unwind::RuntimeError) {
+    reporter.add_diagnostic(&error.start, &error.end, &error.message);
+    let mut frames = error.stack.iter().peekable();
+    while let Some(frame) = frames.next() {
+        let mut repeats = 0;
+        while frames.next_if_eq(&frame).is_some() {
+            repeats += 1;
+        }
+        let message = if repeats > 0 {
+            format!(
+                "in call to '{}' (repeated {} more times)",
+                frame.function, repeats
+            )
+        } else {
+            format!("in call to '{}'", frame.function)
+        };
+        reporter.add_diagnostic(&frame.start, &frame.end, &message);
+    }
 }
 
 fn interpret_with_environment(
     reporter: &dyn reporter::Reporter,
-    depths: &HashMap<usize, usize>,
+    runtime: &runtime::Runtime,
     environment: &mut environment::Environment,
     statements: &LinkedList<stmt::Stmt>,
 ) -> Result<(), unwind::Unwind> {
-    let interpreter = Interpreter::new(reporter, depths, statements);
+    let interpreter = Interpreter::new(reporter, runtime, statements);
 
     interpreter.interpret_statements(environment)
 }
 
+/// Runs a generator body, picking up from the frames saved by its last
+/// `yield`, and hands back the frames saved by the next one.
+fn resume_with_environment(
+    reporter: &dyn reporter::Reporter,
+    runtime: &runtime::Runtime,
+    environment: &mut environment::Environment,
+    statements: &LinkedList<stmt::Stmt>,
+    resume: Vec<generator::Resume>,
+) -> (Result<(), unwind::Unwind>, Vec<generator::Resume>) {
+    let interpreter = Interpreter::new(reporter, runtime, statements);
+    interpreter.resume.replace(resume);
+
+    let result = interpreter.interpret_statements(environment);
+    (result, interpreter.resume.take())
+}
+
 struct Interpreter<'r> {
     reporter: &'r dyn reporter::Reporter,
-    depths: &'r HashMap<usize, usize>,
+    runtime: &'r runtime::Runtime,
     statements: &'r LinkedList<stmt::Stmt>,
+    resume: RefCell<Vec<generator::Resume>>,
 }
 
 impl<'r> Interpreter<'r> {
     fn new(
         reporter: &'r dyn reporter::Reporter,
-        depths: &'r HashMap<usize, usize>,
+        runtime: &'r runtime::Runtime,
         statements: &'r LinkedList<stmt::Stmt>,
     ) -> Self {
         Self {
             reporter,
-            depths,
+            runtime,
             statements,
+            resume: RefCell::new(Vec::new()),
         }
     }
 
     fn define_native_functions(environment: &mut environment::Environment) {
         environment.define("clock", native_functions::clock());
+        environment.define("range", native_functions::range());
+        environment.define("typeof", native_functions::type_of());
+        environment.define("classOf", native_functions::class_of());
+        environment.define("fields", native_functions::fields());
+        environment.define("methods", native_functions::methods());
+        environment.define("superclassOf", native_functions::superclass_of());
+        environment.define("sqrt", native_functions::sqrt());
+        environment.define("pow", native_functions::pow());
+        environment.define("floor", native_functions::floor());
+        environment.define("abs", native_functions::abs());
+        environment.define("min", native_functions::min());
+        environment.define("max", native_functions::max());
+        environment.define("num", native_functions::num());
+        environment.define("str", native_functions::str());
+        environment.define("len", native_functions::len());
+        environment.define("chr", native_functions::chr());
+        environment.define("ord", native_functions::ord());
+        environment.define("random", native_functions::random());
+        environment.define("seed", native_functions::seed());
+        environment.define("assert", native_functions::assert());
+        environment.define("readFile", native_functions::read_file());
+        environment.define("writeFile", native_functions::write_file());
+        environment.define("listDir", native_functions::list_dir());
+        environment.define("getEnv", native_functions::get_env());
+        environment.define("args", native_functions::args());
+        environment.define("readLine", native_functions::read_line());
+        environment.define("map", native_functions::map());
+        environment.define("filter", native_functions::filter());
+        environment.define("reduce", native_functions::reduce());
+        environment.define("forEach", native_functions::for_each());
+        environment.define("sort", native_functions::sort());
+        environment.define("jsonParse", native_functions::json_parse());
+        environment.define("jsonStringify", native_functions::json_stringify());
     }
 
     fn interpret_statements(
         &self,
         environment: &mut environment::Environment,
     ) -> Result<(), unwind::Unwind> {
-        for statement in self.statements {
-            match self.evaluate_stmt(environment, statement) {
-                Err(unwind::Unwind::WithError(message)) => {
-                    self.reporter.add_message(&message);
-                    return Err(unwind::Unwind::WithError(message));
-                }
-                Err(unwind::Unwind::WithResult(value)) => {
-                    return Err(unwind::Unwind::WithResult(value));
-                }
-                _ => (),
+        self.evaluate_statements(environment.clone(), self.statements)
+    }
+
+    fn evaluate_statements(
+        &self,
+        mut environment: environment::Environment,
+        statements: &LinkedList<stmt::Stmt>,
+    ) -> Result<(), unwind::Unwind> {
+        let start = match self.resume_frame() {
+            Some(generator::Resume::Statements {
+                index,
+                environment: suspended,
+            }) => {
+                environment = suspended;
+                index
+            }
+            _ => 0,
+        };
+
+        for (index, statement) in statements.iter().enumerate().skip(start) {
+            if let Err(unwind) = self.evaluate_stmt(&mut environment, statement) {
+                self.suspend(&unwind, || generator::Resume::Statements {
+                    index,
+                    environment: environment.clone(),
+                });
+                return Err(unwind);
             }
         }
         Ok(())
     }
 
+    /// The frame for the statement being resumed, if a generator is resuming.
+    fn resume_frame(&self) -> Option<generator::Resume> {
+        self.resume.borrow_mut().pop()
+    }
+
+    /// Records how to get back into the statement a `yield` is unwinding through.
+    fn suspend(&self, unwind: &unwind::Unwind, frame: impl FnOnce() -> generator::Resume) {
+        if matches!(unwind, unwind::Unwind::Yield(_)) {
+            self.resume.borrow_mut().push(frame());
+        }
+    }
+
     fn evaluate_stmt(
         &self,
         environment: &mut environment::Environment,
@@ -86,9 +270,17 @@ impl<'r> Interpreter<'r> {
                 superclass,
                 methods,
             } => self.evalute_stmt_class(environment, name, superclass, methods),
+            stmt::Stmt::Enum { name, members } => {
+                self.evaluate_stmt_enum(environment, name, members)
+            }
             stmt::Stmt::Expression { expression } => {
                 self.evaluate_stmt_expression(environment, expression)
             }
+            stmt::Stmt::ForIn {
+                name,
+                iterable,
+                body,
+            } => self.evaluate_stmt_for_in(environment, name, iterable, body),
             stmt::Stmt::Function { function } => self.evaluate_stmt_function(environment, function),
             stmt::Stmt::If {
                 condition,
@@ -105,6 +297,7 @@ impl<'r> Interpreter<'r> {
             stmt::Stmt::While { condition, body } => {
                 self.evaluate_stmt_while(environment, condition, body)
             }
+            stmt::Stmt::Yield { value, .. } => self.evaluate_stmt_yield(environment, value),
         }
     }
 
@@ -113,11 +306,8 @@ impl<'r> Interpreter<'r> {
         environment: &mut environment::Environment,
         statements: &LinkedList<stmt::Stmt>,
     ) -> Result<(), unwind::Unwind> {
-        let mut environment = environment::Environment::new_with_enclosing(environment);
-        for statement in statements {
-            self.evaluate_stmt(&mut environment, statement)?;
-        }
-        Ok(())
+        let environment = environment::Environment::new_with_enclosing(environment);
+        self.evaluate_statements(environment, statements)
     }
 
     fn evalute_stmt_class(
@@ -132,35 +322,28 @@ impl<'r> Interpreter<'r> {
             if matches!(superclass_type, lox_type::LoxType::Class { .. }) {
                 Some(superclass_type)
             } else {
-                self.add_diagnostic(superclass, "Superclass must be a class".to_string())?;
+                self.runtime_error(superclass, "Superclass must be a class".to_string())?;
                 None
             }
         } else {
             None
         };
 
-        environment.define(&name.lexeme, lox_type::LoxType::Nil);
-
-        let mut super_environment = if let Some(ref superclass) = superclass {
+        let super_environment = superclass.as_ref().map(|superclass| {
             let mut environment = environment::Environment::new_with_enclosing(environment);
             environment.define("super", superclass.clone());
-            Some(environment)
-        } else {
-            None
-        };
-
-        let (environment, depth) = if let Some(super_environment) = &mut super_environment {
-            (super_environment, Some(1))
-        } else {
-            (environment, Some(0))
-        };
+            environment
+        });
+        let method_environment = super_environment.as_ref().unwrap_or(environment);
 
+        // The methods share the scope the class is defined in, so they see its
+        // name once it is defined below.
         let methods = methods
             .iter()
             .map(|method| {
                 if let stmt::Stmt::Function { function } = method {
                     let f = function::Function::new(
-                        environment,
+                        method_environment,
                         function.clone(),
                         function.name().lexeme == "init",
                     );
@@ -175,8 +358,19 @@ impl<'r> Interpreter<'r> {
             .collect::<HashMap<String, lox_type::LoxType>>();
 
         let class = class::Class::new(&name.lexeme, methods, superclass);
-        let class = lox_type::LoxType::Class { class };
-        let _ = environment.assign_at(depth, &name.lexeme, class);
+        environment.define(&name.lexeme, lox_type::LoxType::Class { class });
+        Ok(())
+    }
+
+    fn evaluate_stmt_enum(
+        &self,
+        environment: &mut environment::Environment,
+        name: &token::Token,
+        members: &LinkedList<token::Token>,
+    ) -> Result<(), unwind::Unwind> {
+        let members = members.iter().map(|member| member.lexeme.clone()).collect();
+        let enumeration = enumeration::Enum::new(&name.lexeme, members);
+        environment.define(&name.lexeme, lox_type::LoxType::Enum { enumeration });
         Ok(())
     }
 
@@ -194,6 +388,64 @@ impl<'r> Interpreter<'r> {
         }
     }
 
+    fn evaluate_stmt_for_in(
+        &self,
+        environment: &mut environment::Environment,
+        name: &token::Token,
+        iterable: &expr::Expr,
+        body: &stmt::Stmt,
+    ) -> Result<(), unwind::Unwind> {
+        let (iterator, mut suspended) = match self.resume_frame() {
+            Some(generator::Resume::ForIn {
+                iterator,
+                environment,
+            }) => (iterator, Some(environment)),
+            _ => (self.evaluate_iterator(environment, iterable)?, None),
+        };
+
+        loop {
+            let mut environment = match suspended.take() {
+                Some(environment) => environment,
+                None => {
+                    if !is_truthy(&self.call_method(iterable, &iterator, "hasNext")?) {
+                        break;
+                    }
+                    let value = self.call_method(iterable, &iterator, "next")?;
+                    let mut environment = environment::Environment::new_with_enclosing(environment);
+                    environment.define(&name.lexeme, value);
+                    environment
+                }
+            };
+            if let Err(unwind) = self.evaluate_stmt(&mut environment, body) {
+                self.suspend(&unwind, || generator::Resume::ForIn {
+                    iterator: iterator.clone(),
+                    environment,
+                });
+                return Err(unwind);
+            }
+        }
+        Ok(())
+    }
+
+    fn evaluate_iterator(
+        &self,
+        environment: &mut environment::Environment,
+        iterable: &expr::Expr,
+    ) -> Result<lox_type::LoxType, unwind::Unwind> {
+        let value = self.evaluate_expr(environment, iterable)?;
+        match lox_type::LoxType::get_property(&value, "iterator") {
+            Ok(method) => self.call_function(method, iterable, Vec::new()),
+            Err(unwind::Unwind::WithError(message)) => {
+                if matches!(value, lox_type::LoxType::Instance { .. }) {
+                    self.runtime_error(iterable, message)
+                } else {
+                    self.runtime_error(iterable, "Can only iterate over iterables".to_string())
+                }
+            }
+            Err(unwind) => Err(unwind),
+        }
+    }
+
     fn evaluate_stmt_function(
         &self,
         environment: &mut environment::Environment,
@@ -213,10 +465,23 @@ impl<'r> Interpreter<'r> {
         then_branch: &stmt::Stmt,
         else_branch: &Option<stmt::Stmt>,
     ) -> Result<(), unwind::Unwind> {
-        if is_truthy(&self.evaluate_expr(environment, condition)?) {
-            self.evaluate_stmt(environment, then_branch)?;
-        } else if let Some(else_branch) = else_branch {
-            self.evaluate_stmt(environment, else_branch)?;
+        let take_then_branch = match self.resume_frame() {
+            Some(generator::Resume::If { then_branch }) => then_branch,
+            _ => is_truthy(&self.evaluate_expr(environment, condition)?),
+        };
+
+        let branch = if take_then_branch {
+            Some(then_branch)
+        } else {
+            else_branch.as_ref()
+        };
+        if let Some(branch) = branch {
+            if let Err(unwind) = self.evaluate_stmt(environment, branch) {
+                self.suspend(&unwind, || generator::Resume::If {
+                    then_branch: take_then_branch,
+                });
+                return Err(unwind);
+            }
         }
         Ok(())
     }
@@ -227,7 +492,11 @@ impl<'r> Interpreter<'r> {
         expr: &expr::Expr,
     ) -> Result<(), unwind::Unwind> {
         let result = self.evaluate_expr(environment, expr)?;
-        self.reporter.add_message(&format!("[print] {}", result));
+        let text = self.print_text(expr, &result)?;
+        match self.runtime.output() {
+            Some(output) => output.print(&text),
+            None => self.reporter.add_message(&format!("[print] {text}")),
+        }
         Ok(())
     }
 
@@ -266,21 +535,48 @@ impl<'r> Interpreter<'r> {
         condition: &expr::Expr,
         body: &stmt::Stmt,
     ) -> Result<(), unwind::Unwind> {
-        while is_truthy(&(self.evaluate_expr(environment, condition)?)) {
-            self.evaluate_stmt(environment, body)?;
+        let mut resuming = matches!(self.resume_frame(), Some(generator::Resume::While));
+        while resuming || is_truthy(&(self.evaluate_expr(environment, condition)?)) {
+            resuming = false;
+            if let Err(unwind) = self.evaluate_stmt(environment, body) {
+                self.suspend(&unwind, || generator::Resume::While);
+                return Err(unwind);
+            }
         }
         Ok(())
     }
 
+    fn evaluate_stmt_yield(
+        &self,
+        environment: &mut environment::Environment,
+        value: &Option<expr::Expr>,
+    ) -> Result<(), unwind::Unwind> {
+        if let Some(generator::Resume::Yield) = self.resume_frame() {
+            return Ok(());
+        }
+
+        let value = if let Some(value) = value {
+            self.evaluate_expr(environment, value)?
+        } else {
+            lox_type::LoxType::Nil
+        };
+        let unwind = unwind::Unwind::Yield(value);
+        self.suspend(&unwind, || generator::Resume::Yield);
+        Err(unwind)
+    }
+
     fn evaluate_expr(
         &self,
         environment: &mut environment::Environment,
         expression: &expr::Expr,
     ) -> Result<lox_type::LoxType, unwind::Unwind> {
+        if let Err(unwind::Unwind::WithError(message)) = self.runtime.step() {
+            return self.runtime_error(expression, message);
+        }
         match expression {
             expr::Expr::Assign {
-                id, name, value, ..
-            } => self.evaluate_expr_assign(environment, expression, id, name, value),
+                local, name, value, ..
+            } => self.evaluate_expr_assign(environment, expression, local, name, value),
             expr::Expr::Binary {
                 left,
                 operator,
@@ -294,15 +590,22 @@ impl<'r> Interpreter<'r> {
                 ..
             } => self.evaluate_expr_call(environment, callee, paren, arguments),
             expr::Expr::Get { object, name, .. } => {
-                self.evaluate_expr_get(environment, object, name)
+                self.evaluate_expr_get(environment, object, name, false)
             }
             expr::Expr::Grouping { expression, .. } => self.evaluate_expr(environment, expression),
+            expr::Expr::Index { object, index, .. } => {
+                self.evaluate_expr_index(environment, expression, object, index)
+            }
+            expr::Expr::Inner { local, keyword } => {
+                self.evaluate_expr_inner(environment, local, keyword)
+            }
             expr::Expr::InvalidGet { .. } => {
                 unreachable!()
             }
             expr::Expr::InvalidSuper { .. } => {
                 unreachable!()
             }
+            expr::Expr::List { elements, .. } => self.evaluate_expr_list(environment, elements),
             expr::Expr::Literal { value, .. } => self.evaluate_expr_literal(expression, value),
             expr::Expr::Logical {
                 left,
@@ -310,26 +613,33 @@ impl<'r> Interpreter<'r> {
                 right,
                 ..
             } => self.evaluate_expr_logical(environment, left, operator, right),
+            expr::Expr::Map { entries, .. } => self.evaluate_expr_map(environment, entries),
             expr::Expr::Set {
                 object,
                 name,
                 value,
                 ..
             } => self.evaluate_expr_set(environment, object, name, value),
+            expr::Expr::SetIndex {
+                object,
+                index,
+                value,
+                ..
+            } => self.evaluate_expr_set_index(environment, expression, object, index, value),
             expr::Expr::Super {
-                id,
+                local,
                 keyword,
                 method,
                 ..
-            } => self.evaluate_expr_super(environment, id, keyword, method),
-            expr::Expr::This { id, keyword, .. } => {
-                self.evaluate_expr_this(environment, id, keyword)
+            } => self.evaluate_expr_super(environment, local, keyword, method),
+            expr::Expr::This { local, keyword, .. } => {
+                self.evaluate_expr_this(environment, local, keyword)
             }
             expr::Expr::Unary {
                 operator, right, ..
             } => self.evaluate_expr_unary(environment, expression, operator, right),
-            expr::Expr::Variable { id, name, .. } => {
-                self.evaluate_expr_var(environment, expression, id, name)
+            expr::Expr::Variable { local, name, .. } => {
+                self.evaluate_expr_var(environment, expression, local, name)
             }
         }
     }
@@ -338,15 +648,15 @@ impl<'r> Interpreter<'r> {
         &self,
         environment: &mut environment::Environment,
         expression: &expr::Expr,
-        id: &usize,
+        local: &expr::Resolution,
         name: &token::Token,
         value: &expr::Expr,
     ) -> Result<lox_type::LoxType, unwind::Unwind> {
         let value = self.evaluate_expr(environment, value)?;
         if let Err(unwind::Unwind::WithError(message)) =
-            environment.assign_at(self.depths.get(id).cloned(), &name.lexeme, value.clone())
+            environment.assign_at(local.get(), &name.lexeme, value.clone())
         {
-            self.add_diagnostic(expression, message)?;
+            self.runtime_error(expression, message)?;
         }
         Ok(value)
     }
@@ -362,42 +672,95 @@ impl<'r> Interpreter<'r> {
         let left = self.evaluate_expr(environment, left)?;
         let right = self.evaluate_expr(environment, right)?;
 
-        if matches!(operator.token_type, token::TokenType::Plus) {
-            if matches!(right, lox_type::LoxType::Number(_))
-                && matches!(left, lox_type::LoxType::Number(_))
-            {
-                let right = self.check_number_operand(expression, &right)?;
-                let left = self.check_number_operand(expression, &left)?;
-                Ok(lox_type::LoxType::Number(left + right))
-            } else if matches!(right, lox_type::LoxType::String(_))
+        // Each kind of operator is evaluated in its own function, so that the
+        // native stack used by this one, which recursion passes through, stays
+        // small.
+        match operator.token_type {
+            token::TokenType::Plus => self.evaluate_plus(expression, left, right),
+            token::TokenType::EqualEqual => Ok(lox_type::LoxType::Boolean(
+                self.equals(expression, &left, &right)?,
+            )),
+            token::TokenType::BangEqual => Ok(lox_type::LoxType::Boolean(
+                !self.equals(expression, &left, &right)?,
+            )),
+            token::TokenType::Is => self.evaluate_is(expression, &left, &right),
+            _ => self.evaluate_arithmetic(expression, operator, &left, &right),
+        }
+    }
+
+    fn evaluate_plus(
+        &self,
+        expression: &expr::Expr,
+        left: lox_type::LoxType,
+        right: lox_type::LoxType,
+    ) -> Result<lox_type::LoxType, unwind::Unwind> {
+        if matches!(right, lox_type::LoxType::Number(_))
+            && matches!(left, lox_type::LoxType::Number(_))
+        {
+            let right = self.check_number_operand(expression, &right)?;
+            let left = self.check_number_operand(expression, &left)?;
+            Ok(lox_type::LoxType::Number(left + right))
+        } else if matches!(right, lox_type::LoxType::String(_))
+            || matches!(left, lox_type::LoxType::String(_))
+        {
+            let right = self.to_string_operand(expression, right)?;
+            let left = self.to_string_operand(expression, left)?;
+            if matches!(right, lox_type::LoxType::String(_))
                 && matches!(left, lox_type::LoxType::String(_))
             {
                 let right = self.check_string_operand(expression, &right)?;
                 let left = self.check_string_operand(expression, &left)?;
                 Ok(lox_type::LoxType::String(left.to_string() + right))
             } else {
-                self.add_diagnostic(
+                self.runtime_error(
                     expression,
                     "Operands must be two numbers or two strings".to_string(),
                 )
             }
-        } else if matches!(operator.token_type, token::TokenType::EqualEqual) {
-            Ok(lox_type::LoxType::Boolean(is_equal(&left, &right)))
-        } else if matches!(operator.token_type, token::TokenType::BangEqual) {
-            Ok(lox_type::LoxType::Boolean(!is_equal(&left, &right)))
         } else {
-            let right = self.check_number_operand(expression, &right)?;
-            let left = self.check_number_operand(expression, &left)?;
-            match operator.token_type {
-                token::TokenType::Minus => Ok(lox_type::LoxType::Number(left - right)),
-                token::TokenType::Slash => Ok(lox_type::LoxType::Number(left / right)),
-                token::TokenType::Star => Ok(lox_type::LoxType::Number(left * right)),
-                token::TokenType::Greater => Ok(lox_type::LoxType::Boolean(left > right)),
-                token::TokenType::GreaterEqual => Ok(lox_type::LoxType::Boolean(left >= right)),
-                token::TokenType::Less => Ok(lox_type::LoxType::Boolean(left < right)),
-                token::TokenType::LessEqual => Ok(lox_type::LoxType::Boolean(left <= right)),
-                _ => self.add_diagnostic(expression, "Unsupported operator".to_string()),
-            }
+            self.runtime_error(
+                expression,
+                "Operands must be two numbers or two strings".to_string(),
+            )
+        }
+    }
+
+    fn evaluate_is(
+        &self,
+        expression: &expr::Expr,
+        left: &lox_type::LoxType,
+        right: &lox_type::LoxType,
+    ) -> Result<lox_type::LoxType, unwind::Unwind> {
+        match (left, right) {
+            (lox_type::LoxType::Instance { instance }, lox_type::LoxType::Class { class }) => Ok(
+                lox_type::LoxType::Boolean(instance.class().is_subclass_of(class)),
+            ),
+            (_, lox_type::LoxType::Class { .. }) => Ok(lox_type::LoxType::Boolean(false)),
+            _ => self.runtime_error(
+                expression,
+                "Right operand of 'is' must be a class".to_string(),
+            ),
+        }
+    }
+
+    fn evaluate_arithmetic(
+        &self,
+        expression: &expr::Expr,
+        operator: &token::Token,
+        left: &lox_type::LoxType,
+        right: &lox_type::LoxType,
+    ) -> Result<lox_type::LoxType, unwind::Unwind> {
+        let right = self.check_number_operand(expression, right)?;
+        let left = self.check_number_operand(expression, left)?;
+        match operator.token_type {
+            token::TokenType::Minus => Ok(lox_type::LoxType::Number(left - right)),
+            token::TokenType::Slash => Ok(lox_type::LoxType::Number(left / right)),
+            token::TokenType::Star => Ok(lox_type::LoxType::Number(left * right)),
+            token::TokenType::Greater => Ok(lox_type::LoxType::Boolean(left > right)),
+            token::TokenType::GreaterEqual => Ok(lox_type::LoxType::Boolean(left >= right)),
+            token::TokenType::Less => Ok(lox_type::LoxType::Boolean(left < right)),
+            token::TokenType::LessEqual => Ok(lox_type::LoxType::Boolean(left <= right)),
+            _ => self.runtime_error(expression, "Unsupported operator".to_string()),
         }
     }
 
@@ -408,19 +771,14 @@ impl<'r> Interpreter<'r> {
         _: &token::Token,
         arguments: &Vec<expr::Expr>,
     ) -> Result<lox_type::LoxType, unwind::Unwind> {
-        let actual_callee = self.evaluate_expr(environment, callee)?;
+        let actual_callee = self.evaluate_callee(environment, callee)?;
 
         let mut args = Vec::new();
         for expr in arguments {
             args.push(self.evaluate_expr(environment, expr)?);
         }
 
-        let result = self.call_function(actual_callee, callee, args);
-        match result {
-            Err(unwind::Unwind::WithError(_)) => result,
-            Err(unwind::Unwind::WithResult(_)) => result,
-            Ok(value) => Ok(value),
-        }
+        self.call_function(actual_callee, callee, args)
     }
 
     fn call_function(
@@ -429,59 +787,151 @@ impl<'r> Interpreter<'r> {
         expr: &expr::Expr,
         arguments: Vec<lox_type::LoxType>,
     ) -> Result<lox_type::LoxType, unwind::Unwind> {
-        let check_arity = |callable_arity: usize| -> Result<lox_type::LoxType, unwind::Unwind> {
-            if arguments.len() != callable_arity {
-                self.add_diagnostic(
-                    expr,
-                    format!(
-                        "Expected {} arguments but got {}",
-                        callable_arity,
-                        arguments.len()
-                    ),
-                )?;
+        match call(self.reporter, self.runtime, &callee, arguments) {
+            Err(unwind::Unwind::WithError(message)) => self.runtime_error(expr, message),
+            Err(unwind::Unwind::WithRuntimeError(error)) => {
+                Err(add_frame(error, callable_name(&callee), expr))
             }
-            Ok(lox_type::LoxType::Nil)
+            result => result,
+        }
+    }
+
+    fn call_method(
+        &self,
+        expr: &expr::Expr,
+        object: &lox_type::LoxType,
+        name: &str,
+    ) -> Result<lox_type::LoxType, unwind::Unwind> {
+        let method = match lox_type::LoxType::get_property(object, name) {
+            Ok(method) => method,
+            Err(unwind::Unwind::WithError(message)) => return self.runtime_error(expr, message),
+            Err(unwind) => return Err(unwind),
         };
+        self.call_function(method, expr, Vec::new())
+    }
 
+    /// Evaluates the callee of a call, the only place a built-in method such
+    /// as `"abc".length` may be looked up.
+    fn evaluate_callee(
+        &self,
+        environment: &mut environment::Environment,
+        callee: &expr::Expr,
+    ) -> Result<lox_type::LoxType, unwind::Unwind> {
         match callee {
-            lox_type::LoxType::Function { function, .. } => {
-                check_arity(function.arity())?;
-                match function.call(self.reporter, self.depths, arguments) {
-                    Err(unwind::Unwind::WithError(message)) => {
-                        Err(unwind::Unwind::WithError(message))
-                    }
-                    Err(unwind::Unwind::WithResult(value)) => Ok(value),
-                    _ => Ok(lox_type::LoxType::Nil),
+            expr::Expr::Get { object, name, .. } => {
+                if let Err(unwind::Unwind::WithError(message)) = self.runtime.step() {
+                    return self.runtime_error(callee, message);
                 }
+                self.evaluate_expr_get(environment, object, name, true)
             }
-            lox_type::LoxType::NativeFunction { callable, .. } => {
-                check_arity(callable.arity())?;
-                callable.call(arguments)
-            }
-            lox_type::LoxType::Class { class, .. } => {
-                check_arity(class.arity())?;
-                class.call(self.reporter, self.depths, arguments)
-            }
-            _ => Err(self
-                .add_diagnostic(expr, "Can only call functions and classes".to_string())
-                .unwrap_err()),
+            _ => self.evaluate_expr(environment, callee),
         }
     }
 
+    /// Gets the property `name`. Unless `called`, a built-in method is an
+    /// error, so that `"abc".length` without parentheses isn't quietly a
+    /// function.
     fn evaluate_expr_get(
         &self,
         environment: &mut environment::Environment,
         expression: &expr::Expr,
         name: &token::Token,
+        called: bool,
     ) -> Result<lox_type::LoxType, unwind::Unwind> {
         let object = self.evaluate_expr(environment, expression)?;
-        match lox_type::LoxType::get_instance_value(&object, &name.lexeme) {
+        if name.token_type == token::TokenType::PrivateIdentifier {
+            let owner = self.private_owner(environment, expression, name)?;
+            return match lox_type::LoxType::get_private_property(&object, &owner, &name.lexeme) {
+                Ok(value) => Ok(value),
+                Err(unwind::Unwind::WithError(message)) => self.runtime_error(expression, message),
+                _ => unreachable!(),
+            };
+        }
+        match lox_type::LoxType::get_property(&object, &name.lexeme) {
+            Ok(lox_type::LoxType::NativeFunction { .. })
+                if !called && native_methods::has_methods(&object) =>
+            {
+                self.runtime_error(name, format!("Method '{}' must be called", name.lexeme))
+            }
+            Ok(value) => Ok(value),
+            Err(unwind::Unwind::WithError(message)) => self.runtime_error(expression, message),
+            _ => unreachable!(),
+        }
+    }
+
+    /// The class whose private members the running method may access. Only
+    /// `this.#name` inside a method has one.
+    fn private_owner(
+        &self,
+        environment: &environment::Environment,
+        object: &expr::Expr,
+        name: &token::Token,
+    ) -> Result<class::Class, unwind::Unwind> {
+        if let expr::Expr::This { local, .. } = object {
+            let owner = local.get().map(|this| resolver::Local {
+                slot: function::OWNER_SLOT,
+                ..this
+            });
+            if let Ok(lox_type::LoxType::Class { class }) =
+                environment.get_at(owner, function::OWNER)
+            {
+                return Ok(class);
+            }
+        }
+        Err(self
+            .runtime_error(
+                name,
+                format!(
+                    "Private member '{}' is only accessible through 'this' inside its class",
+                    name.lexeme
+                ),
+            )
+            .unwrap_err())
+    }
+
+    fn evaluate_expr_inner(
+        &self,
+        environment: &mut environment::Environment,
+        local: &expr::Resolution,
+        keyword: &token::Token,
+    ) -> Result<lox_type::LoxType, unwind::Unwind> {
+        match self.look_up_variable(environment, local, keyword) {
+            Ok(value) => Ok(value),
+            Err(unwind::Unwind::WithError(message)) => self.runtime_error(keyword, message),
+            _ => unreachable!(),
+        }
+    }
+
+    fn evaluate_expr_index(
+        &self,
+        environment: &mut environment::Environment,
+        expression: &expr::Expr,
+        object: &expr::Expr,
+        index: &expr::Expr,
+    ) -> Result<lox_type::LoxType, unwind::Unwind> {
+        let object = self.evaluate_expr(environment, object)?;
+        let index = self.evaluate_expr(environment, index)?;
+        match lox_type::LoxType::get_index(&object, &index) {
             Ok(value) => Ok(value),
-            Err(unwind::Unwind::WithError(message)) => self.add_diagnostic(expression, message),
+            Err(unwind::Unwind::WithError(message)) => self.runtime_error(expression, message),
             _ => unreachable!(),
         }
     }
 
+    fn evaluate_expr_list(
+        &self,
+        environment: &mut environment::Environment,
+        elements: &[expr::Expr],
+    ) -> Result<lox_type::LoxType, unwind::Unwind> {
+        let mut values = Vec::new();
+        for element in elements {
+            values.push(self.evaluate_expr(environment, element)?);
+        }
+        Ok(lox_type::LoxType::List {
+            list: list::List::new(values),
+        })
+    }
+
     fn evaluate_expr_literal(
         &self,
         expr: &expr::Expr,
@@ -495,7 +945,7 @@ impl<'r> Interpreter<'r> {
             Some(token::Literal::True) => Ok(lox_type::LoxType::Boolean(true)),
             Some(token::Literal::False) => Ok(lox_type::LoxType::Boolean(false)),
             Some(token::Literal::Nil) => Ok(lox_type::LoxType::Nil),
-            _ => self.add_diagnostic(expr, "Unhandled literal".to_string()),
+            _ => self.runtime_error(expr, "Unhandled literal".to_string()),
         }
     }
 
@@ -518,6 +968,22 @@ impl<'r> Interpreter<'r> {
         self.evaluate_expr(environment, right)
     }
 
+    fn evaluate_expr_map(
+        &self,
+        environment: &mut environment::Environment,
+        entries: &[(expr::Expr, expr::Expr)],
+    ) -> Result<lox_type::LoxType, unwind::Unwind> {
+        let map = map::Map::new();
+        for (key_expr, value) in entries {
+            let key = self.evaluate_expr(environment, key_expr)?;
+            let value = self.evaluate_expr(environment, value)?;
+            if let Err(unwind::Unwind::WithError(message)) = map.set(key, value) {
+                self.runtime_error(key_expr, message)?;
+            }
+        }
+        Ok(lox_type::LoxType::Map { map })
+    }
+
     fn evaluate_expr_set(
         &self,
         environment: &mut environment::Environment,
@@ -526,11 +992,43 @@ impl<'r> Interpreter<'r> {
         value: &expr::Expr,
     ) -> Result<lox_type::LoxType, unwind::Unwind> {
         let instance = self.evaluate_expr(environment, expression)?;
+        let owner = if name.token_type == token::TokenType::PrivateIdentifier {
+            Some(self.private_owner(environment, expression, name)?)
+        } else {
+            None
+        };
         let value = self.evaluate_expr(environment, value)?;
-        if let Err(unwind::Unwind::WithError(message)) =
+        let result = if let Some(owner) = owner {
+            lox_type::LoxType::set_private_instance_value(
+                &instance,
+                &owner,
+                &name.lexeme,
+                value.clone(),
+            )
+        } else {
             lox_type::LoxType::set_instance_value(&instance, &name.lexeme, value.clone())
+        };
+        if let Err(unwind::Unwind::WithError(message)) = result {
+            self.runtime_error(expression, message)?;
+        }
+        Ok(value)
+    }
+
+    fn evaluate_expr_set_index(
+        &self,
+        environment: &mut environment::Environment,
+        expression: &expr::Expr,
+        object: &expr::Expr,
+        index: &expr::Expr,
+        value: &expr::Expr,
+    ) -> Result<lox_type::LoxType, unwind::Unwind> {
+        let object = self.evaluate_expr(environment, object)?;
+        let index = self.evaluate_expr(environment, index)?;
+        let value = self.evaluate_expr(environment, value)?;
+        if let Err(unwind::Unwind::WithError(message)) =
+            lox_type::LoxType::set_index(&object, &index, value.clone())
         {
-            self.add_diagnostic(expression, message)?;
+            self.runtime_error(expression, message)?;
         }
         Ok(value)
     }
@@ -538,49 +1036,54 @@ impl<'r> Interpreter<'r> {
     fn evaluate_expr_super(
         &self,
         environment: &mut environment::Environment,
-        id: &usize,
+        local: &expr::Resolution,
         keyword: &token::Token,
         method: &token::Token,
     ) -> Result<lox_type::LoxType, unwind::Unwind> {
-        let depth = self.depths.get(id).cloned();
+        let local = local.get();
         let superclass = if let Ok(lox_type::LoxType::Class { class }) =
-            environment.get_at(depth, &keyword.lexeme)
+            environment.get_at(local, &keyword.lexeme)
         {
             class
         } else {
-            return self.add_diagnostic(keyword, "Superclass not found".to_string());
+            return self.runtime_error(keyword, "Superclass not found".to_string());
         };
 
+        let this = local.map(|local| resolver::Local {
+            depth: local.depth - 1,
+            slot: function::THIS_SLOT,
+        });
         let instance = if let Ok(lox_type::LoxType::Instance { instance }) =
-            environment.get_at(Some(depth.unwrap() - 1), "this")
+            environment.get_at(this, "this")
         {
             instance
         } else {
-            return self.add_diagnostic(keyword, "Instance not found".to_string());
+            return self.runtime_error(keyword, "Instance not found".to_string());
         };
 
-        let method = if let Some(lox_type::LoxType::Function { function }) =
-            superclass.find_method(&method.lexeme)
-        {
-            function
-        } else {
-            return self.add_diagnostic(keyword, "Method not found".to_string());
-        };
-
-        Ok(lox_type::LoxType::Function {
-            function: method.bind_this(lox_type::LoxType::Instance { instance }),
-        })
+        match superclass.find_method(&method.lexeme) {
+            Some((
+                lox_type::LoxType::Function { .. } | lox_type::LoxType::NativeFunction { .. },
+                owner,
+            )) => instance
+                .find_method_from(&owner, &method.lexeme)
+                .ok_or_else(|| {
+                    self.runtime_error(keyword, "Method not found".to_string())
+                        .unwrap_err()
+                }),
+            _ => self.runtime_error(keyword, "Method not found".to_string()),
+        }
     }
 
     fn evaluate_expr_this(
         &self,
         environment: &mut environment::Environment,
-        id: &usize,
+        local: &expr::Resolution,
         keyword: &token::Token,
     ) -> Result<lox_type::LoxType, unwind::Unwind> {
-        match self.look_up_variable(environment, id, keyword) {
+        match self.look_up_variable(environment, local, keyword) {
             Ok(value) => Ok(value),
-            Err(unwind::Unwind::WithError(message)) => self.add_diagnostic(keyword, message),
+            Err(unwind::Unwind::WithError(message)) => self.runtime_error(keyword, message),
             _ => unreachable!(),
         }
     }
@@ -596,10 +1099,10 @@ impl<'r> Interpreter<'r> {
         match operator.token_type {
             token::TokenType::Minus => {
                 let right = self.check_number_operand(expression, &right)?;
-                Ok(lox_type::LoxType::Number(-1.0 * right))
+                Ok(lox_type::LoxType::Number(-right))
             }
             token::TokenType::Bang => Ok(lox_type::LoxType::Boolean(!is_truthy(&right))),
-            _ => self.add_diagnostic(expression, "Unsupported operand".to_string()),
+            _ => self.runtime_error(expression, "Unsupported operand".to_string()),
         }
     }
 
@@ -607,12 +1110,12 @@ impl<'r> Interpreter<'r> {
         &self,
         environment: &mut environment::Environment,
         expression: &expr::Expr,
-        id: &usize,
+        local: &expr::Resolution,
         name: &token::Token,
     ) -> Result<lox_type::LoxType, unwind::Unwind> {
-        match self.look_up_variable(environment, id, name) {
+        match self.look_up_variable(environment, local, name) {
             Ok(value) => Ok(value),
-            Err(unwind::Unwind::WithError(message)) => self.add_diagnostic(expression, message),
+            Err(unwind::Unwind::WithError(message)) => self.runtime_error(expression, message),
             _ => unreachable!(),
         }
     }
@@ -620,21 +1123,25 @@ impl<'r> Interpreter<'r> {
     fn look_up_variable(
         &self,
         environment: &environment::Environment,
-        id: &usize,
+        local: &expr::Resolution,
         name: &token::Token,
     ) -> Result<lox_type::LoxType, unwind::Unwind> {
-        let depth = self.depths.get(id).cloned();
-        environment.get_at(depth, &name.lexeme)
+        environment.get_at(local.get(), &name.lexeme)
     }
 
-    fn add_diagnostic(
+    fn runtime_error(
         &self,
         provider: impl location::ProvideLocation,
         message: String,
     ) -> Result<lox_type::LoxType, unwind::Unwind> {
-        self.reporter
-            .add_diagnostic(provider.start(), provider.end(), &message);
-        Err(unwind::Unwind::WithError(message))
+        Err(unwind::Unwind::WithRuntimeError(Box::new(
+            unwind::RuntimeError {
+                message,
+                start: *provider.start(),
+                end: *provider.end(),
+                stack: Vec::new(),
+            },
+        )))
     }
 
     fn check_number_operand(
@@ -645,9 +1152,137 @@ impl<'r> Interpreter<'r> {
         match lox_type {
             lox_type::LoxType::Number(value) => Ok(*value),
             _ => Err(self
-                .add_diagnostic(expression, "Operand should be a number".to_string())
+                .runtime_error(expression, "Operand should be a number".to_string())
+                .unwrap_err()),
+        }
+    }
+
+    /// Converts an instance to a string, through `toString()` where its class
+    /// defines one, so that it can be concatenated with a string. Other values
+    /// are returned unchanged.
+    fn to_string_operand(
+        &self,
+        expression: &expr::Expr,
+        value: lox_type::LoxType,
+    ) -> Result<lox_type::LoxType, unwind::Unwind> {
+        if let lox_type::LoxType::Instance { instance } = &value {
+            let string = self
+                .call_to_string(expression, instance)?
+                .unwrap_or_else(|| format!("{} instance", instance.class_name()));
+            return Ok(lox_type::LoxType::String(string));
+        }
+        Ok(value)
+    }
+
+    fn call_to_string(
+        &self,
+        expression: &expr::Expr,
+        instance: &instance::Instance,
+    ) -> Result<Option<String>, unwind::Unwind> {
+        let result = instance.with_protocol_method("toString", |method| {
+            self.call_function(method, expression, Vec::new())
+        })?;
+        match result {
+            Some(lox_type::LoxType::String(string)) => Ok(Some(string)),
+            Some(_) => Err(self
+                .runtime_error(expression, "'toString' must return a string".to_string())
                 .unwrap_err()),
+            None => Ok(None),
+        }
+    }
+
+    /// The text `print` shows for a value, using `toString()` for instances
+    /// that define it.
+    fn print_text(
+        &self,
+        expression: &expr::Expr,
+        value: &lox_type::LoxType,
+    ) -> Result<String, unwind::Unwind> {
+        match value {
+            lox_type::LoxType::Instance { instance } => Ok(self
+                .call_to_string(expression, instance)?
+                .unwrap_or_else(|| value.text())),
+            lox_type::LoxType::List { .. } | lox_type::LoxType::Map { .. } => {
+                self.stringify(expression, value)
+            }
+            _ => Ok(value.text()),
+        }
+    }
+
+    /// The text a value shows inside a printed list or map, using
+    /// `toString()` for instances that define it, including nested ones. A
+    /// list, map or instance met again inside itself shows as `[...]`, `{...}`
+    /// or its text without `toString()`.
+    fn stringify(
+        &self,
+        expression: &expr::Expr,
+        value: &lox_type::LoxType,
+    ) -> Result<String, unwind::Unwind> {
+        let placeholder = match value {
+            lox_type::LoxType::Instance { .. } => value.to_string(),
+            lox_type::LoxType::List { .. } => "[...]".to_string(),
+            lox_type::LoxType::Map { .. } => "{...}".to_string(),
+            _ => return Ok(value.to_string()),
+        };
+        lox_type::print_once(value, || self.stringify_open(expression, value))
+            .unwrap_or(Ok(placeholder))
+    }
+
+    fn stringify_open(
+        &self,
+        expression: &expr::Expr,
+        value: &lox_type::LoxType,
+    ) -> Result<String, unwind::Unwind> {
+        match value {
+            lox_type::LoxType::Instance { instance } => {
+                match self.call_to_string(expression, instance)? {
+                    Some(string) => Ok(lox_type::LoxType::String(string).to_string()),
+                    None => Ok(value.to_string()),
+                }
+            }
+            lox_type::LoxType::List { list } => {
+                let elements = list
+                    .elements()
+                    .iter()
+                    .map(|element| self.stringify(expression, element))
+                    .collect::<Result<Vec<String>, unwind::Unwind>>()?;
+                Ok(format!("[{}]", elements.join(", ")))
+            }
+            lox_type::LoxType::Map { map } => {
+                let entries = map
+                    .entries()
+                    .iter()
+                    .map(|(key, value)| {
+                        Ok(format!(
+                            "{}: {}",
+                            self.stringify(expression, key)?,
+                            self.stringify(expression, value)?
+                        ))
+                    })
+                    .collect::<Result<Vec<String>, unwind::Unwind>>()?;
+                Ok(format!("{{{}}}", entries.join(", ")))
+            }
+            _ => Ok(value.to_string()),
+        }
+    }
+
+    /// `==` on an instance whose class defines `equals(other)` calls it, and
+    /// otherwise compares by identity.
+    fn equals(
+        &self,
+        expression: &expr::Expr,
+        left: &lox_type::LoxType,
+        right: &lox_type::LoxType,
+    ) -> Result<bool, unwind::Unwind> {
+        if let lox_type::LoxType::Instance { instance } = left {
+            let result = instance.with_protocol_method("equals", |method| {
+                self.call_function(method, expression, vec![right.clone()])
+            })?;
+            if let Some(result) = result {
+                return Ok(is_truthy(&result));
+            }
         }
+        Ok(is_equal(left, right))
     }
 
     fn check_string_operand<'a>(
@@ -658,12 +1293,141 @@ impl<'r> Interpreter<'r> {
         match &lox_type {
             lox_type::LoxType::String(string) => Ok(string),
             _ => Err(self
-                .add_diagnostic(expression, "Operand should be a string".to_string())
+                .runtime_error(expression, "Operand should be a string".to_string())
                 .unwrap_err()),
         }
     }
 }
 
+/// Calls `callee` for a native that calls back into Lox, such as `map`. The
+/// call has no expression of its own, so an error raised inside `callee` gains
+/// a frame placed where `callee` is declared.
+pub fn call_back(
+    reporter: &dyn reporter::Reporter,
+    runtime: &runtime::Runtime,
+    callee: &lox_type::LoxType,
+    arguments: Vec<lox_type::LoxType>,
+) -> Result<lox_type::LoxType, unwind::Unwind> {
+    match call(reporter, runtime, callee, arguments) {
+        Err(unwind::Unwind::WithRuntimeError(error)) => match declaration(callee) {
+            Some(declaration) => Err(add_frame(error, callable_name(callee), declaration)),
+            None => Err(unwind::Unwind::WithRuntimeError(error)),
+        },
+        result => result,
+    }
+}
+
+/// Calls a function, native or class the way a call expression does. Errors
+/// that have no location yet, such as a wrong number of arguments, are left
+/// for the caller to place.
+fn call(
+    reporter: &dyn reporter::Reporter,
+    runtime: &runtime::Runtime,
+    callee: &lox_type::LoxType,
+    arguments: Vec<lox_type::LoxType>,
+) -> Result<lox_type::LoxType, unwind::Unwind> {
+    match callee {
+        lox_type::LoxType::Function { function, .. } => {
+            check_arity(function.arity(), &arguments)?;
+            let _call = runtime.enter_call()?;
+            match function.call(reporter, runtime, arguments) {
+                Err(unwind::Unwind::WithResult(value)) => Ok(value),
+                Err(unwind) => Err(unwind),
+                _ => Ok(lox_type::LoxType::Nil),
+            }
+        }
+        lox_type::LoxType::NativeFunction { callable, .. } => {
+            call_native(reporter, runtime, callable.as_ref().as_ref(), arguments)
+        }
+        lox_type::LoxType::Class { class, .. } => call_class(reporter, runtime, class, arguments),
+        _ => Err(unwind::Unwind::WithError(
+            "Can only call functions and classes".to_string(),
+        )),
+    }
+}
+
+fn call_class(
+    reporter: &dyn reporter::Reporter,
+    runtime: &runtime::Runtime,
+    class: &class::Class,
+    arguments: Vec<lox_type::LoxType>,
+) -> Result<lox_type::LoxType, unwind::Unwind> {
+    check_arity(class.arity(), &arguments)?;
+    let _call = runtime.enter_call()?;
+    class.call(reporter, runtime, arguments)
+}
+
+/// Calls a native, padding any optional arguments left out with nil.
+///
+/// Natives and classes are called from functions of their own so that
+/// recursion between Lox functions, which passes through `call`, uses less of
+/// the native stack.
+fn call_native(
+    reporter: &dyn reporter::Reporter,
+    runtime: &runtime::Runtime,
+    callable: &dyn lox_type::NativeCallable,
+    mut arguments: Vec<lox_type::LoxType>,
+) -> Result<lox_type::LoxType, unwind::Unwind> {
+    let arity = callable.arity();
+    let required = arity - callable.optional();
+    if arguments.len() < required || arguments.len() > arity {
+        check_arity(arity, &arguments)?;
+    }
+    arguments.resize(arity, lox_type::LoxType::Nil);
+    let _call = runtime.enter_call()?;
+    callable.call(reporter, runtime, arguments)
+}
+
+fn check_arity(arity: usize, arguments: &[lox_type::LoxType]) -> Result<(), unwind::Unwind> {
+    if arguments.len() != arity {
+        return Err(unwind::Unwind::WithError(format!(
+            "Expected {} arguments but got {}",
+            arity,
+            arguments.len()
+        )));
+    }
+    Ok(())
+}
+
+/// The name a call to `callee` shows in a stack trace.
+fn callable_name(callee: &lox_type::LoxType) -> &str {
+    match callee {
+        lox_type::LoxType::Function { function, .. } => function.name(),
+        lox_type::LoxType::NativeFunction { name, .. } => name,
+        lox_type::LoxType::Class { class, .. } => class.name(),
+        _ => "",
+    }
+}
+
+/// The name `callee` is declared with, or its `init` method's for a class.
+/// Natives have none.
+fn declaration(callee: &lox_type::LoxType) -> Option<token::Token> {
+    match callee {
+        lox_type::LoxType::Function { function, .. } => Some(function.declaration().clone()),
+        lox_type::LoxType::Class { class, .. } => match class.find_method("init") {
+            Some((lox_type::LoxType::Function { function }, _)) => {
+                Some(function.declaration().clone())
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Records the call made at `provider` on an error unwinding out of `function`.
+fn add_frame(
+    mut error: Box<unwind::RuntimeError>,
+    function: &str,
+    provider: impl location::ProvideLocation,
+) -> unwind::Unwind {
+    error.stack.push(unwind::Frame {
+        function: function.to_string(),
+        start: *provider.start(),
+        end: *provider.end(),
+    });
+    unwind::Unwind::WithRuntimeError(error)
+}
+
 fn is_truthy(lox_type: &lox_type::LoxType) -> bool {
     match lox_type {
         lox_type::LoxType::Nil => false,
@@ -697,6 +1461,42 @@ fn is_equal(left: &lox_type::LoxType, right: &lox_type::LoxType) -> bool {
         }
     }
 
+    if let lox_type::LoxType::Class { class: left } = left {
+        if let lox_type::LoxType::Class { class: right } = right {
+            return left.id() == right.id();
+        }
+    }
+
+    if let lox_type::LoxType::Instance { instance: left } = left {
+        if let lox_type::LoxType::Instance { instance: right } = right {
+            return left == right;
+        }
+    }
+
+    if let lox_type::LoxType::Enum { enumeration: left } = left {
+        if let lox_type::LoxType::Enum { enumeration: right } = right {
+            return left == right;
+        }
+    }
+
+    if let lox_type::LoxType::EnumMember { member: left } = left {
+        if let lox_type::LoxType::EnumMember { member: right } = right {
+            return left == right;
+        }
+    }
+
+    if let lox_type::LoxType::List { list: left } = left {
+        if let lox_type::LoxType::List { list: right } = right {
+            return left == right;
+        }
+    }
+
+    if let lox_type::LoxType::Map { map: left } = left {
+        if let lox_type::LoxType::Map { map: right } = right {
+            return left == right;
+        }
+    }
+
     false
 }
 
@@ -708,6 +1508,21 @@ mod test {
         scanner, token,
     };
 
+    fn runtime_error<T>(
+        message: &str,
+        start: FileLocation,
+        end: FileLocation,
+    ) -> Result<T, unwind::Unwind> {
+        Err(unwind::Unwind::WithRuntimeError(Box::new(
+            unwind::RuntimeError {
+                message: message.to_string(),
+                start,
+                end,
+                stack: Vec::new(),
+            },
+        )))
+    }
+
     #[test]
     fn test_truthy_values() {
         let tests = vec![
@@ -739,7 +1554,6 @@ mod test {
         let reporter = TestReporter::new();
         let blank_location = FileLocation::new(0, 0);
         let expression = expr::Expr::Literal {
-            id: 0,
             value: token::Token::new(
                 token::TokenType::String,
                 "\"\"",
@@ -756,27 +1570,21 @@ mod test {
             ),
             (
                 lox_type::LoxType::Number(0f64),
-                Err::<&str, unwind::Unwind>(unwind::Unwind::WithError(
-                    "Operand should be a string".to_string(),
-                )),
+                runtime_error("Operand should be a string", blank_location, blank_location),
             ),
             (
                 lox_type::LoxType::Nil,
-                Err::<&str, unwind::Unwind>(unwind::Unwind::WithError(
-                    "Operand should be a string".to_string(),
-                )),
+                runtime_error("Operand should be a string", blank_location, blank_location),
             ),
             (
                 lox_type::LoxType::Boolean(true),
-                Err::<&str, unwind::Unwind>(unwind::Unwind::WithError(
-                    "Operand should be a string".to_string(),
-                )),
+                runtime_error("Operand should be a string", blank_location, blank_location),
             ),
         ];
 
-        let depths = HashMap::<usize, usize>::new();
+        let runtime = runtime::Runtime::new(runtime::Options::default());
         let statements = LinkedList::<stmt::Stmt>::new();
-        let interpreter = Interpreter::new(&reporter, &depths, &statements);
+        let interpreter = Interpreter::new(&reporter, &runtime, &statements);
 
         for (value, expected_result) in &tests {
             reporter.reset();
@@ -794,7 +1602,6 @@ mod test {
         let reporter = TestReporter::new();
         let blank_location = FileLocation::new(0, 0);
         let expression = expr::Expr::Literal {
-            id: 0,
             value: token::Token::new(
                 token::TokenType::String,
                 "\"\"",
@@ -807,9 +1614,7 @@ mod test {
         let tests = vec![
             (
                 lox_type::LoxType::String("".to_string()),
-                Err::<f64, unwind::Unwind>(unwind::Unwind::WithError(
-                    "Operand should be a number".to_string(),
-                )),
+                runtime_error("Operand should be a number", blank_location, blank_location),
             ),
             (
                 lox_type::LoxType::Number(0f64),
@@ -817,21 +1622,17 @@ mod test {
             ),
             (
                 lox_type::LoxType::Nil,
-                Err::<f64, unwind::Unwind>(unwind::Unwind::WithError(
-                    "Operand should be a number".to_string(),
-                )),
+                runtime_error("Operand should be a number", blank_location, blank_location),
             ),
             (
                 lox_type::LoxType::Boolean(true),
-                Err::<f64, unwind::Unwind>(unwind::Unwind::WithError(
-                    "Operand should be a number".to_string(),
-                )),
+                runtime_error("Operand should be a number", blank_location, blank_location),
             ),
         ];
 
-        let depths = HashMap::<usize, usize>::new();
+        let runtime = runtime::Runtime::new(runtime::Options::default());
         let statements = LinkedList::<stmt::Stmt>::new();
-        let interpreter = Interpreter::new(&reporter, &depths, &statements);
+        let interpreter = Interpreter::new(&reporter, &runtime, &statements);
         for (value, expected_result) in &tests {
             reporter.reset();
             assert_eq!(
@@ -907,10 +1708,10 @@ mod test {
 
     fn test_expressions(tests: Vec<(&str, Result<lox_type::LoxType, unwind::Unwind>)>) {
         let reporter = TestReporter::new();
-        let depths = HashMap::<usize, usize>::new();
+        let runtime = runtime::Runtime::new(runtime::Options::default());
         let statements = LinkedList::<stmt::Stmt>::new();
         for (src, expected_result) in tests {
-            let interpreter = Interpreter::new(&reporter, &depths, &statements);
+            let interpreter = Interpreter::new(&reporter, &runtime, &statements);
             let mut environment = environment::Environment::new();
             reporter.reset();
             let tokens = scanner::scan_tokens(&reporter, src);
@@ -948,9 +1749,11 @@ mod test {
             ),
             (
                 "10 + \", world\";",
-                Err::<lox_type::LoxType, unwind::Unwind>(unwind::Unwind::WithError(
-                    "Operands must be two numbers or two strings".to_string(),
-                )),
+                runtime_error(
+                    "Operands must be two numbers or two strings",
+                    FileLocation::new(0, 0),
+                    FileLocation::new(0, 14),
+                ),
             ),
         ];
 
@@ -1075,10 +1878,10 @@ mod test {
         ];
 
         let reporter = TestReporter::new();
-        let depths = HashMap::<usize, usize>::new();
+        let runtime = runtime::Runtime::new(runtime::Options::default());
         let statements = LinkedList::<stmt::Stmt>::new();
         for (src, key, expected_value) in tests {
-            let interpreter = Interpreter::new(&reporter, &depths, &statements);
+            let interpreter = Interpreter::new(&reporter, &runtime, &statements);
             let mut environment = environment::Environment::new();
             reporter.reset();
             let tokens = scanner::scan_tokens(&reporter, src);
@@ -1115,26 +1918,23 @@ mod test {
     #[test]
     fn test_stmt() {
         let tests = vec![
-            ("print \"value\";", "[print] \"value\""),
+            ("print \"value\";", "[print] value"),
             ("print 10 + 10;", "[print] 20"),
             ("{print true == false;} ", "[print] false"),
-            (
-                "if (true) print \"then branch\"; ",
-                "[print] \"then branch\"",
-            ),
+            ("if (true) print \"then branch\"; ", "[print] then branch"),
             (
                 "if (false) print \"then branch\"; else print \"else branch\";",
-                "[print] \"else branch\"",
+                "[print] else branch",
             ),
-            ("print \"hi\" or 2 ; ", "[print] \"hi\""),
-            ("print nil or \"yes\" ; ", "[print] \"yes\""),
+            ("print \"hi\" or 2 ; ", "[print] hi"),
+            ("print nil or \"yes\" ; ", "[print] yes"),
         ];
 
         let reporter = TestReporter::new();
-        let depths = HashMap::<usize, usize>::new();
+        let runtime = runtime::Runtime::new(runtime::Options::default());
         let statements = LinkedList::<stmt::Stmt>::new();
         for (src, expected_message) in tests {
-            let interpreter = Interpreter::new(&reporter, &depths, &statements);
+            let interpreter = Interpreter::new(&reporter, &runtime, &statements);
             let mut environment = environment::Environment::new();
             reporter.reset();
             let tokens = scanner::scan_tokens(&reporter, src);
@@ -1163,15 +1963,15 @@ mod test {
         let tests = vec![
             (
                 "var a = 3; if (a > 1) print \"> 1\"; else print \"<= 1\" ;",
-                "[print] \"> 1\"",
+                "[print] > 1",
             ),
             (
                 "var a = \"init\"; if (true) a = \"updated\" ; print a;",
-                "[print] \"updated\"",
+                "[print] updated",
             ),
             (
                 "var a = \"init\"; if (false) a = \"updated\" ; print a;",
-                "[print] \"init\"",
+                "[print] init",
             ),
             (
                 "var a = 1; while ( a < 5) a = a + 1 ; print a;",
@@ -1179,7 +1979,7 @@ mod test {
             ),
             (
                 "fun sayHi(first, last) { print \"Hi, \" + first + \" \" + last; } sayHi(\"Dear\", \"Reader\");",
-                "[print] \"Hi, Dear Reader\"",
+                "[print] Hi, Dear Reader",
             ),
             (
                 "fun count(n) {if (n> 1) count(n-1); print n; } count(3) ;",
@@ -1187,23 +1987,23 @@ mod test {
             ),
             (
                 "class Bagel{} var b = Bagel() ; b.val = \"hello field\" ; print b.val ;",
-                "[print] \"hello field\"",
+                "[print] hello field",
             ),
             (
                 "class Box {} fun notMethod(argument) { print \"called with '\" + argument + \"'\"; } var box = Box() ; box.function = notMethod; box.function(\"hello\");",
-                "[print] \"called with 'hello'\"",
+                "[print] called with 'hello'",
             ),
             (
                 "class Bacon { eat() { print \"chewy\"; } } Bacon().eat();",
-                "[print] \"chewy\"",
+                "[print] chewy",
             ),
             (
                 "fun bacon() { return \"chewy\"; } print bacon();",
-                "[print] \"chewy\"",
+                "[print] chewy",
             ),
             (
                 "class Bacon { init() { this.how = \"chewy\"; } } var b = Bacon(); print b.how;",
-                "[print] \"chewy\"",
+                "[print] chewy",
             ),
             (
                 "print (10+10)+2;",
@@ -1211,7 +2011,7 @@ mod test {
             ),
             (
                 "class Thing {} var a = Thing(); print (a == a);",
-                "[print] false",
+                "[print] true",
             ),
         ];
 
@@ -1220,8 +2020,8 @@ mod test {
             reporter.reset();
             let tokens = scanner::scan_tokens(&reporter, src);
             let statements = parser::parse(&reporter, tokens);
-            let depths = resolver::resolve(&reporter, &statements);
-            interpret(&reporter, &depths, statements);
+            resolver::resolve(&reporter, &statements);
+            State::new(runtime::Options::default(), &[]).run(&reporter, statements);
 
             if !reporter.has_message(expected_message) {
                 reporter.print_contents();
@@ -1,14 +1,19 @@
 use crate::interpreter::lox_type::Callable;
-use crate::interpreter::{instance, lox_type, unwind};
+use crate::interpreter::{gc, host_class, instance, lox_type, runtime, unwind};
 use crate::reporter;
 use std::collections::HashMap;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static ID_SRC: AtomicUsize = AtomicUsize::new(1);
 
 #[derive(Debug)]
 struct InternalClass {
+    id: usize,
     name: String,
     methods: HashMap<String, lox_type::LoxType>,
     superclass: Option<Rc<InternalClass>>,
+    host: Option<Rc<host_class::HostClass>>,
 }
 
 impl InternalClass {
@@ -18,18 +23,19 @@ impl InternalClass {
         superclass: Option<Rc<InternalClass>>,
     ) -> Self {
         Self {
+            id: ID_SRC.fetch_add(1, Ordering::Relaxed),
             name: name.to_string(),
             methods,
             superclass,
+            host: None,
         }
     }
 
     fn arity(&self) -> usize {
-        let initializer = self.find_method("init");
-        if let Some(lox_type::LoxType::Function { function }) = initializer {
-            function.arity()
-        } else {
-            0
+        match self.find_method("init") {
+            Some(lox_type::LoxType::Function { function }) => function.arity(),
+            Some(lox_type::LoxType::NativeFunction { callable, .. }) => callable.arity(),
+            _ => 0,
         }
     }
 
@@ -51,6 +57,16 @@ impl PartialEq for InternalClass {
     }
 }
 
+impl gc::Trace for InternalClass {
+    fn trace(&self, visit: &mut dyn FnMut(gc::Id)) -> bool {
+        if let Some(superclass) = &self.superclass {
+            visit(gc::id(superclass));
+        }
+        self.methods.values().for_each(|method| method.trace(visit));
+        true
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Class {
     class: Rc<InternalClass>,
@@ -68,17 +84,95 @@ impl Class {
             None
         };
 
-        Self {
-            class: Rc::new(InternalClass::new(name, methods, superclass)),
-        }
+        let class = Rc::new(InternalClass::new(name, methods, superclass));
+        gc::track(&class);
+        Self { class }
+    }
+
+    /// A class whose constructor, methods and properties are implemented by
+    /// an embedding application.
+    pub fn new_host(name: &str, host: host_class::HostClass) -> Self {
+        let mut class = InternalClass::new(name, host.methods(name), None);
+        class.host = Some(Rc::new(host));
+        let class = Rc::new(class);
+        gc::track(&class);
+        Self { class }
+    }
+
+    pub fn gc_id(&self) -> gc::Id {
+        gc::id(&self.class)
     }
 
     pub fn name(&self) -> &str {
         self.class.name()
     }
 
-    pub fn find_method(&self, name: &str) -> Option<lox_type::LoxType> {
-        self.class.find_method(name)
+    /// The host class this class is or inherits from, if any.
+    pub fn find_host(&self) -> Option<Rc<host_class::HostClass>> {
+        self.class.host.clone().or_else(|| {
+            self.superclass()
+                .and_then(|superclass| superclass.find_host())
+        })
+    }
+
+    /// Returns the method along with the class that declares it.
+    pub fn find_method(&self, name: &str) -> Option<(lox_type::LoxType, Class)> {
+        if let Some(method) = self.class.methods.get(name) {
+            Some((method.clone(), self.clone()))
+        } else {
+            self.superclass()
+                .and_then(|superclass| superclass.find_method(name))
+        }
+    }
+
+    /// Every definition of the method, from the top-most superclass down to
+    /// this class, along with the class that declares each one.
+    pub fn method_chain(&self, name: &str) -> Vec<(lox_type::LoxType, Class)> {
+        let mut chain = self
+            .superclass()
+            .map_or(Vec::new(), |superclass| superclass.method_chain(name));
+        if let Some(method) = self.class.methods.get(name) {
+            chain.push((method.clone(), self.clone()));
+        }
+        chain
+    }
+
+    /// Private methods are not inherited, so only this class's own methods are searched.
+    pub fn find_private_method(&self, name: &str) -> Option<lox_type::LoxType> {
+        self.class.methods.get(name).cloned()
+    }
+
+    pub fn superclass(&self) -> Option<Class> {
+        self.class.superclass.as_ref().map(|superclass| Class {
+            class: superclass.clone(),
+        })
+    }
+
+    /// True if this class is `other` or inherits from it.
+    pub fn is_subclass_of(&self, other: &Class) -> bool {
+        self.id() == other.id()
+            || self
+                .superclass()
+                .is_some_and(|superclass| superclass.is_subclass_of(other))
+    }
+
+    /// Names of the public methods, including inherited ones, in sorted order.
+    pub fn method_names(&self) -> Vec<String> {
+        let mut names = self.superclass().map_or(Vec::new(), |s| s.method_names());
+        names.extend(
+            self.class
+                .methods
+                .keys()
+                .filter(|name| !name.starts_with('#'))
+                .cloned(),
+        );
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    pub fn id(&self) -> usize {
+        self.class.id
     }
 }
 
@@ -90,18 +184,36 @@ impl lox_type::Callable for Class {
     fn call(
         &self,
         reporter: &dyn reporter::Reporter,
-        depths: &HashMap<usize, usize>,
+        runtime: &runtime::Runtime,
         arguments: Vec<lox_type::LoxType>,
     ) -> Result<lox_type::LoxType, unwind::Unwind> {
+        // Host data is only made by a constructor, so without one (or a Lox
+        // `init` standing in for it) the instance would be unusable.
+        if self.find_host().is_some() && self.find_method("init").is_none() {
+            return Err(unwind::Unwind::WithError(format!(
+                "'{}' has no constructor",
+                self.name()
+            )));
+        }
         let instance = instance::Instance::new(self.clone());
         let instance = lox_type::LoxType::Instance { instance };
         let initializer = lox_type::LoxType::find_instance_method(&instance, "init");
-        if let Some(lox_type::LoxType::Function { function }) = initializer {
-            if let Err(unwind::Unwind::WithError(message)) =
-                function.call(reporter, depths, arguments)
-            {
-                return Err(unwind::Unwind::WithError(message));
+        match initializer {
+            Some(lox_type::LoxType::Function { function }) => {
+                match function.call(reporter, runtime, arguments) {
+                    Err(unwind::Unwind::WithError(message)) => {
+                        return Err(unwind::Unwind::WithError(message))
+                    }
+                    Err(unwind::Unwind::WithRuntimeError(error)) => {
+                        return Err(unwind::Unwind::WithRuntimeError(error))
+                    }
+                    _ => (),
+                }
+            }
+            Some(lox_type::LoxType::NativeFunction { callable, .. }) => {
+                callable.call(reporter, runtime, arguments)?;
             }
+            _ => (),
         }
         Ok(instance)
     }
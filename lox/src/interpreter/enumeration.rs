@@ -0,0 +1,76 @@
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static ID_SRC: AtomicUsize = AtomicUsize::new(1);
+
+#[derive(Debug)]
+struct InternalEnum {
+    id: usize,
+    name: String,
+    members: Vec<String>,
+}
+
+/// The value bound to an `enum` declaration. Members are created on access and
+/// compare by their enum's identity and position.
+#[derive(Clone, Debug)]
+pub struct Enum {
+    enumeration: Rc<InternalEnum>,
+}
+
+impl Enum {
+    pub fn new(name: &str, members: Vec<String>) -> Self {
+        Self {
+            enumeration: Rc::new(InternalEnum {
+                id: ID_SRC.fetch_add(1, Ordering::Relaxed),
+                name: name.to_string(),
+                members,
+            }),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.enumeration.name
+    }
+
+    pub fn member(&self, name: &str) -> Option<Member> {
+        self.enumeration
+            .members
+            .iter()
+            .position(|member| member == name)
+            .map(|index| Member {
+                enumeration: self.clone(),
+                index,
+            })
+    }
+
+    pub fn members(&self) -> Vec<Member> {
+        (0..self.enumeration.members.len())
+            .map(|index| Member {
+                enumeration: self.clone(),
+                index,
+            })
+            .collect()
+    }
+}
+
+impl PartialEq for Enum {
+    fn eq(&self, other: &Self) -> bool {
+        self.enumeration.id == other.enumeration.id
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Member {
+    enumeration: Enum,
+    index: usize,
+}
+
+impl Member {
+    pub fn enumeration(&self) -> &Enum {
+        &self.enumeration
+    }
+
+    pub fn name(&self) -> &str {
+        &self.enumeration.enumeration.members[self.index]
+    }
+}
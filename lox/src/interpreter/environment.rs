@@ -1,122 +1,168 @@
-use crate::{interpreter::lox_type, interpreter::unwind};
+use crate::{interpreter::gc, interpreter::lox_type, interpreter::unwind, resolver};
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
 
+/// The variables of one local scope, in the slots the resolver gave them.
 struct Frame {
     enclosing: Option<Rc<RefCell<Frame>>>,
-    values: HashMap<String, lox_type::LoxType>,
+    values: Vec<lox_type::LoxType>,
 }
 
 impl Frame {
-    fn define(&mut self, name: &str, value: lox_type::LoxType) {
-        self.values.insert(name.to_string(), value);
+    fn assign_at(&mut self, depth: usize, slot: usize, value: lox_type::LoxType) -> Option<()> {
+        if depth > 0 {
+            let enclosing = self.enclosing.as_ref()?;
+            enclosing.borrow_mut().assign_at(depth - 1, slot, value)
+        } else {
+            *self.values.get_mut(slot)? = value;
+            Some(())
+        }
     }
 
-    fn assign_at(
-        &mut self,
-        depth: usize,
-        name: &str,
-        value: lox_type::LoxType,
-    ) -> Result<(), unwind::Unwind> {
-        if depth > 0 && self.enclosing.is_some() {
-            self.enclosing
-                .as_ref()
-                .unwrap()
-                .borrow_mut()
-                .assign_at(depth - 1, name, value)
-        } else if depth == 0 && self.values.contains_key(name) {
-            self.values.insert(name.to_string(), value.clone());
-            Ok(())
+    fn get_at(&self, depth: usize, slot: usize) -> Option<lox_type::LoxType> {
+        if depth > 0 {
+            self.enclosing.as_ref()?.borrow().get_at(depth - 1, slot)
         } else {
-            Err(unwind::Unwind::WithError(format!(
-                "Undefined variable '{}'",
-                name
-            )))
+            self.values.get(slot).cloned()
         }
     }
+}
 
-    fn get_at(&self, depth: usize, name: &str) -> Result<lox_type::LoxType, unwind::Unwind> {
-        if depth > 0 && self.enclosing.is_some() {
-            self.enclosing
-                .as_ref()
-                .unwrap()
-                .borrow()
-                .get_at(depth - 1, name)
-        } else if depth == 0 && self.values.contains_key(name) {
-            Ok(self.values.get(name).cloned().unwrap())
-        } else {
-            Err(unwind::Unwind::WithError(format!(
-                "Undefined variable '{}'",
-                name
-            )))
+impl gc::Trace for RefCell<Frame> {
+    fn trace(&self, visit: &mut dyn FnMut(gc::Id)) -> bool {
+        let Ok(frame) = self.try_borrow() else {
+            return false;
+        };
+        if let Some(enclosing) = &frame.enclosing {
+            visit(gc::id(enclosing));
+        }
+        frame.values.iter().for_each(|value| value.trace(visit));
+        true
+    }
+
+    fn clear(&self) {
+        if let Ok(mut frame) = self.try_borrow_mut() {
+            frame.enclosing = None;
+            frame.values.clear();
         }
     }
 }
 
+type Globals = HashMap<String, lox_type::LoxType>;
+
+impl gc::Trace for RefCell<Globals> {
+    fn trace(&self, visit: &mut dyn FnMut(gc::Id)) -> bool {
+        let Ok(globals) = self.try_borrow() else {
+            return false;
+        };
+        globals.values().for_each(|value| value.trace(visit));
+        true
+    }
+
+    fn clear(&self) {
+        if let Ok(mut globals) = self.try_borrow_mut() {
+            globals.clear();
+        }
+    }
+}
+
+/// Globals are looked up by name, as later input can declare more of them.
+/// Locals are looked up by the depth and slot the resolver found, and are
+/// defined in the order the resolver declared them.
 #[derive(Clone)]
 pub struct Environment {
-    frame: Rc<RefCell<Frame>>,
-    global: Rc<RefCell<Frame>>,
+    frame: Option<Rc<RefCell<Frame>>>,
+    global: Rc<RefCell<Globals>>,
 }
 
 impl Environment {
     pub fn new() -> Self {
-        let frame = Frame {
-            enclosing: None,
-            values: HashMap::new(),
-        };
-
-        let frame = Rc::new(RefCell::new(frame));
+        let global = Rc::new(RefCell::new(HashMap::new()));
+        gc::track(&global);
         Self {
-            frame: frame.clone(),
-            global: frame.clone(),
+            frame: None,
+            global,
         }
     }
 
     pub fn new_with_enclosing(enclosing: &Environment) -> Self {
         let frame = Frame {
-            enclosing: Some(enclosing.frame.clone()),
-            values: HashMap::new(),
+            enclosing: enclosing.frame.clone(),
+            values: Vec::new(),
         };
+        let frame = Rc::new(RefCell::new(frame));
+        gc::track(&frame);
         Self {
-            frame: Rc::new(RefCell::new(frame)),
+            frame: Some(frame),
             global: enclosing.global.clone(),
         }
     }
 
+    /// Visits the frames this holds, for the collector.
+    pub fn trace(&self, visit: &mut dyn FnMut(gc::Id)) {
+        if let Some(frame) = &self.frame {
+            visit(gc::id(frame));
+        }
+        visit(gc::id(&self.global));
+    }
+
+    /// Defines `name` in the innermost scope: by name for a global, or in the
+    /// next slot for a local.
     pub fn define(&mut self, name: &str, value: lox_type::LoxType) {
-        self.frame.borrow_mut().define(name, value)
+        match &self.frame {
+            Some(frame) => frame.borrow_mut().values.push(value),
+            None => {
+                self.global.borrow_mut().insert(name.to_string(), value);
+            }
+        }
     }
 
     pub fn assign_at(
         &mut self,
-        depth: Option<usize>,
+        local: Option<resolver::Local>,
         name: &str,
         value: lox_type::LoxType,
     ) -> Result<(), unwind::Unwind> {
-        match depth {
-            None => self.global.borrow_mut().assign_at(0, name, value),
-            Some(depth) => self.frame.borrow_mut().assign_at(depth, name, value),
-        }
+        let assigned = match (local, &self.frame) {
+            (Some(local), Some(frame)) => {
+                frame.borrow_mut().assign_at(local.depth, local.slot, value)
+            }
+            (Some(_), None) => None,
+            (None, _) => {
+                let mut globals = self.global.borrow_mut();
+                globals.get_mut(name).map(|global| *global = value)
+            }
+        };
+        assigned.ok_or_else(|| undefined(name))
     }
 
     pub fn get_at(
         &self,
-        depth: Option<usize>,
+        local: Option<resolver::Local>,
         name: &str,
     ) -> Result<lox_type::LoxType, unwind::Unwind> {
-        match depth {
-            None => self.global.borrow().get_at(0, name),
-            Some(depth) => self.frame.borrow().get_at(depth, name),
-        }
+        let value = match (local, &self.frame) {
+            (Some(local), Some(frame)) => frame.borrow().get_at(local.depth, local.slot),
+            (Some(_), None) => None,
+            (None, _) => self.global.borrow().get(name).cloned(),
+        };
+        value.ok_or_else(|| undefined(name))
     }
 }
 
+fn undefined(name: &str) -> unwind::Unwind {
+    unwind::Unwind::WithError(format!("Undefined variable '{}'", name))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    fn local(depth: usize, slot: usize) -> Option<resolver::Local> {
+        Some(resolver::Local { depth, slot })
+    }
+
     #[test]
     fn test_environment() {
         let mut environment = Environment::new();
@@ -130,8 +176,8 @@ mod test {
             ))
         );
         assert_eq!(
-            Rc::strong_count(&environment.frame),
-            2,
+            Rc::strong_count(&environment.global),
+            1,
             "Unexpected ref count"
         );
 
@@ -144,8 +190,8 @@ mod test {
             ))
         );
         assert_eq!(
-            Rc::strong_count(&environment.frame),
-            2,
+            Rc::strong_count(&environment.global),
+            1,
             "Unexpected ref count"
         );
 
@@ -153,8 +199,8 @@ mod test {
         let result = environment.get_at(None, a_key);
         assert_eq!(result, Ok(a_initial_value));
         assert_eq!(
-            Rc::strong_count(&environment.frame),
-            2,
+            Rc::strong_count(&environment.global),
+            1,
             "Unexpected ref count"
         );
 
@@ -162,23 +208,24 @@ mod test {
         let result = environment.assign_at(None, a_key, a_updated_value.clone());
         assert_eq!(result, Ok(()));
         assert_eq!(
-            Rc::strong_count(&environment.frame),
-            2,
+            Rc::strong_count(&environment.global),
+            1,
             "Unexpected ref count"
         );
 
         let result = environment.get_at(None, a_key);
         assert_eq!(result, Ok(a_updated_value.clone()));
         assert_eq!(
-            Rc::strong_count(&environment.frame),
-            2,
+            Rc::strong_count(&environment.global),
+            1,
             "Unexpected ref count"
         );
     }
 
     #[test]
     fn test_nested_environment() {
-        let mut original_environment = Environment::new();
+        let mut global_environment = Environment::new();
+        let mut original_environment = Environment::new_with_enclosing(&global_environment);
         let a_key = "a";
         let b_key = "b";
         let c_key = "c";
@@ -186,14 +233,16 @@ mod test {
         let a_initial_value = lox_type::LoxType::String("a value".to_string());
         let b_initial_value = lox_type::LoxType::String("b value".to_string());
         let c_initial_value = lox_type::LoxType::String("c value".to_string());
+        global_environment.define(a_key, a_initial_value.clone());
         original_environment.define(a_key, a_initial_value.clone());
         original_environment.define(b_key, b_initial_value.clone());
 
         let a_nested_value = lox_type::LoxType::String("a value (nested)".to_string());
         let b_updated_value = lox_type::LoxType::String("b value (updated)".to_string());
+        let frame = original_environment.frame.clone().unwrap();
 
         assert_eq!(
-            Rc::strong_count(&original_environment.frame),
+            Rc::strong_count(&frame),
             2,
             "Unexpected ref count before block"
         );
@@ -202,8 +251,8 @@ mod test {
             let mut nested_environment = Environment::new_with_enclosing(&original_environment);
 
             assert_eq!(
-                Rc::strong_count(&original_environment.frame),
-                4,
+                Rc::strong_count(&frame),
+                3,
                 "Unexpected ref count at start of block"
             );
 
@@ -211,12 +260,17 @@ mod test {
             nested_environment.define(c_key, c_initial_value.clone());
 
             let result = nested_environment.assign_at(None, b_key, b_updated_value.clone());
-            assert_eq!(result, Ok(()));
+            assert_eq!(
+                result,
+                Err(unwind::Unwind::WithError(
+                    "Undefined variable 'b'".to_string()
+                ))
+            );
 
-            let result = nested_environment.assign_at(Some(1), b_key, b_updated_value.clone());
+            let result = nested_environment.assign_at(local(1, 1), b_key, b_updated_value.clone());
             assert_eq!(result, Ok(()));
 
-            let result = nested_environment.assign_at(Some(2), b_key, b_updated_value.clone());
+            let result = nested_environment.assign_at(local(2, 1), b_key, b_updated_value.clone());
             assert_eq!(
                 result,
                 Err(unwind::Unwind::WithError(
@@ -224,35 +278,35 @@ mod test {
                 ))
             );
 
-            let result = nested_environment.get_at(Some(0), a_key);
+            let result = nested_environment.get_at(local(0, 0), a_key);
             assert_eq!(result, Ok(a_nested_value));
 
             let result = nested_environment.get_at(None, a_key);
             assert_eq!(result, Ok(a_initial_value.clone()));
 
-            let result = nested_environment.get_at(Some(1), a_key);
+            let result = nested_environment.get_at(local(1, 0), a_key);
             assert_eq!(result, Ok(a_initial_value.clone()));
 
-            let result = nested_environment.get_at(Some(1), b_key);
+            let result = nested_environment.get_at(local(1, 1), b_key);
             assert_eq!(result, Ok(b_updated_value.clone()));
 
-            let result = nested_environment.get_at(Some(0), c_key);
+            let result = nested_environment.get_at(local(0, 1), c_key);
             assert_eq!(result, Ok(c_initial_value));
         }
 
         assert_eq!(
-            Rc::strong_count(&original_environment.frame),
+            Rc::strong_count(&frame),
             2,
             "Unexpected ref count after block"
         );
 
-        let result = original_environment.get_at(Some(0), a_key);
+        let result = original_environment.get_at(local(0, 0), a_key);
         assert_eq!(result, Ok(a_initial_value.clone()));
 
-        let result = original_environment.get_at(Some(0), b_key);
+        let result = original_environment.get_at(local(0, 1), b_key);
         assert_eq!(result, Ok(b_updated_value.clone()));
 
-        let result = original_environment.get_at(Some(0), c_key);
+        let result = original_environment.get_at(local(0, 2), c_key);
         assert_eq!(
             result,
             Err(unwind::Unwind::WithError(
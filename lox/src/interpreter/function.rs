@@ -1,11 +1,22 @@
 use crate::interpreter::interpret_with_environment;
 use crate::interpreter::lox_type::LoxType;
-use crate::{interpreter::environment, interpreter::lox_type, interpreter::unwind, reporter, stmt};
-use std::collections::HashMap;
+use crate::{
+    interpreter::class, interpreter::environment, interpreter::gc, interpreter::generator,
+    interpreter::lox_type, interpreter::runtime, interpreter::unwind, reporter, resolver, stmt,
+    token,
+};
 use std::fmt::{Debug, Display, Formatter};
 use std::iter::zip;
 use std::rc::Rc;
 
+/// Bound alongside `this`. The leading `#` keeps it out of reach of Lox code.
+pub const OWNER: &str = "#class";
+
+/// Slots of `this` and the owner in the scope a method is bound in, following
+/// the order the resolver declares them.
+pub const THIS_SLOT: usize = 0;
+pub const OWNER_SLOT: usize = 2;
+
 struct InternalFunction {
     closure: environment::Environment,
     pub function: stmt::function::Function,
@@ -25,9 +36,16 @@ impl InternalFunction {
         }
     }
 
-    fn bind_this(&self, this: lox_type::LoxType) -> Self {
+    fn bind_this(
+        &self,
+        this: lox_type::LoxType,
+        owner: class::Class,
+        inner: lox_type::LoxType,
+    ) -> Self {
         let mut closure = environment::Environment::new_with_enclosing(&self.closure);
         closure.define("this", this);
+        closure.define("inner", inner);
+        closure.define(OWNER, lox_type::LoxType::Class { class: owner });
 
         Self {
             closure,
@@ -43,7 +61,15 @@ impl InternalFunction {
     ) -> Result<lox_type::LoxType, unwind::Unwind> {
         if self.is_initialiser {
             Err(unwind::Unwind::WithResult(
-                environment.get_at(Some(1), "this").unwrap(),
+                environment
+                    .get_at(
+                        Some(resolver::Local {
+                            depth: 1,
+                            slot: THIS_SLOT,
+                        }),
+                        "this",
+                    )
+                    .unwrap(),
             ))
         } else {
             Err(unwind::Unwind::WithResult(default))
@@ -67,6 +93,13 @@ impl PartialEq for InternalFunction {
     }
 }
 
+impl gc::Trace for InternalFunction {
+    fn trace(&self, visit: &mut dyn FnMut(gc::Id)) -> bool {
+        self.closure.trace(visit);
+        true
+    }
+}
+
 impl lox_type::Callable for InternalFunction {
     fn arity(&self) -> usize {
         self.function.params().len()
@@ -75,7 +108,7 @@ impl lox_type::Callable for InternalFunction {
     fn call(
         &self,
         reporter: &dyn reporter::Reporter,
-        depths: &HashMap<usize, usize>,
+        runtime: &runtime::Runtime,
         arguments: Vec<lox_type::LoxType>,
     ) -> Result<lox_type::LoxType, unwind::Unwind> {
         let closure = self.closure.clone();
@@ -85,10 +118,19 @@ impl lox_type::Callable for InternalFunction {
             environment.define(&k.lexeme, v);
         }
 
+        if self.function.is_generator() {
+            return Err(unwind::Unwind::WithResult(lox_type::LoxType::Iterator {
+                iterator: generator::generator(self.function.clone(), environment),
+            }));
+        }
+
         let result =
-            interpret_with_environment(reporter, depths, &mut environment, self.function.body());
+            interpret_with_environment(reporter, runtime, &mut environment, self.function.body());
         match result {
             Err(unwind::Unwind::WithError(message)) => Err(unwind::Unwind::WithError(message)),
+            Err(unwind::Unwind::WithRuntimeError(error)) => {
+                Err(unwind::Unwind::WithRuntimeError(error))
+            }
             Err(unwind::Unwind::WithResult(value)) => {
                 self.return_this_or_unwind_with(&environment, value)
             }
@@ -108,20 +150,41 @@ impl Function {
         function: stmt::function::Function,
         is_initialiser: bool,
     ) -> Self {
-        Self {
-            function: Rc::new(InternalFunction::new(closure, function, is_initialiser)),
-        }
+        let function = Rc::new(InternalFunction::new(closure, function, is_initialiser));
+        gc::track(&function);
+        Self { function }
     }
 
-    pub fn bind_this(&self, this: lox_type::LoxType) -> Self {
-        Self {
-            function: Rc::new(self.function.bind_this(this)),
-        }
+    /// Binds `this` for a method declared by `owner`, which is the class whose
+    /// private members the method can reach. `inner` is the next method down
+    /// the subclass chain, already bound to the same instance.
+    pub fn bind_this(
+        &self,
+        this: lox_type::LoxType,
+        owner: class::Class,
+        inner: lox_type::LoxType,
+    ) -> Self {
+        let function = Rc::new(self.function.bind_this(this, owner, inner));
+        gc::track(&function);
+        Self { function }
+    }
+
+    pub fn gc_id(&self) -> gc::Id {
+        gc::id(&self.function)
+    }
+
+    pub fn calls_inner(&self) -> bool {
+        self.function.function.calls_inner()
     }
 
     pub fn name(&self) -> &str {
         self.function.name()
     }
+
+    /// The name token of the declaration.
+    pub fn declaration(&self) -> &token::Token {
+        self.function.function.name()
+    }
 }
 
 impl lox_type::Callable for Function {
@@ -132,10 +195,10 @@ impl lox_type::Callable for Function {
     fn call(
         &self,
         reporter: &dyn reporter::Reporter,
-        depths: &HashMap<usize, usize>,
+        runtime: &runtime::Runtime,
         arguments: Vec<lox_type::LoxType>,
     ) -> Result<lox_type::LoxType, unwind::Unwind> {
-        self.function.call(reporter, depths, arguments)
+        self.function.call(reporter, runtime, arguments)
     }
 }
 
@@ -0,0 +1,200 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+/// Identifies a tracked allocation by the address of its contents.
+pub type Id = *const ();
+
+/// Heap sizes below this never trigger a collection.
+const MIN_THRESHOLD: usize = 10_000;
+
+/// An allocation that can hold references to other tracked allocations, and
+/// so can be part of a reference cycle.
+pub trait Trace {
+    /// Calls `visit` once for each strong reference this holds to a tracked
+    /// allocation. Returns false, without visiting anything, when the contents
+    /// are borrowed and cannot be looked at.
+    fn trace(&self, visit: &mut dyn FnMut(Id)) -> bool;
+
+    /// Drops the references this holds, breaking any cycle through it. Only
+    /// called once nothing outside the heap can reach it.
+    fn clear(&self) {}
+}
+
+thread_local! {
+    static HEAP: RefCell<Vec<Weak<dyn Trace>>> = const { RefCell::new(Vec::new()) };
+    static THRESHOLD: Cell<usize> = const { Cell::new(MIN_THRESHOLD) };
+}
+
+pub fn id<T: ?Sized>(rc: &Rc<T>) -> Id {
+    Rc::as_ptr(rc) as Id
+}
+
+/// Registers a new allocation with the collector.
+pub fn track<T: Trace + 'static>(rc: &Rc<T>) {
+    let weak: Weak<dyn Trace> = Rc::downgrade(rc) as Weak<dyn Trace>;
+    HEAP.with(|heap| heap.borrow_mut().push(weak));
+}
+
+/// How many tracked allocations are still alive.
+pub fn live() -> usize {
+    HEAP.with(|heap| {
+        heap.borrow()
+            .iter()
+            .filter(|node| node.strong_count() > 0)
+            .count()
+    })
+}
+
+/// Collects once the heap has doubled since the last collection.
+pub fn collect_if_due() {
+    let size = HEAP.with(|heap| heap.borrow().len());
+    if size >= THRESHOLD.with(Cell::get) {
+        collect();
+        THRESHOLD.with(|threshold| threshold.set(MIN_THRESHOLD.max(2 * live())));
+    }
+}
+
+/// Frees allocations that are only reachable from each other, and returns
+/// how many there were.
+///
+/// An allocation with more strong references than the heap itself holds is
+/// referenced from outside it: from a Rust variable, the interpreter's stack,
+/// or a value the collector cannot see into such as an iterator. Those are
+/// the roots; whatever they cannot reach is garbage, and clearing it breaks
+/// the cycles that kept it alive.
+pub fn collect() -> usize {
+    let nodes: Vec<Rc<dyn Trace>> = HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        heap.retain(|node| node.strong_count() > 0);
+        heap.iter().filter_map(Weak::upgrade).collect()
+    });
+    let index: HashMap<Id, usize> = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| (id(node), i))
+        .collect();
+
+    // Leave out the reference `nodes` holds, then the ones from other nodes.
+    let mut external: Vec<usize> = nodes
+        .iter()
+        .map(|node| Rc::strong_count(node) - 1)
+        .collect();
+    let mut reachable = vec![false; nodes.len()];
+    let mut pending = Vec::new();
+    for (i, node) in nodes.iter().enumerate() {
+        let traced = node.trace(&mut |child| {
+            if let Some(&child) = index.get(&child) {
+                external[child] = external[child].saturating_sub(1);
+            }
+        });
+        if !traced {
+            reachable[i] = true;
+            pending.push(i);
+        }
+    }
+    for (i, count) in external.iter().enumerate() {
+        if *count > 0 && !reachable[i] {
+            reachable[i] = true;
+            pending.push(i);
+        }
+    }
+
+    while let Some(i) = pending.pop() {
+        nodes[i].trace(&mut |child| {
+            if let Some(&child) = index.get(&child) {
+                if !reachable[child] {
+                    reachable[child] = true;
+                    pending.push(child);
+                }
+            }
+        });
+    }
+
+    let mut freed = 0;
+    for (node, reachable) in nodes.iter().zip(&reachable) {
+        if !reachable {
+            node.clear();
+            freed += 1;
+        }
+    }
+    drop(nodes);
+    HEAP.with(|heap| heap.borrow_mut().retain(|node| node.strong_count() > 0));
+    freed
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{embedding, reporter::test::TestReporter};
+
+    #[test]
+    fn test_cycles_are_collected() {
+        let reporter = TestReporter::new();
+        let mut session = embedding::Session::new();
+        session.evaluate(
+            &reporter,
+            "class Node {
+                init() {
+                    this.me = this;
+                    this.hello = this.hello;
+                    this.items = [this, {\"node\": this}];
+                }
+                hello() {}
+            }
+            fun counter() {
+                var count = 0;
+                fun next() { count = count + 1; return count; }
+                return next;
+            }",
+        );
+        collect();
+        let baseline = live();
+
+        session.evaluate(
+            &reporter,
+            "for (var i = 0; i < 20000; i = i + 1) { Node(); counter(); }",
+        );
+        assert!(!reporter.has_messages(), "Unexpected messages");
+        assert!(
+            live() < baseline + 4 * MIN_THRESHOLD,
+            "{} allocations still alive",
+            live()
+        );
+
+        assert!(collect() > 0);
+        assert!(
+            live() <= baseline + 10,
+            "{} allocations still alive",
+            live()
+        );
+        session.evaluate(&reporter, "print Node().me.items[1][\"node\"].me;");
+        assert!(reporter.has_message("[print] Node instance"));
+    }
+
+    #[test]
+    fn test_bound_host_methods_are_collected() {
+        let reporter = TestReporter::new();
+        let handle = embedding::NativeClass::<()>::new("Handle")
+            .constructor(0, |_| Ok(()))
+            .method("close", 0, |_, _| Ok(crate::interpreter::Value::nil()));
+        let mut session = embedding::Interpreter::builder()
+            .class(handle)
+            .build()
+            .session();
+        collect();
+        let baseline = live();
+
+        session.evaluate(
+            &reporter,
+            "for (var i = 0; i < 100; i = i + 1) { var h = Handle(); h.onClose = h.close; }",
+        );
+        assert!(!reporter.has_messages(), "Unexpected messages");
+        assert!(collect() > 0);
+        assert!(
+            live() <= baseline + 10,
+            "{} allocations still alive",
+            live()
+        );
+    }
+}
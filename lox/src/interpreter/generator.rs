@@ -0,0 +1,116 @@
+use crate::interpreter::{
+    environment, iterator, lox_type, resume_with_environment, runtime, unwind,
+};
+use crate::{reporter, stmt};
+use std::fmt::{Debug, Formatter};
+
+/// One step on the path from a generator's body down to the `yield` that
+/// suspended it. Frames are pushed innermost first while a yield unwinds, and
+/// popped outermost first when the generator is resumed.
+pub enum Resume {
+    Statements {
+        index: usize,
+        environment: environment::Environment,
+    },
+    If {
+        then_branch: bool,
+    },
+    While,
+    ForIn {
+        iterator: lox_type::LoxType,
+        environment: environment::Environment,
+    },
+    Yield,
+}
+
+enum State {
+    Suspended(Vec<Resume>),
+    Yielded(lox_type::LoxType, Vec<Resume>),
+    Done,
+}
+
+struct Generator {
+    function: stmt::function::Function,
+    environment: environment::Environment,
+    state: State,
+}
+
+impl Generator {
+    fn resume(
+        &mut self,
+        reporter: &dyn reporter::Reporter,
+        runtime: &runtime::Runtime,
+    ) -> Result<(), unwind::Unwind> {
+        let frames = match std::mem::replace(&mut self.state, State::Done) {
+            State::Suspended(frames) => frames,
+            state => {
+                self.state = state;
+                return Ok(());
+            }
+        };
+
+        let (result, frames) = resume_with_environment(
+            reporter,
+            runtime,
+            &mut self.environment,
+            self.function.body(),
+            frames,
+        );
+        match result {
+            Err(unwind::Unwind::Yield(value)) => {
+                self.state = State::Yielded(value, frames);
+                Ok(())
+            }
+            Err(unwind::Unwind::WithError(message)) => Err(unwind::Unwind::WithError(message)),
+            Err(unwind::Unwind::WithRuntimeError(error)) => {
+                Err(unwind::Unwind::WithRuntimeError(error))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl iterator::NativeIterator for Generator {
+    fn has_next(
+        &mut self,
+        reporter: &dyn reporter::Reporter,
+        runtime: &runtime::Runtime,
+    ) -> Result<bool, unwind::Unwind> {
+        self.resume(reporter, runtime)?;
+        Ok(matches!(self.state, State::Yielded(..)))
+    }
+
+    fn next(
+        &mut self,
+        reporter: &dyn reporter::Reporter,
+        runtime: &runtime::Runtime,
+    ) -> Result<Option<lox_type::LoxType>, unwind::Unwind> {
+        self.resume(reporter, runtime)?;
+        match std::mem::replace(&mut self.state, State::Done) {
+            State::Yielded(value, frames) => {
+                self.state = State::Suspended(frames);
+                Ok(Some(value))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+impl Debug for Generator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<generator {}>", self.function.name().lexeme)
+    }
+}
+
+/// The iterator returned by calling a generator function. Its body does not
+/// start running until the first `hasNext()` or `next()`.
+pub fn generator(
+    function: stmt::function::Function,
+    environment: environment::Environment,
+) -> iterator::LoxIterator {
+    iterator::LoxIterator::new(Box::new(Generator {
+        function,
+        environment,
+        state: State::Suspended(Vec::new()),
+    }))
+}
@@ -0,0 +1,140 @@
+use crate::interpreter::{gc, instance, lox_type, runtime, unwind, value};
+use crate::reporter;
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::rc::Rc;
+
+pub type HostConstructor = Rc<dyn Fn(Vec<value::Value>) -> Result<Box<dyn Any>, String>>;
+pub type HostMethod = Rc<dyn Fn(&mut dyn Any, Vec<value::Value>) -> Result<value::Value, String>>;
+pub type HostGetter = Rc<dyn Fn(&dyn Any) -> Result<value::Value, String>>;
+pub type HostSetter = Rc<dyn Fn(&mut dyn Any, value::Value) -> Result<(), String>>;
+
+/// The Rust side of a class defined by an embedding application: how to build
+/// the data each instance holds, and the methods and properties that use it.
+#[derive(Default)]
+pub struct HostClass {
+    pub constructor: Option<(usize, HostConstructor)>,
+    pub methods: HashMap<String, (usize, HostMethod)>,
+    pub getters: HashMap<String, HostGetter>,
+    pub setters: HashMap<String, HostSetter>,
+}
+
+impl HostClass {
+    /// The constructor, as `init`, and the methods, as natives that bind to
+    /// an instance like any other method.
+    pub fn methods(&self, class_name: &str) -> HashMap<String, lox_type::LoxType> {
+        let constructor = self
+            .constructor
+            .iter()
+            .map(|(arity, constructor)| ("init", *arity, Member::Init(constructor.clone())));
+        let methods = self
+            .methods
+            .iter()
+            .map(|(name, (arity, method))| (name.as_str(), *arity, Member::Method(method.clone())));
+        constructor
+            .chain(methods)
+            .map(|(name, arity, member)| {
+                let method = Method {
+                    class_name: class_name.to_string(),
+                    name: name.to_string(),
+                    arity,
+                    member,
+                    this: None,
+                };
+                (name.to_string(), method.into_lox_type())
+            })
+            .collect()
+    }
+}
+
+impl Debug for HostClass {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut methods: Vec<&String> = self.methods.keys().collect();
+        methods.sort();
+        f.debug_struct("HostClass")
+            .field("constructor", &self.constructor.is_some())
+            .field("methods", &methods)
+            .finish()
+    }
+}
+
+#[derive(Clone)]
+enum Member {
+    Init(HostConstructor),
+    Method(HostMethod),
+}
+
+/// A host constructor or method, bound to `this` once looked up on an
+/// instance.
+#[derive(Clone)]
+struct Method {
+    class_name: String,
+    name: String,
+    arity: usize,
+    member: Member,
+    this: Option<instance::Instance>,
+}
+
+impl Method {
+    fn into_lox_type(self) -> lox_type::LoxType {
+        let name = self.name.clone();
+        let bound = self.this.is_some();
+        let callable: Rc<Box<dyn lox_type::NativeCallable>> = Rc::new(Box::new(self));
+        // A bound method stored on its own instance is a cycle.
+        if bound {
+            gc::track(&callable);
+        }
+        lox_type::LoxType::NativeFunction { name, callable }
+    }
+}
+
+impl Debug for Method {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<host method {}.{}>", self.class_name, self.name)
+    }
+}
+
+impl lox_type::NativeCallable for Method {
+    fn call(
+        &self,
+        _: &dyn reporter::Reporter,
+        _: &runtime::Runtime,
+        arguments: Vec<lox_type::LoxType>,
+    ) -> Result<lox_type::LoxType, unwind::Unwind> {
+        let this = self.this.as_ref().ok_or_else(|| {
+            unwind::Unwind::WithError(format!("'{}' must be called on an instance", self.name))
+        })?;
+        let arguments = arguments.into_iter().map(value::Value).collect();
+        match &self.member {
+            Member::Init(constructor) => {
+                let data = constructor(arguments).map_err(unwind::Unwind::WithError)?;
+                this.set_host_data(data);
+                Ok(lox_type::LoxType::Nil)
+            }
+            Member::Method(method) => this
+                .with_host_data(&self.class_name, |data| method(data, arguments))
+                .map(|result| result.0),
+        }
+    }
+
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn bind(&self, this: &instance::Instance) -> Option<lox_type::LoxType> {
+        Some(
+            Method {
+                this: Some(this.clone()),
+                ..self.clone()
+            }
+            .into_lox_type(),
+        )
+    }
+
+    fn trace(&self, visit: &mut dyn FnMut(gc::Id)) {
+        if let Some(this) = &self.this {
+            visit(this.gc_id());
+        }
+    }
+}
@@ -1,7 +1,8 @@
-use crate::interpreter::lox_type::LoxType;
-use crate::interpreter::{class, lox_type, unwind};
+use crate::interpreter::lox_type::{Callable, LoxType};
+use crate::interpreter::{class, gc, lox_type, native_functions, unwind, value};
+use std::any::Any;
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
@@ -12,6 +13,9 @@ struct InternalInstance {
     id: usize,
     class: class::Class,
     fields: HashMap<String, lox_type::LoxType>,
+    private_fields: HashMap<(usize, String), lox_type::LoxType>,
+    running_protocols: HashSet<String>,
+    host_data: Option<Box<dyn Any>>,
 }
 
 impl InternalInstance {
@@ -24,6 +28,9 @@ impl InternalInstance {
             id: InternalInstance::get_id(),
             class,
             fields: HashMap::new(),
+            private_fields: HashMap::new(),
+            running_protocols: HashSet::new(),
+            host_data: None,
         }
     }
 
@@ -31,15 +38,24 @@ impl InternalInstance {
         self.class.name()
     }
 
-    pub fn find_method(&self, name: &str) -> Option<lox_type::LoxType> {
-        self.class.find_method(name)
-    }
-
     pub fn get(&self, name: &str) -> Result<lox_type::LoxType, unwind::Unwind> {
         if self.fields.contains_key(name) {
             Ok(self.fields.get(name).unwrap().clone())
-        } else if let Some(method) = self.find_method(name) {
-            Ok(method)
+        } else {
+            Err(unwind::Unwind::WithError(format!(
+                "Undefined property '{}'",
+                name
+            )))
+        }
+    }
+
+    pub fn get_private(
+        &self,
+        owner: &class::Class,
+        name: &str,
+    ) -> Result<lox_type::LoxType, unwind::Unwind> {
+        if let Some(value) = self.private_fields.get(&(owner.id(), name.to_string())) {
+            Ok(value.clone())
         } else {
             Err(unwind::Unwind::WithError(format!(
                 "Undefined property '{}'",
@@ -51,6 +67,11 @@ impl InternalInstance {
     pub fn set(&mut self, name: &str, value: lox_type::LoxType) {
         self.fields.insert(name.to_string(), value);
     }
+
+    pub fn set_private(&mut self, owner: &class::Class, name: &str, value: lox_type::LoxType) {
+        self.private_fields
+            .insert((owner.id(), name.to_string()), value);
+    }
 }
 
 impl PartialEq for InternalInstance {
@@ -59,6 +80,31 @@ impl PartialEq for InternalInstance {
     }
 }
 
+impl gc::Trace for RefCell<InternalInstance> {
+    fn trace(&self, visit: &mut dyn FnMut(gc::Id)) -> bool {
+        let Ok(instance) = self.try_borrow() else {
+            return false;
+        };
+        visit(instance.class.gc_id());
+        instance
+            .fields
+            .values()
+            .for_each(|value| value.trace(visit));
+        instance
+            .private_fields
+            .values()
+            .for_each(|value| value.trace(visit));
+        true
+    }
+
+    fn clear(&self) {
+        if let Ok(mut instance) = self.try_borrow_mut() {
+            instance.fields.clear();
+            instance.private_fields.clear();
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Instance {
     instance: Rc<RefCell<InternalInstance>>,
@@ -66,42 +112,173 @@ pub struct Instance {
 
 impl Instance {
     pub fn new(class: class::Class) -> Self {
-        Self {
-            instance: Rc::new(RefCell::new(InternalInstance::new(class))),
-        }
+        let instance = Rc::new(RefCell::new(InternalInstance::new(class)));
+        gc::track(&instance);
+        Self { instance }
+    }
+
+    pub fn gc_id(&self) -> gc::Id {
+        gc::id(&self.instance)
     }
 
     pub fn class_name(&self) -> String {
         self.instance.borrow().class_name().to_string()
     }
 
-    fn bind_this(&self, value: LoxType) -> LoxType {
-        if let LoxType::Function { function } = value {
+    pub fn class(&self) -> class::Class {
+        self.instance.borrow().class.clone()
+    }
+
+    /// Names of the public fields, in sorted order.
+    pub fn field_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.instance.borrow().fields.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Binds the first method in `chain`, with `inner` bound to the rest of it.
+    fn bind_this(&self, chain: &[(LoxType, class::Class)]) -> LoxType {
+        let (method, owner) = &chain[0];
+        if let LoxType::Function { function } = method {
+            let inner = if chain.len() > 1 {
+                self.bind_this(&chain[1..])
+            } else {
+                native_functions::no_inner(function.arity())
+            };
             LoxType::Function {
-                function: function.bind_this(LoxType::Instance {
-                    instance: self.clone(),
-                }),
+                function: function.bind_this(
+                    LoxType::Instance {
+                        instance: self.clone(),
+                    },
+                    owner.clone(),
+                    inner,
+                ),
             }
+        } else if let LoxType::NativeFunction { callable, .. } = method {
+            callable.bind(self).unwrap_or_else(|| method.clone())
         } else {
-            value
+            method.clone()
         }
     }
 
     pub fn get(&self, name: &str) -> Result<lox_type::LoxType, unwind::Unwind> {
-        let value = self.instance.borrow().get(name)?;
-        let value = self.bind_this(value);
-        Ok(value)
+        let field = self.instance.borrow().get(name);
+        match field {
+            Ok(value) => Ok(value),
+            Err(error) => {
+                let class = self.class();
+                if let Some(getter) = class
+                    .find_host()
+                    .and_then(|host| host.getters.get(name).cloned())
+                {
+                    return self
+                        .with_host_data(class.name(), |data| getter(data))
+                        .map(|value| value.0);
+                }
+                self.find_method(name).ok_or(error)
+            }
+        }
+    }
+
+    /// Reads a private field or method declared by `owner`.
+    pub fn get_private(&self, owner: &class::Class, name: &str) -> Result<LoxType, unwind::Unwind> {
+        let field = self.instance.borrow().get_private(owner, name);
+        match field {
+            Ok(value) => Ok(value),
+            Err(error) => owner
+                .find_private_method(name)
+                .map(|method| self.bind_this(&[(method, owner.clone())]))
+                .ok_or(error),
+        }
     }
 
+    /// Methods that call `inner()` take precedence over the subclass methods
+    /// they defer to; otherwise the most derived method wins.
     pub fn find_method(&self, name: &str) -> Option<LoxType> {
+        let chain = self.class().method_chain(name);
+        let start = chain
+            .iter()
+            .position(|(method, _)| {
+                matches!(method, LoxType::Function { function } if function.calls_inner())
+            })
+            .or(chain.len().checked_sub(1))?;
+        Some(self.bind_this(&chain[start..]))
+    }
+
+    /// Binds the method declared by `owner`, as reached through `super`.
+    pub fn find_method_from(&self, owner: &class::Class, name: &str) -> Option<LoxType> {
+        let chain = self.class().method_chain(name);
+        let start = chain
+            .iter()
+            .position(|(_, class)| class.id() == owner.id())?;
+        Some(self.bind_this(&chain[start..]))
+    }
+
+    /// Runs `call` with the protocol method `name` (e.g. `toString`) if the class
+    /// defines one. Returns `None` while that method is already running on this
+    /// instance, so a `toString` that prints `this` falls back to the default.
+    pub fn with_protocol_method<T>(
+        &self,
+        name: &str,
+        call: impl FnOnce(LoxType) -> Result<T, unwind::Unwind>,
+    ) -> Result<Option<T>, unwind::Unwind> {
+        if self.instance.borrow().running_protocols.contains(name) {
+            return Ok(None);
+        }
+        let method = match self.find_method(name) {
+            Some(method) => method,
+            None => return Ok(None),
+        };
+
         self.instance
-            .borrow()
-            .find_method(name)
-            .map(|f| self.bind_this(f))
+            .borrow_mut()
+            .running_protocols
+            .insert(name.to_string());
+        let result = call(method);
+        self.instance.borrow_mut().running_protocols.remove(name);
+        result.map(Some)
     }
 
-    pub fn set(&self, name: &str, value: LoxType) {
+    /// Sets a field, or a property through its host setter.
+    pub fn set(&self, name: &str, value: LoxType) -> Result<(), unwind::Unwind> {
+        let class = self.class();
+        if let Some(host) = class.find_host() {
+            if let Some(setter) = host.setters.get(name) {
+                return self.with_host_data(class.name(), |data| setter(data, value::Value(value)));
+            }
+            if host.getters.contains_key(name) {
+                return Err(unwind::Unwind::WithError(format!(
+                    "Property '{name}' is read-only"
+                )));
+            }
+        }
         self.instance.borrow_mut().set(name, value);
+        Ok(())
+    }
+
+    pub fn set_host_data(&self, data: Box<dyn Any>) {
+        self.instance.borrow_mut().host_data = Some(data);
+    }
+
+    /// Runs `call` on the data a host constructor made for this instance. The
+    /// data is taken out while `call` runs, so the instance is not borrowed.
+    pub fn with_host_data<T>(
+        &self,
+        class_name: &str,
+        call: impl FnOnce(&mut dyn Any) -> Result<T, String>,
+    ) -> Result<T, unwind::Unwind> {
+        let mut data = self.instance.borrow_mut().host_data.take().ok_or_else(|| {
+            unwind::Unwind::WithError(format!(
+                "Instance of '{class_name}' has not been constructed"
+            ))
+        })?;
+        let result = call(data.as_mut());
+        self.instance.borrow_mut().host_data = Some(data);
+        result.map_err(unwind::Unwind::WithError)
+    }
+
+    pub fn set_private(&self, owner: &class::Class, name: &str, value: LoxType) {
+        self.instance.borrow_mut().set_private(owner, name, value);
     }
 }
 
@@ -0,0 +1,169 @@
+use crate::interpreter::{list, lox_type, runtime, unwind};
+use crate::reporter;
+use std::cell::RefCell;
+use std::fmt::Debug;
+use std::rc::Rc;
+
+type IteratorResult<T> = Result<T, unwind::Unwind>;
+
+/// Iterators are handed the reporter and runtime so that generators can resume
+/// their function body.
+pub trait NativeIterator: Debug {
+    fn has_next(
+        &mut self,
+        reporter: &dyn reporter::Reporter,
+        runtime: &runtime::Runtime,
+    ) -> IteratorResult<bool>;
+    fn next(
+        &mut self,
+        reporter: &dyn reporter::Reporter,
+        runtime: &runtime::Runtime,
+    ) -> IteratorResult<Option<lox_type::LoxType>>;
+}
+
+/// Native side of the iterator protocol: `hasNext()` and `next()` on the value
+/// returned by `iterator()`.
+#[derive(Clone, Debug)]
+pub struct LoxIterator {
+    iterator: Rc<RefCell<Box<dyn NativeIterator>>>,
+}
+
+impl LoxIterator {
+    pub fn new(iterator: Box<dyn NativeIterator>) -> Self {
+        Self {
+            iterator: Rc::new(RefCell::new(iterator)),
+        }
+    }
+
+    pub fn has_next(
+        &self,
+        reporter: &dyn reporter::Reporter,
+        runtime: &runtime::Runtime,
+    ) -> IteratorResult<bool> {
+        self.borrow_mut()?.has_next(reporter, runtime)
+    }
+
+    pub fn next(
+        &self,
+        reporter: &dyn reporter::Reporter,
+        runtime: &runtime::Runtime,
+    ) -> IteratorResult<lox_type::LoxType> {
+        self.borrow_mut()?
+            .next(reporter, runtime)?
+            .ok_or_else(|| unwind::Unwind::WithError("Iterator is exhausted".to_string()))
+    }
+
+    fn borrow_mut(&self) -> IteratorResult<std::cell::RefMut<'_, Box<dyn NativeIterator>>> {
+        self.iterator
+            .try_borrow_mut()
+            .map_err(|_| unwind::Unwind::WithError("Iterator is already running".to_string()))
+    }
+}
+
+impl PartialEq for LoxIterator {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.iterator, &other.iterator)
+    }
+}
+
+#[derive(Debug)]
+struct ListIterator {
+    list: list::List,
+    index: usize,
+}
+
+impl NativeIterator for ListIterator {
+    fn has_next(
+        &mut self,
+        _: &dyn reporter::Reporter,
+        _: &runtime::Runtime,
+    ) -> IteratorResult<bool> {
+        Ok(self.index < self.list.len())
+    }
+
+    fn next(
+        &mut self,
+        _: &dyn reporter::Reporter,
+        _: &runtime::Runtime,
+    ) -> IteratorResult<Option<lox_type::LoxType>> {
+        let value = self.list.get_at(self.index);
+        if value.is_some() {
+            self.index += 1;
+        }
+        Ok(value)
+    }
+}
+
+#[derive(Debug)]
+struct ValuesIterator {
+    values: std::vec::IntoIter<lox_type::LoxType>,
+}
+
+impl NativeIterator for ValuesIterator {
+    fn has_next(
+        &mut self,
+        _: &dyn reporter::Reporter,
+        _: &runtime::Runtime,
+    ) -> IteratorResult<bool> {
+        Ok(self.values.len() > 0)
+    }
+
+    fn next(
+        &mut self,
+        _: &dyn reporter::Reporter,
+        _: &runtime::Runtime,
+    ) -> IteratorResult<Option<lox_type::LoxType>> {
+        Ok(self.values.next())
+    }
+}
+
+#[derive(Debug)]
+struct RangeIterator {
+    current: f64,
+    end: f64,
+}
+
+impl NativeIterator for RangeIterator {
+    fn has_next(
+        &mut self,
+        _: &dyn reporter::Reporter,
+        _: &runtime::Runtime,
+    ) -> IteratorResult<bool> {
+        Ok(self.current < self.end)
+    }
+
+    fn next(
+        &mut self,
+        _: &dyn reporter::Reporter,
+        _: &runtime::Runtime,
+    ) -> IteratorResult<Option<lox_type::LoxType>> {
+        if self.current < self.end {
+            let value = self.current;
+            self.current += 1.0;
+            Ok(Some(lox_type::LoxType::Number(value)))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+pub fn list(list: &list::List) -> LoxIterator {
+    LoxIterator::new(Box::new(ListIterator {
+        list: list.clone(),
+        index: 0,
+    }))
+}
+
+/// Iterates over a snapshot of the values, used for map keys and string characters.
+pub fn values(values: Vec<lox_type::LoxType>) -> LoxIterator {
+    LoxIterator::new(Box::new(ValuesIterator {
+        values: values.into_iter(),
+    }))
+}
+
+pub fn range(start: f64, end: f64) -> LoxIterator {
+    LoxIterator::new(Box::new(RangeIterator {
+        current: start,
+        end,
+    }))
+}
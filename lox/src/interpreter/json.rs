@@ -0,0 +1,398 @@
+use crate::interpreter::{list, lox_type, map};
+
+/// How deeply arrays and objects may nest, so that untrusted text or a
+/// deeply nested value can't overflow the native stack.
+const MAX_DEPTH: usize = 512;
+
+/// The widest indent, as in JavaScript; wider ones are narrowed to it.
+const MAX_INDENT: usize = 10;
+
+/// Parses JSON text into Lox values: objects become maps, arrays become
+/// lists and every number becomes a `Number`. An error names the line and
+/// column where the text stopped making sense.
+pub fn parse(text: &str) -> Result<lox_type::LoxType, String> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        current: 0,
+        depth: 0,
+    };
+    parser.skip_whitespace();
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.current < parser.chars.len() {
+        return Err(parser.error("unexpected text after the value"));
+    }
+    Ok(value)
+}
+
+/// Formats a value as JSON. With `indent`, nested values go on their own
+/// lines indented by that many spaces, up to 10; without it the output is
+/// compact.
+pub fn stringify(value: &lox_type::LoxType, indent: Option<usize>) -> Result<String, String> {
+    let mut writer = Writer {
+        indent: indent.map(|indent| indent.min(MAX_INDENT)),
+        out: String::new(),
+        open: Vec::new(),
+    };
+    writer.value(value)?;
+    Ok(writer.out)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    current: usize,
+    /// How many arrays and objects the parser is inside.
+    depth: usize,
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> String {
+        let before = &self.chars[..self.current.min(self.chars.len())];
+        let line = before.iter().filter(|c| **c == '\n').count() + 1;
+        let column = before.iter().rev().take_while(|c| **c != '\n').count() + 1;
+        format!("Invalid JSON at line {line}, column {column}: {message}")
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.current).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.current += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        if self.peek() == Some(expected) {
+            self.current += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{expected}'")))
+        }
+    }
+
+    fn value(&mut self) -> Result<lox_type::LoxType, String> {
+        match self.peek() {
+            Some('{' | '[') if self.depth == MAX_DEPTH => Err(self.error("nesting too deep")),
+            Some('{') => self.nested(Self::object),
+            Some('[') => self.nested(Self::array),
+            Some('"') => self.string().map(lox_type::LoxType::String),
+            Some('-' | '0'..='9') => self.number(),
+            Some('t') => self.literal("true", lox_type::LoxType::Boolean(true)),
+            Some('f') => self.literal("false", lox_type::LoxType::Boolean(false)),
+            Some('n') => self.literal("null", lox_type::LoxType::Nil),
+            Some(_) => Err(self.error("expected a value")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn nested(
+        &mut self,
+        parse: fn(&mut Self) -> Result<lox_type::LoxType, String>,
+    ) -> Result<lox_type::LoxType, String> {
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn literal(
+        &mut self,
+        word: &str,
+        value: lox_type::LoxType,
+    ) -> Result<lox_type::LoxType, String> {
+        let word: Vec<char> = word.chars().collect();
+        if !self.chars[self.current..].starts_with(&word) {
+            return Err(self.error("expected a value"));
+        }
+        self.current += word.len();
+        Ok(value)
+    }
+
+    fn object(&mut self) -> Result<lox_type::LoxType, String> {
+        self.current += 1;
+        let map = map::Map::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.current += 1;
+            return Ok(lox_type::LoxType::Map { map });
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return Err(self.error("expected a string key"));
+            }
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            self.skip_whitespace();
+            let value = self.value()?;
+            map.set(lox_type::LoxType::String(key), value)
+                .map_err(|_| self.error("invalid key"))?;
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.current += 1,
+                Some('}') => {
+                    self.current += 1;
+                    return Ok(lox_type::LoxType::Map { map });
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<lox_type::LoxType, String> {
+        self.current += 1;
+        let mut elements = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.current += 1;
+            return Ok(lox_type::LoxType::List {
+                list: list::List::new(elements),
+            });
+        }
+        loop {
+            self.skip_whitespace();
+            elements.push(self.value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.current += 1,
+                Some(']') => {
+                    self.current += 1;
+                    return Ok(lox_type::LoxType::List {
+                        list: list::List::new(elements),
+                    });
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.current += 1;
+        let mut string = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(self.error("unterminated string")),
+                Some('"') => {
+                    self.current += 1;
+                    return Ok(string);
+                }
+                Some('\\') => {
+                    self.current += 1;
+                    let escaped = match self.peek() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => self.unicode_escape()?,
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    self.current += 1;
+                    string.push(escaped);
+                }
+                Some(c) if c < ' ' => return Err(self.error("control character in string")),
+                Some(c) => {
+                    self.current += 1;
+                    string.push(c);
+                }
+            }
+        }
+    }
+
+    /// Reads the digits of a `\u` escape, and the low half of a surrogate
+    /// pair when there is one. Leaves `current` on the last digit.
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.hex_digits()?;
+        if !(0xD800..0xDC00).contains(&high) {
+            return char::from_u32(high).ok_or_else(|| self.error("invalid escape"));
+        }
+        if self.chars.get(self.current + 1) != Some(&'\\')
+            || self.chars.get(self.current + 2) != Some(&'u')
+        {
+            return Err(self.error("invalid escape"));
+        }
+        self.current += 2;
+        let low = self.hex_digits()?;
+        if !(0xDC00..0xE000).contains(&low) {
+            return Err(self.error("invalid escape"));
+        }
+        char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00))
+            .ok_or_else(|| self.error("invalid escape"))
+    }
+
+    fn hex_digits(&mut self) -> Result<u32, String> {
+        let mut code = 0;
+        for _ in 0..4 {
+            self.current += 1;
+            let digit = self
+                .peek()
+                .and_then(|c| c.to_digit(16))
+                .ok_or_else(|| self.error("invalid escape"))?;
+            code = code * 16 + digit;
+        }
+        Ok(code)
+    }
+
+    fn number(&mut self) -> Result<lox_type::LoxType, String> {
+        let start = self.current;
+        if self.peek() == Some('-') {
+            self.current += 1;
+        }
+        match self.peek() {
+            Some('0') => self.current += 1,
+            Some('1'..='9') => self.digits(),
+            _ => return Err(self.error("expected a digit")),
+        }
+        if self.peek() == Some('.') {
+            self.current += 1;
+            if !matches!(self.peek(), Some('0'..='9')) {
+                return Err(self.error("expected a digit"));
+            }
+            self.digits();
+        }
+        if matches!(self.peek(), Some('e' | 'E')) {
+            self.current += 1;
+            if matches!(self.peek(), Some('+' | '-')) {
+                self.current += 1;
+            }
+            if !matches!(self.peek(), Some('0'..='9')) {
+                return Err(self.error("expected a digit"));
+            }
+            self.digits();
+        }
+        let text: String = self.chars[start..self.current].iter().collect();
+        text.parse()
+            .map(lox_type::LoxType::Number)
+            .map_err(|_| self.error("invalid number"))
+    }
+
+    fn digits(&mut self) {
+        while matches!(self.peek(), Some('0'..='9')) {
+            self.current += 1;
+        }
+    }
+}
+
+struct Writer {
+    indent: Option<usize>,
+    out: String,
+    /// The lists, maps and instances being written, to detect cycles.
+    open: Vec<lox_type::LoxType>,
+}
+
+impl Writer {
+    fn value(&mut self, value: &lox_type::LoxType) -> Result<(), String> {
+        match value {
+            lox_type::LoxType::Nil => self.out.push_str("null"),
+            lox_type::LoxType::Boolean(value) => self.out.push_str(&value.to_string()),
+            lox_type::LoxType::Number(number) if number.is_finite() => {
+                self.out.push_str(&number.to_string())
+            }
+            lox_type::LoxType::Number(_) => {
+                return Err("Cannot convert a non-finite number to JSON".to_string())
+            }
+            lox_type::LoxType::String(string) => self.string(string),
+            lox_type::LoxType::EnumMember { member } => self.string(member.name()),
+            lox_type::LoxType::List { list } => {
+                let elements = list.elements().into_iter().map(|e| (None, e)).collect();
+                self.container(value, '[', ']', elements)?
+            }
+            lox_type::LoxType::Map { map } => {
+                let entries = map
+                    .entries()
+                    .into_iter()
+                    .map(|(key, value)| (Some(key.text()), value))
+                    .collect();
+                self.container(value, '{', '}', entries)?
+            }
+            lox_type::LoxType::Instance { instance } => {
+                let fields = instance
+                    .field_names()
+                    .into_iter()
+                    .map(|name| {
+                        let field = instance.get(&name).unwrap_or(lox_type::LoxType::Nil);
+                        (Some(name), field)
+                    })
+                    .collect();
+                self.container(value, '{', '}', fields)?
+            }
+            other => {
+                return Err(format!(
+                    "Cannot convert a value of type '{}' to JSON",
+                    other.type_name()
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes an array, or an object when the entries have keys.
+    fn container(
+        &mut self,
+        value: &lox_type::LoxType,
+        open: char,
+        close: char,
+        entries: Vec<(Option<String>, lox_type::LoxType)>,
+    ) -> Result<(), String> {
+        if self.open.contains(value) {
+            return Err("Cannot convert a cyclic structure to JSON".to_string());
+        }
+        if self.open.len() == MAX_DEPTH {
+            return Err(format!(
+                "Cannot convert a structure nested more than {MAX_DEPTH} deep to JSON"
+            ));
+        }
+        self.open.push(value.clone());
+        self.out.push(open);
+        for (i, (key, entry)) in entries.iter().enumerate() {
+            if i > 0 {
+                self.out.push(',');
+            }
+            self.newline();
+            if let Some(key) = key {
+                self.string(key);
+                self.out.push(':');
+                if self.indent.is_some() {
+                    self.out.push(' ');
+                }
+            }
+            self.value(entry)?;
+        }
+        self.open.pop();
+        if !entries.is_empty() {
+            self.newline();
+        }
+        self.out.push(close);
+        Ok(())
+    }
+
+    fn newline(&mut self) {
+        if let Some(indent) = self.indent {
+            self.out.push('\n');
+            self.out
+                .push_str(&" ".repeat(indent.saturating_mul(self.open.len())));
+        }
+    }
+
+    fn string(&mut self, string: &str) {
+        self.out.push('"');
+        for c in string.chars() {
+            match c {
+                '"' => self.out.push_str("\\\""),
+                '\\' => self.out.push_str("\\\\"),
+                '\n' => self.out.push_str("\\n"),
+                '\r' => self.out.push_str("\\r"),
+                '\t' => self.out.push_str("\\t"),
+                c if c < ' ' => self.out.push_str(&format!("\\u{:04x}", c as u32)),
+                c => self.out.push(c),
+            }
+        }
+        self.out.push('"');
+    }
+}
@@ -0,0 +1,88 @@
+use crate::interpreter::{gc, lox_type, unwind};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[derive(Clone, Debug)]
+pub struct List {
+    elements: Rc<RefCell<Vec<lox_type::LoxType>>>,
+}
+
+impl List {
+    pub fn new(elements: Vec<lox_type::LoxType>) -> Self {
+        let elements = Rc::new(RefCell::new(elements));
+        gc::track(&elements);
+        Self { elements }
+    }
+
+    pub fn gc_id(&self) -> gc::Id {
+        gc::id(&self.elements)
+    }
+
+    pub fn len(&self) -> usize {
+        self.elements.borrow().len()
+    }
+
+    pub fn elements(&self) -> Vec<lox_type::LoxType> {
+        self.elements.borrow().clone()
+    }
+
+    pub fn get(&self, index: &lox_type::LoxType) -> Result<lox_type::LoxType, unwind::Unwind> {
+        let index = self.check_index(index)?;
+        Ok(self.elements.borrow()[index].clone())
+    }
+
+    pub fn get_at(&self, index: usize) -> Option<lox_type::LoxType> {
+        self.elements.borrow().get(index).cloned()
+    }
+
+    pub fn set(
+        &self,
+        index: &lox_type::LoxType,
+        value: lox_type::LoxType,
+    ) -> Result<(), unwind::Unwind> {
+        let index = self.check_index(index)?;
+        self.elements.borrow_mut()[index] = value;
+        Ok(())
+    }
+
+    pub fn push(&self, value: lox_type::LoxType) {
+        self.elements.borrow_mut().push(value);
+    }
+
+    fn check_index(&self, index: &lox_type::LoxType) -> Result<usize, unwind::Unwind> {
+        match index {
+            lox_type::LoxType::Number(number) if number.fract() == 0.0 => {
+                if *number >= 0.0 && (*number as usize) < self.len() {
+                    Ok(*number as usize)
+                } else {
+                    Err(unwind::Unwind::WithError("Index out of range".to_string()))
+                }
+            }
+            _ => Err(unwind::Unwind::WithError(
+                "Index must be an integer".to_string(),
+            )),
+        }
+    }
+}
+
+impl gc::Trace for RefCell<Vec<lox_type::LoxType>> {
+    fn trace(&self, visit: &mut dyn FnMut(gc::Id)) -> bool {
+        let Ok(elements) = self.try_borrow() else {
+            return false;
+        };
+        elements.iter().for_each(|element| element.trace(visit));
+        true
+    }
+
+    fn clear(&self) {
+        if let Ok(mut elements) = self.try_borrow_mut() {
+            std::mem::take(&mut *elements);
+        }
+    }
+}
+
+impl PartialEq for List {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.elements, &other.elements)
+    }
+}
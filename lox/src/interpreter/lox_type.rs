@@ -1,24 +1,86 @@
 use crate::{
-    interpreter::class, interpreter::function, interpreter::instance, interpreter::lox_type,
-    interpreter::unwind, reporter,
+    interpreter::class, interpreter::enumeration, interpreter::function, interpreter::gc,
+    interpreter::instance, interpreter::iterator, interpreter::list, interpreter::lox_type,
+    interpreter::map, interpreter::native_methods, interpreter::runtime, interpreter::unwind,
+    reporter,
 };
-use std::collections::HashMap;
+use std::cell::RefCell;
 use std::fmt::{Debug, Display, Formatter};
 use std::rc::Rc;
 
+thread_local! {
+    /// The lists, maps and instances being turned into text, outermost first.
+    static PRINTING: RefCell<Vec<LoxType>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Runs `print` with `value` marked as being printed, or returns `None`
+/// without running it when `value` is already being printed further out, as
+/// happens when a list, map or instance contains itself.
+pub fn print_once<T>(value: &LoxType, print: impl FnOnce() -> T) -> Option<T> {
+    let repeated = PRINTING.with(|printing| {
+        let mut printing = printing.borrow_mut();
+        let repeated = printing.contains(value);
+        if !repeated {
+            printing.push(value.clone());
+        }
+        repeated
+    });
+    if repeated {
+        return None;
+    }
+    let _printed = Printed;
+    Some(print())
+}
+
+/// Unmarks the innermost value being printed once dropped, even on an error.
+struct Printed;
+
+impl Drop for Printed {
+    fn drop(&mut self) {
+        PRINTING.with(|printing| printing.borrow_mut().pop());
+    }
+}
+
 pub trait Callable: Debug {
     fn call(
         &self,
         reporter: &dyn reporter::Reporter,
-        depths: &HashMap<usize, usize>,
+        runtime: &runtime::Runtime,
         arguments: Vec<lox_type::LoxType>,
     ) -> Result<lox_type::LoxType, unwind::Unwind>;
     fn arity(&self) -> usize;
 }
 
 pub trait NativeCallable: Debug {
-    fn call(&self, arguments: Vec<lox_type::LoxType>) -> Result<lox_type::LoxType, unwind::Unwind>;
+    fn call(
+        &self,
+        reporter: &dyn reporter::Reporter,
+        runtime: &runtime::Runtime,
+        arguments: Vec<lox_type::LoxType>,
+    ) -> Result<lox_type::LoxType, unwind::Unwind>;
     fn arity(&self) -> usize;
+
+    /// How many of the trailing parameters may be left out. Missing arguments
+    /// are passed as `nil`.
+    fn optional(&self) -> usize {
+        0
+    }
+
+    /// The method bound to `this`, for natives that are methods of a class.
+    fn bind(&self, _this: &instance::Instance) -> Option<LoxType> {
+        None
+    }
+
+    /// Visits the tracked allocations this holds, for natives the collector
+    /// tracks, such as a host method bound to its instance.
+    fn trace(&self, _visit: &mut dyn FnMut(gc::Id)) {}
+}
+
+impl gc::Trace for Box<dyn NativeCallable> {
+    fn trace(&self, visit: &mut dyn FnMut(gc::Id)) -> bool {
+        NativeCallable::trace(self.as_ref(), visit);
+        true
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -27,12 +89,27 @@ pub enum LoxType {
     Class {
         class: class::Class,
     },
+    Enum {
+        enumeration: enumeration::Enum,
+    },
+    EnumMember {
+        member: enumeration::Member,
+    },
     Function {
         function: function::Function,
     },
     Instance {
         instance: instance::Instance,
     },
+    Iterator {
+        iterator: iterator::LoxIterator,
+    },
+    List {
+        list: list::List,
+    },
+    Map {
+        map: map::Map,
+    },
     Number(f64),
     NativeFunction {
         name: String,
@@ -59,6 +136,20 @@ impl PartialEq for LoxType {
                     false
                 }
             }
+            LoxType::Enum { enumeration: value } => {
+                if let LoxType::Enum { enumeration: other } = other {
+                    other == value
+                } else {
+                    false
+                }
+            }
+            LoxType::EnumMember { member: value } => {
+                if let LoxType::EnumMember { member: other } = other {
+                    other == value
+                } else {
+                    false
+                }
+            }
             LoxType::Function {
                 function: value, ..
             } => {
@@ -83,6 +174,27 @@ impl PartialEq for LoxType {
                     false
                 }
             }
+            LoxType::Iterator { iterator: value } => {
+                if let LoxType::Iterator { iterator: other } = other {
+                    other == value
+                } else {
+                    false
+                }
+            }
+            LoxType::List { list: value } => {
+                if let LoxType::List { list: other } = other {
+                    other == value
+                } else {
+                    false
+                }
+            }
+            LoxType::Map { map: value } => {
+                if let LoxType::Map { map: other } = other {
+                    other == value
+                } else {
+                    false
+                }
+            }
             LoxType::NativeFunction { name: value, .. } => {
                 if let LoxType::NativeFunction { name: other, .. } = other {
                     other == value
@@ -114,10 +226,37 @@ impl Display for LoxType {
         match self {
             LoxType::Boolean(bool) => write!(f, "{bool}"),
             LoxType::Class { class, .. } => write!(f, "\"class {}\"", class.name()),
+            LoxType::Enum { enumeration } => write!(f, "\"enum {}\"", enumeration.name()),
+            LoxType::EnumMember { member } => {
+                write!(f, "{}.{}", member.enumeration().name(), member.name())
+            }
             LoxType::Function { function, .. } => write!(f, "\"fun {}\"", function.name()),
             LoxType::Instance { instance, .. } => {
                 write!(f, "\"instance of {}\"", instance.class_name())
             }
+            LoxType::Iterator { .. } => write!(f, "\"iterator\""),
+            LoxType::List { list } => print_once(self, || {
+                write!(f, "[")?;
+                for (i, element) in list.elements().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{element}")?;
+                }
+                write!(f, "]")
+            })
+            .unwrap_or_else(|| write!(f, "[...]")),
+            LoxType::Map { map } => print_once(self, || {
+                write!(f, "{{")?;
+                for (i, (key, value)) in map.entries().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{key}: {value}")?;
+                }
+                write!(f, "}}")
+            })
+            .unwrap_or_else(|| write!(f, "{{...}}")),
             LoxType::NativeFunction { name, .. } => write!(f, "\"native fun {name}\""),
             LoxType::Nil => write!(f, "nil"),
             LoxType::Number(number) => write!(f, "{number}"),
@@ -127,6 +266,54 @@ impl Display for LoxType {
 }
 
 impl LoxType {
+    /// The text `print` and `str` show for the value, formatted as in the
+    /// book: strings without quotes, `<fn name>` for functions and
+    /// `Name instance` for instances.
+    pub fn text(&self) -> String {
+        match self {
+            LoxType::Class { class, .. } => class.name().to_string(),
+            LoxType::Enum { enumeration } => format!("enum {}", enumeration.name()),
+            LoxType::Function { function, .. } => format!("<fn {}>", function.name()),
+            LoxType::Instance { instance } => format!("{} instance", instance.class_name()),
+            LoxType::Iterator { .. } => "iterator".to_string(),
+            LoxType::NativeFunction { .. } => "<native fn>".to_string(),
+            LoxType::String(string) => string.clone(),
+            _ => self.to_string(),
+        }
+    }
+
+    /// Visits the tracked allocation this value refers to, if any.
+    pub fn trace(&self, visit: &mut dyn FnMut(gc::Id)) {
+        match self {
+            LoxType::Class { class } => visit(class.gc_id()),
+            LoxType::Function { function } => visit(function.gc_id()),
+            LoxType::Instance { instance } => visit(instance.gc_id()),
+            LoxType::List { list } => visit(list.gc_id()),
+            LoxType::Map { map } => visit(map.gc_id()),
+            LoxType::NativeFunction { callable, .. } => visit(gc::id(callable)),
+            _ => (),
+        }
+    }
+
+    /// The name `typeof` gives this value.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            LoxType::Boolean(_) => "boolean",
+            LoxType::Class { .. } => "class",
+            LoxType::Enum { .. } => "enum",
+            LoxType::EnumMember { .. } => "enum member",
+            LoxType::Function { .. } => "function",
+            LoxType::Instance { .. } => "instance",
+            LoxType::Iterator { .. } => "iterator",
+            LoxType::List { .. } => "list",
+            LoxType::Map { .. } => "map",
+            LoxType::NativeFunction { .. } => "function",
+            LoxType::Nil => "nil",
+            LoxType::Number(_) => "number",
+            LoxType::String(_) => "string",
+        }
+    }
+
     pub fn find_instance_method(instance: &LoxType, name: &str) -> Option<LoxType> {
         if let LoxType::Instance { instance } = instance {
             instance.find_method(name)
@@ -135,12 +322,58 @@ impl LoxType {
         }
     }
 
-    pub fn get_instance_value(
+    pub fn get_property(object: &LoxType, name: &str) -> Result<lox_type::LoxType, unwind::Unwind> {
+        if let LoxType::Instance { instance } = object {
+            instance.get(name)
+        } else if let Some(member) = Self::find_enum_member(object, name) {
+            Ok(member)
+        } else if let Some(method) = native_methods::find_method(object, name) {
+            Ok(method)
+        } else if native_methods::has_methods(object) {
+            Err(unwind::Unwind::WithError(format!(
+                "Undefined property '{}'",
+                name
+            )))
+        } else {
+            Err(unwind::Unwind::WithError(
+                "Only instances have fields".to_string(),
+            ))
+        }
+    }
+
+    fn find_enum_member(object: &LoxType, name: &str) -> Option<LoxType> {
+        if let LoxType::Enum { enumeration } = object {
+            enumeration
+                .member(name)
+                .map(|member| LoxType::EnumMember { member })
+        } else {
+            None
+        }
+    }
+
+    pub fn get_private_property(
+        object: &LoxType,
+        owner: &class::Class,
+        name: &str,
+    ) -> Result<LoxType, unwind::Unwind> {
+        if let LoxType::Instance { instance } = object {
+            instance.get_private(owner, name)
+        } else {
+            Err(unwind::Unwind::WithError(
+                "Only instances have fields".to_string(),
+            ))
+        }
+    }
+
+    pub fn set_private_instance_value(
         instance: &LoxType,
+        owner: &class::Class,
         name: &str,
-    ) -> Result<lox_type::LoxType, unwind::Unwind> {
+        value: LoxType,
+    ) -> Result<(), unwind::Unwind> {
         if let LoxType::Instance { instance } = instance {
-            instance.get(name)
+            instance.set_private(owner, name, value);
+            Ok(())
         } else {
             Err(unwind::Unwind::WithError(
                 "Only instances have fields".to_string(),
@@ -154,12 +387,46 @@ impl LoxType {
         value: LoxType,
     ) -> Result<(), unwind::Unwind> {
         if let LoxType::Instance { instance } = instance {
-            instance.set(name, value);
-            Ok(())
+            instance.set(name, value)
         } else {
             Err(unwind::Unwind::WithError(
                 "Only instances have fields".to_string(),
             ))
         }
     }
+
+    pub fn get_index(object: &LoxType, index: &LoxType) -> Result<LoxType, unwind::Unwind> {
+        match object {
+            LoxType::List { list } => list.get(index),
+            LoxType::Map { map } => map.get(index),
+            LoxType::String(string) => {
+                let characters = LoxType::List {
+                    list: list::List::new(
+                        string
+                            .chars()
+                            .map(|c| LoxType::String(c.to_string()))
+                            .collect(),
+                    ),
+                };
+                LoxType::get_index(&characters, index)
+            }
+            _ => Err(unwind::Unwind::WithError(
+                "Only lists, maps and strings can be indexed".to_string(),
+            )),
+        }
+    }
+
+    pub fn set_index(
+        object: &LoxType,
+        index: &LoxType,
+        value: LoxType,
+    ) -> Result<(), unwind::Unwind> {
+        match object {
+            LoxType::List { list } => list.set(index, value),
+            LoxType::Map { map } => map.set(index.clone(), value),
+            _ => Err(unwind::Unwind::WithError(
+                "Only lists and maps support index assignment".to_string(),
+            )),
+        }
+    }
 }
@@ -0,0 +1,97 @@
+use crate::interpreter::{gc, lox_type, unwind};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Entries are kept in insertion order; keys are restricted to strings and numbers.
+#[derive(Clone, Debug)]
+pub struct Map {
+    entries: Rc<RefCell<Vec<(lox_type::LoxType, lox_type::LoxType)>>>,
+}
+
+impl Map {
+    pub fn new() -> Self {
+        let entries = Rc::new(RefCell::new(Vec::new()));
+        gc::track(&entries);
+        Self { entries }
+    }
+
+    pub fn gc_id(&self) -> gc::Id {
+        gc::id(&self.entries)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.borrow().len()
+    }
+
+    pub fn keys(&self) -> Vec<lox_type::LoxType> {
+        self.entries
+            .borrow()
+            .iter()
+            .map(|(k, _)| k.clone())
+            .collect()
+    }
+
+    pub fn entries(&self) -> Vec<(lox_type::LoxType, lox_type::LoxType)> {
+        self.entries.borrow().clone()
+    }
+
+    pub fn get(&self, key: &lox_type::LoxType) -> Result<lox_type::LoxType, unwind::Unwind> {
+        Map::check_key(key)?;
+        Ok(self
+            .entries
+            .borrow()
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.clone())
+            .unwrap_or(lox_type::LoxType::Nil))
+    }
+
+    pub fn set(
+        &self,
+        key: lox_type::LoxType,
+        value: lox_type::LoxType,
+    ) -> Result<(), unwind::Unwind> {
+        Map::check_key(&key)?;
+        let mut entries = self.entries.borrow_mut();
+        if let Some(entry) = entries.iter_mut().find(|(k, _)| *k == key) {
+            entry.1 = value;
+        } else {
+            entries.push((key, value));
+        }
+        Ok(())
+    }
+
+    fn check_key(key: &lox_type::LoxType) -> Result<(), unwind::Unwind> {
+        match key {
+            lox_type::LoxType::String(_) | lox_type::LoxType::Number(_) => Ok(()),
+            _ => Err(unwind::Unwind::WithError(
+                "Map key must be a string or a number".to_string(),
+            )),
+        }
+    }
+}
+
+impl gc::Trace for RefCell<Vec<(lox_type::LoxType, lox_type::LoxType)>> {
+    fn trace(&self, visit: &mut dyn FnMut(gc::Id)) -> bool {
+        let Ok(entries) = self.try_borrow() else {
+            return false;
+        };
+        for (key, value) in entries.iter() {
+            key.trace(visit);
+            value.trace(visit);
+        }
+        true
+    }
+
+    fn clear(&self) {
+        if let Ok(mut entries) = self.try_borrow_mut() {
+            std::mem::take(&mut *entries);
+        }
+    }
+}
+
+impl PartialEq for Map {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.entries, &other.entries)
+    }
+}
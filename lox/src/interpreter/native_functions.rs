@@ -1,28 +1,667 @@
-use crate::{interpreter::lox_type, interpreter::unwind};
+use crate::interpreter;
+use crate::interpreter::is_truthy;
+use crate::{
+    interpreter::iterator, interpreter::json, interpreter::list, interpreter::lox_type,
+    interpreter::runtime, interpreter::unwind, interpreter::value, reporter,
+};
+use std::fmt::{Debug, Formatter};
 use std::rc::Rc;
-use std::time;
+use std::{env, fs, io};
 
+/// Seconds since the Unix epoch, from the runtime's clock.
+pub fn clock() -> lox_type::LoxType {
+    context_native("clock", 0, |context, _| {
+        Ok(lox_type::LoxType::Number(context.runtime.now()))
+    })
+}
+
+#[derive(Debug)]
+struct Range;
+
+impl lox_type::NativeCallable for Range {
+    fn call(
+        &self,
+        _: &dyn reporter::Reporter,
+        _: &runtime::Runtime,
+        arguments: Vec<lox_type::LoxType>,
+    ) -> Result<lox_type::LoxType, unwind::Unwind> {
+        match (&arguments[0], &arguments[1]) {
+            (lox_type::LoxType::Number(start), lox_type::LoxType::Number(end)) => {
+                Ok(lox_type::LoxType::Iterator {
+                    iterator: iterator::range(*start, *end),
+                })
+            }
+            _ => Err(unwind::Unwind::WithError(
+                "Arguments to 'range' must be numbers".to_string(),
+            )),
+        }
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+}
+
+pub fn range() -> lox_type::LoxType {
+    lox_type::LoxType::NativeFunction {
+        name: "range".to_string(),
+        callable: Rc::new(Box::new(Range)),
+    }
+}
+
+type NativeResult = Result<lox_type::LoxType, unwind::Unwind>;
+
+/// A native backed by a plain function of its arguments.
+#[derive(Debug)]
+struct Native {
+    arity: usize,
+    function: fn(Vec<lox_type::LoxType>) -> NativeResult,
+}
+
+impl lox_type::NativeCallable for Native {
+    fn call(
+        &self,
+        _: &dyn reporter::Reporter,
+        _: &runtime::Runtime,
+        arguments: Vec<lox_type::LoxType>,
+    ) -> NativeResult {
+        (self.function)(arguments)
+    }
+
+    fn arity(&self) -> usize {
+        self.arity
+    }
+}
+
+fn native(
+    name: &str,
+    arity: usize,
+    function: fn(Vec<lox_type::LoxType>) -> NativeResult,
+) -> lox_type::LoxType {
+    lox_type::LoxType::NativeFunction {
+        name: name.to_string(),
+        callable: Rc::new(Box::new(Native { arity, function })),
+    }
+}
+
+/// What a native needs to reach the runtime, e.g. for its random number
+/// generator, or to call back into Lox.
+struct Context<'a> {
+    reporter: &'a dyn reporter::Reporter,
+    runtime: &'a runtime::Runtime,
+}
+
+impl Context<'_> {
+    /// Calls a function, native or class passed in as an argument.
+    fn call(&self, callee: &lox_type::LoxType, arguments: Vec<lox_type::LoxType>) -> NativeResult {
+        interpreter::call_back(self.reporter, self.runtime, callee, arguments)
+    }
+}
+
+type ContextFunction = fn(&Context, Vec<lox_type::LoxType>) -> NativeResult;
+
+/// A native backed by a function of its context and arguments.
 #[derive(Debug)]
-struct Clock;
-
-impl lox_type::NativeCallable for Clock {
-    fn call(&self, _: Vec<lox_type::LoxType>) -> Result<lox_type::LoxType, unwind::Unwind> {
-        Ok(lox_type::LoxType::Number(
-            time::SystemTime::now()
-                .duration_since(time::SystemTime::UNIX_EPOCH)
-                .unwrap()
-                .as_secs_f64(),
+struct ContextNative {
+    arity: usize,
+    function: ContextFunction,
+}
+
+impl lox_type::NativeCallable for ContextNative {
+    fn call(
+        &self,
+        reporter: &dyn reporter::Reporter,
+        runtime: &runtime::Runtime,
+        arguments: Vec<lox_type::LoxType>,
+    ) -> NativeResult {
+        (self.function)(&Context { reporter, runtime }, arguments)
+    }
+
+    fn arity(&self) -> usize {
+        self.arity
+    }
+}
+
+fn context_native(name: &str, arity: usize, function: ContextFunction) -> lox_type::LoxType {
+    lox_type::LoxType::NativeFunction {
+        name: name.to_string(),
+        callable: Rc::new(Box::new(ContextNative { arity, function })),
+    }
+}
+
+/// The Rust closure behind a native registered by an embedding application.
+pub type HostFunction = Rc<dyn Fn(Vec<value::Value>) -> Result<value::Value, String>>;
+
+/// A native registered by an embedding application. An error it returns is
+/// reported as a runtime error at the call.
+struct Host {
+    name: String,
+    arity: usize,
+    function: HostFunction,
+}
+
+impl Debug for Host {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<host fn {}>", self.name)
+    }
+}
+
+impl lox_type::NativeCallable for Host {
+    fn call(
+        &self,
+        _: &dyn reporter::Reporter,
+        _: &runtime::Runtime,
+        arguments: Vec<lox_type::LoxType>,
+    ) -> NativeResult {
+        let arguments = arguments.into_iter().map(value::Value).collect();
+        (self.function)(arguments)
+            .map(|result| result.0)
+            .map_err(unwind::Unwind::WithError)
+    }
+
+    fn arity(&self) -> usize {
+        self.arity
+    }
+}
+
+pub fn host(name: &str, arity: usize, function: HostFunction) -> value::Value {
+    value::Value(lox_type::LoxType::NativeFunction {
+        name: name.to_string(),
+        callable: Rc::new(Box::new(Host {
+            name: name.to_string(),
+            arity,
+            function,
+        })),
+    })
+}
+
+fn names(names: Vec<String>) -> lox_type::LoxType {
+    lox_type::LoxType::List {
+        list: list::List::new(names.into_iter().map(lox_type::LoxType::String).collect()),
+    }
+}
+
+pub fn type_of() -> lox_type::LoxType {
+    native("typeof", 1, |arguments| {
+        Ok(lox_type::LoxType::String(
+            arguments[0].type_name().to_string(),
         ))
+    })
+}
+
+pub fn class_of() -> lox_type::LoxType {
+    native("classOf", 1, |arguments| match &arguments[0] {
+        lox_type::LoxType::Instance { instance } => Ok(lox_type::LoxType::Class {
+            class: instance.class(),
+        }),
+        _ => Ok(lox_type::LoxType::Nil),
+    })
+}
+
+pub fn fields() -> lox_type::LoxType {
+    native("fields", 1, |arguments| match &arguments[0] {
+        lox_type::LoxType::Instance { instance } => Ok(names(instance.field_names())),
+        _ => Err(unwind::Unwind::WithError(
+            "Argument to 'fields' must be an instance".to_string(),
+        )),
+    })
+}
+
+pub fn methods() -> lox_type::LoxType {
+    native("methods", 1, |arguments| match &arguments[0] {
+        lox_type::LoxType::Class { class } => Ok(names(class.method_names())),
+        _ => Err(unwind::Unwind::WithError(
+            "Argument to 'methods' must be a class".to_string(),
+        )),
+    })
+}
+
+pub fn superclass_of() -> lox_type::LoxType {
+    native("superclassOf", 1, |arguments| match &arguments[0] {
+        lox_type::LoxType::Class { class } => {
+            Ok(class.superclass().map_or(lox_type::LoxType::Nil, |class| {
+                lox_type::LoxType::Class { class }
+            }))
+        }
+        _ => Err(unwind::Unwind::WithError(
+            "Argument to 'superclassOf' must be a class".to_string(),
+        )),
+    })
+}
+
+fn numbers<const N: usize>(
+    native: &str,
+    arguments: &[lox_type::LoxType],
+) -> Result<[f64; N], unwind::Unwind> {
+    let mut numbers = [0.0; N];
+    for (number, argument) in numbers.iter_mut().zip(arguments) {
+        match argument {
+            lox_type::LoxType::Number(value) => *number = *value,
+            _ if N == 1 => {
+                return Err(unwind::Unwind::WithError(format!(
+                    "Argument to '{native}' must be a number"
+                )))
+            }
+            _ => {
+                return Err(unwind::Unwind::WithError(format!(
+                    "Arguments to '{native}' must be numbers"
+                )))
+            }
+        }
+    }
+    Ok(numbers)
+}
+
+fn string_argument<'a>(
+    native: &str,
+    argument: &'a lox_type::LoxType,
+) -> Result<&'a str, unwind::Unwind> {
+    match argument {
+        lox_type::LoxType::String(string) => Ok(string),
+        _ => Err(unwind::Unwind::WithError(format!(
+            "Argument to '{native}' must be a string"
+        ))),
+    }
+}
+
+pub fn sqrt() -> lox_type::LoxType {
+    native("sqrt", 1, |arguments| {
+        let [x] = numbers("sqrt", &arguments)?;
+        Ok(lox_type::LoxType::Number(x.sqrt()))
+    })
+}
+
+pub fn pow() -> lox_type::LoxType {
+    native("pow", 2, |arguments| {
+        let [base, exponent] = numbers("pow", &arguments)?;
+        Ok(lox_type::LoxType::Number(base.powf(exponent)))
+    })
+}
+
+pub fn floor() -> lox_type::LoxType {
+    native("floor", 1, |arguments| {
+        let [x] = numbers("floor", &arguments)?;
+        Ok(lox_type::LoxType::Number(x.floor()))
+    })
+}
+
+pub fn abs() -> lox_type::LoxType {
+    native("abs", 1, |arguments| {
+        let [x] = numbers("abs", &arguments)?;
+        Ok(lox_type::LoxType::Number(x.abs()))
+    })
+}
+
+pub fn min() -> lox_type::LoxType {
+    native("min", 2, |arguments| {
+        let [a, b] = numbers("min", &arguments)?;
+        Ok(lox_type::LoxType::Number(a.min(b)))
+    })
+}
+
+pub fn max() -> lox_type::LoxType {
+    native("max", 2, |arguments| {
+        let [a, b] = numbers("max", &arguments)?;
+        Ok(lox_type::LoxType::Number(a.max(b)))
+    })
+}
+
+/// Parses a number, giving `nil` for text that is not one.
+pub fn num() -> lox_type::LoxType {
+    native("num", 1, |arguments| {
+        let text = string_argument("num", &arguments[0])?;
+        Ok(text
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|number| number.is_finite())
+            .map_or(lox_type::LoxType::Nil, lox_type::LoxType::Number))
+    })
+}
+
+/// The text `print` shows for a value, using `toString()` for instances
+/// that define it.
+pub fn str() -> lox_type::LoxType {
+    context_native("str", 1, |context, arguments| {
+        if let lox_type::LoxType::Instance { instance } = &arguments[0] {
+            let text = instance
+                .with_protocol_method("toString", |method| context.call(&method, Vec::new()))?;
+            match text {
+                Some(lox_type::LoxType::String(text)) => {
+                    return Ok(lox_type::LoxType::String(text))
+                }
+                Some(_) => {
+                    return Err(unwind::Unwind::WithError(
+                        "'toString' must return a string".to_string(),
+                    ))
+                }
+                None => (),
+            }
+        }
+        Ok(lox_type::LoxType::String(arguments[0].text()))
+    })
+}
+
+pub fn len() -> lox_type::LoxType {
+    native("len", 1, |arguments| {
+        let length = match &arguments[0] {
+            lox_type::LoxType::String(string) => string.chars().count(),
+            lox_type::LoxType::List { list } => list.len(),
+            lox_type::LoxType::Map { map } => map.len(),
+            _ => {
+                return Err(unwind::Unwind::WithError(
+                    "Argument to 'len' must be a string, list or map".to_string(),
+                ))
+            }
+        };
+        Ok(lox_type::LoxType::Number(length as f64))
+    })
+}
+
+pub fn chr() -> lox_type::LoxType {
+    native("chr", 1, |arguments| {
+        let [code] = numbers("chr", &arguments)?;
+        Some(code)
+            .filter(|code| *code >= 0.0 && code.fract() == 0.0 && *code <= u32::MAX as f64)
+            .and_then(|code| char::from_u32(code as u32))
+            .map(|character| lox_type::LoxType::String(character.to_string()))
+            .ok_or_else(|| {
+                unwind::Unwind::WithError("Argument to 'chr' must be a character code".to_string())
+            })
+    })
+}
+
+pub fn ord() -> lox_type::LoxType {
+    native("ord", 1, |arguments| {
+        let text = string_argument("ord", &arguments[0])?;
+        let mut characters = text.chars();
+        match (characters.next(), characters.next()) {
+            (Some(character), None) => Ok(lox_type::LoxType::Number(character as u32 as f64)),
+            _ => Err(unwind::Unwind::WithError(
+                "Argument to 'ord' must be a single character".to_string(),
+            )),
+        }
+    })
+}
+
+/// A number in [0, 1) from the runtime's generator.
+pub fn random() -> lox_type::LoxType {
+    context_native("random", 0, |context, _| {
+        Ok(lox_type::LoxType::Number(context.runtime.random()))
+    })
+}
+
+/// Reseeds the generator behind `random`, making its numbers repeatable.
+pub fn seed() -> lox_type::LoxType {
+    context_native("seed", 1, |context, arguments| {
+        let [seed] = numbers("seed", &arguments)?;
+        context.runtime.seed_random(seed.to_bits());
+        Ok(lox_type::LoxType::Nil)
+    })
+}
+
+pub fn assert() -> lox_type::LoxType {
+    native("assert", 2, |arguments| {
+        let message = string_argument("assert", &arguments[1])?;
+        if is_truthy(&arguments[0]) {
+            Ok(lox_type::LoxType::Nil)
+        } else {
+            Err(unwind::Unwind::WithError(format!(
+                "Assertion failed: {message}"
+            )))
+        }
+    })
+}
+
+pub fn read_file() -> lox_type::LoxType {
+    context_native("readFile", 1, |context, arguments| {
+        context
+            .runtime
+            .require(runtime::Capability::ReadFiles, "readFile")?;
+        let path = string_argument("readFile", &arguments[0])?;
+        fs::read_to_string(path)
+            .map(lox_type::LoxType::String)
+            .map_err(|error| io_error("read", path, error))
+    })
+}
+
+pub fn write_file() -> lox_type::LoxType {
+    context_native("writeFile", 2, |context, arguments| {
+        context
+            .runtime
+            .require(runtime::Capability::WriteFiles, "writeFile")?;
+        let path = string_argument("writeFile", &arguments[0])?;
+        let contents = string_argument("writeFile", &arguments[1])?;
+        fs::write(path, contents)
+            .map(|_| lox_type::LoxType::Nil)
+            .map_err(|error| io_error("write", path, error))
+    })
+}
+
+/// The names of the entries in a directory, sorted.
+pub fn list_dir() -> lox_type::LoxType {
+    context_native("listDir", 1, |context, arguments| {
+        context
+            .runtime
+            .require(runtime::Capability::ReadFiles, "listDir")?;
+        let path = string_argument("listDir", &arguments[0])?;
+        let mut entries = fs::read_dir(path)
+            .and_then(|entries| {
+                entries
+                    .map(|entry| entry.map(|entry| entry.file_name().to_string_lossy().to_string()))
+                    .collect::<Result<Vec<String>, io::Error>>()
+            })
+            .map_err(|error| io_error("list", path, error))?;
+        entries.sort();
+        Ok(names(entries))
+    })
+}
+
+/// The value of an environment variable, or `nil` when it is not set.
+pub fn get_env() -> lox_type::LoxType {
+    context_native("getEnv", 1, |context, arguments| {
+        context
+            .runtime
+            .require(runtime::Capability::Environment, "getEnv")?;
+        let name = string_argument("getEnv", &arguments[0])?;
+        Ok(env::var(name).map_or(lox_type::LoxType::Nil, lox_type::LoxType::String))
+    })
+}
+
+pub fn args() -> lox_type::LoxType {
+    context_native("args", 0, |context, _| {
+        context
+            .runtime
+            .require(runtime::Capability::Environment, "args")?;
+        Ok(names(context.runtime.args().to_vec()))
+    })
+}
+
+/// The next line of standard input without its line ending, or `nil` once
+/// the input is exhausted.
+pub fn read_line() -> lox_type::LoxType {
+    context_native("readLine", 0, |context, _| {
+        context
+            .runtime
+            .require(runtime::Capability::Stdin, "readLine")?;
+        let mut line = String::new();
+        match io::stdin().read_line(&mut line) {
+            Ok(0) => Ok(lox_type::LoxType::Nil),
+            Ok(_) => Ok(lox_type::LoxType::String(
+                line.trim_end_matches(['\n', '\r']).to_string(),
+            )),
+            Err(error) => Err(unwind::Unwind::WithError(format!(
+                "Could not read standard input: {error}"
+            ))),
+        }
+    })
+}
+
+fn io_error(action: &str, path: &str, error: io::Error) -> unwind::Unwind {
+    unwind::Unwind::WithError(format!("Could not {action} '{path}': {error}"))
+}
+
+fn list_argument(
+    native: &str,
+    argument: &lox_type::LoxType,
+) -> Result<Vec<lox_type::LoxType>, unwind::Unwind> {
+    match argument {
+        lox_type::LoxType::List { list } => Ok(list.elements()),
+        _ => Err(unwind::Unwind::WithError(format!(
+            "Argument to '{native}' must be a list"
+        ))),
+    }
+}
+
+fn new_list(elements: Vec<lox_type::LoxType>) -> lox_type::LoxType {
+    lox_type::LoxType::List {
+        list: list::List::new(elements),
+    }
+}
+
+/// A new list of what the callback returns for each element.
+pub fn map() -> lox_type::LoxType {
+    context_native("map", 2, |context, arguments| {
+        let elements = list_argument("map", &arguments[0])?;
+        let mapped = elements
+            .into_iter()
+            .map(|element| context.call(&arguments[1], vec![element]))
+            .collect::<Result<Vec<lox_type::LoxType>, unwind::Unwind>>()?;
+        Ok(new_list(mapped))
+    })
+}
+
+/// A new list of the elements the callback returns a truthy value for.
+pub fn filter() -> lox_type::LoxType {
+    context_native("filter", 2, |context, arguments| {
+        let mut kept = Vec::new();
+        for element in list_argument("filter", &arguments[0])? {
+            if is_truthy(&context.call(&arguments[1], vec![element.clone()])?) {
+                kept.push(element);
+            }
+        }
+        Ok(new_list(kept))
+    })
+}
+
+/// Folds the elements into one value, starting from `initial`: the callback
+/// gets the value so far and the next element.
+pub fn reduce() -> lox_type::LoxType {
+    context_native("reduce", 3, |context, arguments| {
+        let elements = list_argument("reduce", &arguments[0])?;
+        elements
+            .into_iter()
+            .try_fold(arguments[2].clone(), |accumulator, element| {
+                context.call(&arguments[1], vec![accumulator, element])
+            })
+    })
+}
+
+pub fn for_each() -> lox_type::LoxType {
+    context_native("forEach", 2, |context, arguments| {
+        for element in list_argument("forEach", &arguments[0])? {
+            context.call(&arguments[1], vec![element])?;
+        }
+        Ok(lox_type::LoxType::Nil)
+    })
+}
+
+/// A new list, stably sorted by a comparator that returns a negative number
+/// when its first argument goes first, a positive one when the second does,
+/// and zero otherwise.
+pub fn sort() -> lox_type::LoxType {
+    context_native("sort", 2, |context, arguments| {
+        let elements = list_argument("sort", &arguments[0])?;
+        let sorted = merge_sort(elements, &mut |a, b| match context
+            .call(&arguments[1], vec![a.clone(), b.clone()])?
+        {
+            lox_type::LoxType::Number(order) => Ok(order < 0.0),
+            _ => Err(unwind::Unwind::WithError(
+                "Comparator for 'sort' must return a number".to_string(),
+            )),
+        })?;
+        Ok(new_list(sorted))
+    })
+}
+
+pub fn json_parse() -> lox_type::LoxType {
+    native("jsonParse", 1, |arguments| {
+        let text = string_argument("jsonParse", &arguments[0])?;
+        json::parse(text).map_err(unwind::Unwind::WithError)
+    })
+}
+
+/// `jsonStringify(value, indent?)`: compact JSON, or one entry per line
+/// indented by `indent` spaces per level.
+#[derive(Debug)]
+struct JsonStringify;
+
+impl lox_type::NativeCallable for JsonStringify {
+    fn call(
+        &self,
+        _: &dyn reporter::Reporter,
+        _: &runtime::Runtime,
+        arguments: Vec<lox_type::LoxType>,
+    ) -> NativeResult {
+        let indent = match arguments[1] {
+            lox_type::LoxType::Nil => None,
+            lox_type::LoxType::Number(indent) if indent >= 0.0 && indent.fract() == 0.0 => {
+                Some(indent as usize)
+            }
+            _ => {
+                return Err(unwind::Unwind::WithError(
+                    "Indent for 'jsonStringify' must be a whole number".to_string(),
+                ))
+            }
+        };
+        json::stringify(&arguments[0], indent)
+            .map(lox_type::LoxType::String)
+            .map_err(unwind::Unwind::WithError)
     }
 
     fn arity(&self) -> usize {
-        0
+        2
+    }
+
+    fn optional(&self) -> usize {
+        1
     }
 }
 
-pub fn clock() -> lox_type::LoxType {
+pub fn json_stringify() -> lox_type::LoxType {
     lox_type::LoxType::NativeFunction {
-        name: "clock".to_string(),
-        callable: Rc::new(Box::new(Clock)),
+        name: "jsonStringify".to_string(),
+        callable: Rc::new(Box::new(JsonStringify)),
+    }
+}
+
+/// Sorts with a comparison that can fail, which `slice::sort_by` does not
+/// allow. `goes_before(a, b)` is true when `a` must come before `b`.
+fn merge_sort<T>(
+    mut elements: Vec<T>,
+    goes_before: &mut impl FnMut(&T, &T) -> Result<bool, unwind::Unwind>,
+) -> Result<Vec<T>, unwind::Unwind> {
+    if elements.len() <= 1 {
+        return Ok(elements);
     }
+    let right = elements.split_off(elements.len() / 2);
+    let mut left = merge_sort(elements, goes_before)?.into_iter().peekable();
+    let mut right = merge_sort(right, goes_before)?.into_iter().peekable();
+
+    let mut merged = Vec::new();
+    while let (Some(a), Some(b)) = (left.peek(), right.peek()) {
+        if goes_before(b, a)? {
+            merged.extend(right.next());
+        } else {
+            merged.extend(left.next());
+        }
+    }
+    merged.extend(left);
+    merged.extend(right);
+    Ok(merged)
+}
+
+/// What `inner()` calls when there is no subclass method left to defer to.
+pub fn no_inner(arity: usize) -> lox_type::LoxType {
+    native("inner", arity, |_| Ok(lox_type::LoxType::Nil))
 }
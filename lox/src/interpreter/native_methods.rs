@@ -0,0 +1,419 @@
+use crate::interpreter::{iterator, list, lox_type, runtime, unwind};
+use crate::reporter;
+use std::rc::Rc;
+
+type Method = fn(&lox_type::LoxType, &Context, Vec<lox_type::LoxType>) -> MethodResult;
+type MethodResult = Result<lox_type::LoxType, unwind::Unwind>;
+
+/// What a built-in method needs to run Lox code, e.g. when stepping a generator.
+struct Context<'a> {
+    reporter: &'a dyn reporter::Reporter,
+    runtime: &'a runtime::Runtime,
+}
+
+/// A built-in method bound to the value it was looked up on.
+#[derive(Debug)]
+struct BoundMethod {
+    receiver: lox_type::LoxType,
+    arity: usize,
+    method: Method,
+}
+
+impl lox_type::NativeCallable for BoundMethod {
+    fn call(
+        &self,
+        reporter: &dyn reporter::Reporter,
+        runtime: &runtime::Runtime,
+        arguments: Vec<lox_type::LoxType>,
+    ) -> MethodResult {
+        let context = Context { reporter, runtime };
+        (self.method)(&self.receiver, &context, arguments)
+    }
+
+    fn arity(&self) -> usize {
+        self.arity
+    }
+}
+
+pub fn find_method(receiver: &lox_type::LoxType, name: &str) -> Option<lox_type::LoxType> {
+    let (arity, method): (usize, Method) = match (receiver, name) {
+        (lox_type::LoxType::Enum { .. }, "iterator") => (0, enum_iterator),
+        (lox_type::LoxType::Iterator { .. }, "iterator") => (0, iterator_iterator),
+        (lox_type::LoxType::Iterator { .. }, "hasNext") => (0, iterator_has_next),
+        (lox_type::LoxType::Iterator { .. }, "next") => (0, iterator_next),
+        (lox_type::LoxType::List { .. }, "iterator") => (0, list_iterator),
+        (lox_type::LoxType::List { .. }, "length") => (0, list_length),
+        (lox_type::LoxType::List { .. }, "push") => (1, list_push),
+        (lox_type::LoxType::Map { .. }, "iterator") => (0, map_iterator),
+        (lox_type::LoxType::Map { .. }, "length") => (0, map_length),
+        (lox_type::LoxType::Number(_), "floor") => (0, number_floor),
+        (lox_type::LoxType::Number(_), "round") => (0, number_round),
+        (lox_type::LoxType::Number(_), "toFixed") => (1, number_to_fixed),
+        (lox_type::LoxType::Number(_), "toString") => (0, number_to_string),
+        (lox_type::LoxType::String(_), "chars") => (0, string_chars),
+        (lox_type::LoxType::String(_), "indexOf") => (1, string_index_of),
+        (lox_type::LoxType::String(_), "iterator") => (0, string_iterator),
+        (lox_type::LoxType::String(_), "length") => (0, string_length),
+        (lox_type::LoxType::String(_), "lower") => (0, string_lower),
+        (lox_type::LoxType::String(_), "replace") => (2, string_replace),
+        (lox_type::LoxType::String(_), "split") => (1, string_split),
+        (lox_type::LoxType::String(_), "startsWith") => (1, string_starts_with),
+        (lox_type::LoxType::String(_), "substring") => (2, string_substring),
+        (lox_type::LoxType::String(_), "trim") => (0, string_trim),
+        (lox_type::LoxType::String(_), "upper") => (0, string_upper),
+        _ => return None,
+    };
+    Some(lox_type::LoxType::NativeFunction {
+        name: name.to_string(),
+        callable: Rc::new(Box::new(BoundMethod {
+            receiver: receiver.clone(),
+            arity,
+            method,
+        })),
+    })
+}
+
+pub fn has_methods(receiver: &lox_type::LoxType) -> bool {
+    matches!(
+        receiver,
+        lox_type::LoxType::Enum { .. }
+            | lox_type::LoxType::Iterator { .. }
+            | lox_type::LoxType::List { .. }
+            | lox_type::LoxType::Map { .. }
+            | lox_type::LoxType::Number(_)
+            | lox_type::LoxType::String(_)
+    )
+}
+
+fn enum_iterator(
+    receiver: &lox_type::LoxType,
+    _: &Context,
+    _: Vec<lox_type::LoxType>,
+) -> MethodResult {
+    match receiver {
+        lox_type::LoxType::Enum { enumeration } => Ok(lox_type::LoxType::Iterator {
+            iterator: iterator::values(
+                enumeration
+                    .members()
+                    .into_iter()
+                    .map(|member| lox_type::LoxType::EnumMember { member })
+                    .collect(),
+            ),
+        }),
+        _ => unreachable!(),
+    }
+}
+
+fn iterator_iterator(
+    receiver: &lox_type::LoxType,
+    _: &Context,
+    _: Vec<lox_type::LoxType>,
+) -> MethodResult {
+    Ok(receiver.clone())
+}
+
+fn iterator_has_next(
+    receiver: &lox_type::LoxType,
+    context: &Context,
+    _: Vec<lox_type::LoxType>,
+) -> MethodResult {
+    match receiver {
+        lox_type::LoxType::Iterator { iterator } => Ok(lox_type::LoxType::Boolean(
+            iterator.has_next(context.reporter, context.runtime)?,
+        )),
+        _ => unreachable!(),
+    }
+}
+
+fn iterator_next(
+    receiver: &lox_type::LoxType,
+    context: &Context,
+    _: Vec<lox_type::LoxType>,
+) -> MethodResult {
+    match receiver {
+        lox_type::LoxType::Iterator { iterator } => {
+            iterator.next(context.reporter, context.runtime)
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn list_iterator(
+    receiver: &lox_type::LoxType,
+    _: &Context,
+    _: Vec<lox_type::LoxType>,
+) -> MethodResult {
+    match receiver {
+        lox_type::LoxType::List { list } => Ok(lox_type::LoxType::Iterator {
+            iterator: iterator::list(list),
+        }),
+        _ => unreachable!(),
+    }
+}
+
+fn list_length(
+    receiver: &lox_type::LoxType,
+    _: &Context,
+    _: Vec<lox_type::LoxType>,
+) -> MethodResult {
+    match receiver {
+        lox_type::LoxType::List { list } => Ok(lox_type::LoxType::Number(list.len() as f64)),
+        _ => unreachable!(),
+    }
+}
+
+fn list_push(
+    receiver: &lox_type::LoxType,
+    _: &Context,
+    mut arguments: Vec<lox_type::LoxType>,
+) -> MethodResult {
+    match receiver {
+        lox_type::LoxType::List { list } => {
+            list.push(arguments.remove(0));
+            Ok(lox_type::LoxType::Nil)
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn map_iterator(
+    receiver: &lox_type::LoxType,
+    _: &Context,
+    _: Vec<lox_type::LoxType>,
+) -> MethodResult {
+    match receiver {
+        lox_type::LoxType::Map { map } => Ok(lox_type::LoxType::Iterator {
+            iterator: iterator::values(map.keys()),
+        }),
+        _ => unreachable!(),
+    }
+}
+
+fn map_length(
+    receiver: &lox_type::LoxType,
+    _: &Context,
+    _: Vec<lox_type::LoxType>,
+) -> MethodResult {
+    match receiver {
+        lox_type::LoxType::Map { map } => Ok(lox_type::LoxType::Number(map.len() as f64)),
+        _ => unreachable!(),
+    }
+}
+
+fn number_floor(
+    receiver: &lox_type::LoxType,
+    _: &Context,
+    _: Vec<lox_type::LoxType>,
+) -> MethodResult {
+    Ok(lox_type::LoxType::Number(number(receiver).floor()))
+}
+
+fn number_round(
+    receiver: &lox_type::LoxType,
+    _: &Context,
+    _: Vec<lox_type::LoxType>,
+) -> MethodResult {
+    Ok(lox_type::LoxType::Number(number(receiver).round()))
+}
+
+/// The most digits `toFixed` allows, as in JavaScript.
+const MAX_FIXED_DIGITS: usize = 100;
+
+fn number_to_fixed(
+    receiver: &lox_type::LoxType,
+    _: &Context,
+    arguments: Vec<lox_type::LoxType>,
+) -> MethodResult {
+    let digits = index_argument("toFixed", &arguments[0])?;
+    if digits > MAX_FIXED_DIGITS {
+        return Err(unwind::Unwind::WithError(format!(
+            "Argument to 'toFixed' must be at most {MAX_FIXED_DIGITS}"
+        )));
+    }
+    let number = number(receiver);
+    if !number.is_finite() {
+        // Infinities and NaN have no digits to fix, so read as `print` shows them.
+        return Ok(lox_type::LoxType::String(receiver.text()));
+    }
+    Ok(lox_type::LoxType::String(format!("{:.*}", digits, number)))
+}
+
+fn number_to_string(
+    receiver: &lox_type::LoxType,
+    _: &Context,
+    _: Vec<lox_type::LoxType>,
+) -> MethodResult {
+    Ok(lox_type::LoxType::String(number(receiver).to_string()))
+}
+
+fn string_chars(
+    receiver: &lox_type::LoxType,
+    _: &Context,
+    _: Vec<lox_type::LoxType>,
+) -> MethodResult {
+    Ok(strings(string(receiver).chars().map(|c| c.to_string())))
+}
+
+fn string_index_of(
+    receiver: &lox_type::LoxType,
+    _: &Context,
+    arguments: Vec<lox_type::LoxType>,
+) -> MethodResult {
+    let string = string(receiver);
+    let search = string_argument("indexOf", &arguments[0])?;
+    let index = string
+        .find(search)
+        .map_or(-1.0, |offset| string[..offset].chars().count() as f64);
+    Ok(lox_type::LoxType::Number(index))
+}
+
+fn string_iterator(
+    receiver: &lox_type::LoxType,
+    _: &Context,
+    _: Vec<lox_type::LoxType>,
+) -> MethodResult {
+    Ok(lox_type::LoxType::Iterator {
+        iterator: iterator::values(
+            string(receiver)
+                .chars()
+                .map(|c| lox_type::LoxType::String(c.to_string()))
+                .collect(),
+        ),
+    })
+}
+
+fn string_length(
+    receiver: &lox_type::LoxType,
+    _: &Context,
+    _: Vec<lox_type::LoxType>,
+) -> MethodResult {
+    Ok(lox_type::LoxType::Number(
+        string(receiver).chars().count() as f64
+    ))
+}
+
+fn string_lower(
+    receiver: &lox_type::LoxType,
+    _: &Context,
+    _: Vec<lox_type::LoxType>,
+) -> MethodResult {
+    Ok(lox_type::LoxType::String(string(receiver).to_lowercase()))
+}
+
+fn string_replace(
+    receiver: &lox_type::LoxType,
+    _: &Context,
+    arguments: Vec<lox_type::LoxType>,
+) -> MethodResult {
+    let from = string_argument("replace", &arguments[0])?;
+    let to = string_argument("replace", &arguments[1])?;
+    Ok(lox_type::LoxType::String(
+        string(receiver).replace(from, to),
+    ))
+}
+
+fn string_split(
+    receiver: &lox_type::LoxType,
+    _: &Context,
+    arguments: Vec<lox_type::LoxType>,
+) -> MethodResult {
+    let string = string(receiver);
+    let separator = string_argument("split", &arguments[0])?;
+    if separator.is_empty() {
+        Ok(strings(string.chars().map(|c| c.to_string())))
+    } else {
+        Ok(strings(string.split(separator).map(|s| s.to_string())))
+    }
+}
+
+fn string_starts_with(
+    receiver: &lox_type::LoxType,
+    _: &Context,
+    arguments: Vec<lox_type::LoxType>,
+) -> MethodResult {
+    let prefix = string_argument("startsWith", &arguments[0])?;
+    Ok(lox_type::LoxType::Boolean(
+        string(receiver).starts_with(prefix),
+    ))
+}
+
+/// `substring(start, end)` counts characters rather than bytes, with `end`
+/// exclusive.
+fn string_substring(
+    receiver: &lox_type::LoxType,
+    _: &Context,
+    arguments: Vec<lox_type::LoxType>,
+) -> MethodResult {
+    let string = string(receiver);
+    let start = index_argument("substring", &arguments[0])?;
+    let end = index_argument("substring", &arguments[1])?;
+    if start > end || end > string.chars().count() {
+        return Err(unwind::Unwind::WithError(
+            "Substring range out of bounds".to_string(),
+        ));
+    }
+    Ok(lox_type::LoxType::String(
+        string.chars().skip(start).take(end - start).collect(),
+    ))
+}
+
+fn string_trim(
+    receiver: &lox_type::LoxType,
+    _: &Context,
+    _: Vec<lox_type::LoxType>,
+) -> MethodResult {
+    Ok(lox_type::LoxType::String(
+        string(receiver).trim().to_string(),
+    ))
+}
+
+fn string_upper(
+    receiver: &lox_type::LoxType,
+    _: &Context,
+    _: Vec<lox_type::LoxType>,
+) -> MethodResult {
+    Ok(lox_type::LoxType::String(string(receiver).to_uppercase()))
+}
+
+fn number(receiver: &lox_type::LoxType) -> f64 {
+    match receiver {
+        lox_type::LoxType::Number(number) => *number,
+        _ => unreachable!(),
+    }
+}
+
+fn string(receiver: &lox_type::LoxType) -> &str {
+    match receiver {
+        lox_type::LoxType::String(string) => string,
+        _ => unreachable!(),
+    }
+}
+
+fn strings(values: impl Iterator<Item = String>) -> lox_type::LoxType {
+    lox_type::LoxType::List {
+        list: list::List::new(values.map(lox_type::LoxType::String).collect()),
+    }
+}
+
+fn string_argument<'a>(
+    method: &str,
+    argument: &'a lox_type::LoxType,
+) -> Result<&'a str, unwind::Unwind> {
+    match argument {
+        lox_type::LoxType::String(string) => Ok(string),
+        _ => Err(unwind::Unwind::WithError(format!(
+            "Argument to '{method}' must be a string"
+        ))),
+    }
+}
+
+fn index_argument(method: &str, argument: &lox_type::LoxType) -> Result<usize, unwind::Unwind> {
+    match argument {
+        lox_type::LoxType::Number(number) if *number >= 0.0 && number.fract() == 0.0 => {
+            Ok(*number as usize)
+        }
+        _ => Err(unwind::Unwind::WithError(format!(
+            "Argument to '{method}' must be a non-negative integer"
+        ))),
+    }
+}
@@ -0,0 +1,232 @@
+use crate::interpreter::{gc, unwind};
+use crate::output;
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::fmt::{Debug, Formatter};
+use std::rc::Rc;
+use std::time;
+
+/// How many steps run between polls of `Options::is_cancelled`.
+const CANCELLATION_INTERVAL: usize = 1024;
+
+/// Access to the world outside the interpreter, which natives such as
+/// `readFile` only get when the embedder grants it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// `readFile` and `listDir`.
+    ReadFiles,
+    /// `writeFile`.
+    WriteFiles,
+    /// `getEnv` and `args`.
+    Environment,
+    /// `readLine`, from standard input.
+    Stdin,
+}
+
+/// Settings for a run of the interpreter.
+#[derive(Clone)]
+pub struct Options {
+    /// Calls nested deeper than this fail with "Stack overflow".
+    pub max_call_depth: usize,
+    /// Calls also fail with "Stack overflow" once the run has used this many
+    /// bytes of native stack. How much one call takes depends on the build and
+    /// on what the call does, so this, not `max_call_depth`, is what stops
+    /// deep recursion from overflowing the native stack. The default of 1MB
+    /// suits the 2MB stack Rust gives a new thread; keep it to about half of
+    /// the stack the interpreter runs on.
+    pub max_stack_size: usize,
+    /// Execution stops with "Step limit exceeded" after this many expressions
+    /// have been evaluated.
+    pub max_steps: Option<usize>,
+    /// Polled while running; execution stops with "Execution cancelled" once
+    /// it returns true.
+    pub is_cancelled: Option<Rc<dyn Fn() -> bool>>,
+    /// Where `print` writes. Without one, printed text is reported as a
+    /// "[print] " message.
+    pub output: Option<Rc<dyn output::Output>>,
+    /// What natives may reach outside the interpreter. None by default, so
+    /// that scripts stay sandboxed unless the embedder decides otherwise.
+    pub capabilities: HashSet<Capability>,
+    /// What `args()` returns, such as the command line after the script.
+    pub args: Vec<String>,
+    /// What `clock()` reads, in seconds. Without one it reads the system
+    /// time; a fixed or fake clock makes timing output repeatable.
+    pub clock: Option<Rc<dyn Fn() -> f64>>,
+    /// The seed `random()` starts from. Without one each run gets different
+    /// numbers.
+    pub random_seed: Option<u64>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            max_call_depth: 1000,
+            max_stack_size: 1024 * 1024,
+            max_steps: None,
+            is_cancelled: None,
+            output: None,
+            capabilities: HashSet::new(),
+            args: Vec::new(),
+            clock: None,
+            random_seed: None,
+        }
+    }
+}
+
+impl Debug for Options {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Options")
+            .field("max_call_depth", &self.max_call_depth)
+            .field("max_stack_size", &self.max_stack_size)
+            .field("max_steps", &self.max_steps)
+            .field("is_cancelled", &self.is_cancelled.is_some())
+            .field("output", &self.output.is_some())
+            .field("capabilities", &self.capabilities)
+            .field("args", &self.args)
+            .field("clock", &self.clock.is_some())
+            .field("random_seed", &self.random_seed)
+            .finish()
+    }
+}
+
+/// State shared by the interpreters for every call made during one run.
+pub struct Runtime {
+    options: Options,
+    call_depth: Cell<usize>,
+    stack_start: Cell<usize>,
+    steps: Cell<usize>,
+    random_state: Cell<u64>,
+}
+
+impl Runtime {
+    pub fn new(options: Options) -> Self {
+        let seed = options.random_seed.unwrap_or_else(|| {
+            time::SystemTime::now()
+                .duration_since(time::SystemTime::UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_nanos() as u64)
+        });
+        Self {
+            options,
+            call_depth: Cell::new(0),
+            stack_start: Cell::new(0),
+            steps: Cell::new(0),
+            random_state: Cell::new(mix(seed)),
+        }
+    }
+
+    /// Restarts the step budget for the next piece of input to run.
+    pub fn begin(&self) {
+        self.steps.set(0);
+    }
+
+    pub fn output(&self) -> Option<&dyn output::Output> {
+        self.options.output.as_deref()
+    }
+
+    /// Fails the call to `native` unless the embedder granted `capability`.
+    pub fn require(&self, capability: Capability, native: &str) -> Result<(), unwind::Unwind> {
+        if self.options.capabilities.contains(&capability) {
+            Ok(())
+        } else {
+            Err(unwind::Unwind::WithError(format!(
+                "'{native}' needs the {capability:?} capability"
+            )))
+        }
+    }
+
+    pub fn args(&self) -> &[String] {
+        &self.options.args
+    }
+
+    /// Counts one step against the budget, checking for cancellation every
+    /// `CANCELLATION_INTERVAL` steps.
+    pub fn step(&self) -> Result<(), unwind::Unwind> {
+        let steps = self.steps.get() + 1;
+        self.steps.set(steps);
+        if matches!(self.options.max_steps, Some(max_steps) if steps > max_steps) {
+            return Err(unwind::Unwind::WithError("Step limit exceeded".to_string()));
+        }
+        if steps.is_multiple_of(CANCELLATION_INTERVAL) {
+            gc::collect_if_due();
+            if let Some(is_cancelled) = &self.options.is_cancelled {
+                if is_cancelled() {
+                    return Err(unwind::Unwind::WithError("Execution cancelled".to_string()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Seconds since the Unix epoch, or whatever `Options::clock` says.
+    pub fn now(&self) -> f64 {
+        match &self.options.clock {
+            Some(clock) => clock(),
+            None => time::SystemTime::now()
+                .duration_since(time::SystemTime::UNIX_EPOCH)
+                .map_or(0.0, |duration| duration.as_secs_f64()),
+        }
+    }
+
+    /// Restarts the sequence `random` returns, so that the same seed always
+    /// gives the same numbers.
+    pub fn seed_random(&self, seed: u64) {
+        self.random_state.set(mix(seed));
+    }
+
+    /// The next number in [0, 1) from an xorshift64* generator.
+    pub fn random(&self) -> f64 {
+        let mut state = self.random_state.get();
+        state ^= state >> 12;
+        state ^= state << 25;
+        state ^= state >> 27;
+        self.random_state.set(state);
+        let bits = state.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11;
+        bits as f64 / (1u64 << 53) as f64
+    }
+
+    /// Goes one call deeper, until the returned guard is dropped. Stack use is
+    /// measured from where the outermost call began.
+    pub fn enter_call(&self) -> Result<CallGuard<'_>, unwind::Unwind> {
+        let depth = self.call_depth.get();
+        let position = stack_position();
+        if depth == 0 {
+            self.stack_start.set(position);
+        }
+        if depth >= self.options.max_call_depth
+            || self.stack_start.get().abs_diff(position) > self.options.max_stack_size
+        {
+            return Err(unwind::Unwind::WithError("Stack overflow".to_string()));
+        }
+        self.call_depth.set(depth + 1);
+        Ok(CallGuard {
+            call_depth: &self.call_depth,
+        })
+    }
+}
+
+/// Keeps a call counted towards `Options::max_call_depth` while it runs.
+pub struct CallGuard<'r> {
+    call_depth: &'r Cell<usize>,
+}
+
+impl Drop for CallGuard<'_> {
+    fn drop(&mut self) {
+        self.call_depth.set(self.call_depth.get() - 1);
+    }
+}
+
+/// Roughly where the native stack currently ends.
+#[inline(never)]
+fn stack_position() -> usize {
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
+}
+
+/// Spreads `seed` over all 64 bits (SplitMix64), as xorshift needs a non-zero
+/// state with its bits well mixed.
+fn mix(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (z ^ (z >> 31)).max(1)
+}
@@ -1,6 +1,32 @@
 use crate::interpreter::lox_type;
+use crate::location;
+
 #[derive(Debug, PartialEq)]
 pub enum Unwind {
     WithResult(lox_type::LoxType),
+    /// An error raised without a source location, e.g. by an environment lookup
+    /// or a native method. The interpreter locates it at the failing expression.
     WithError(String),
+    /// Boxed so that the results every evaluation returns stay small, which
+    /// keeps the native stack used by each Lox call down.
+    WithRuntimeError(Box<RuntimeError>),
+    Yield(lox_type::LoxType),
+}
+
+/// A runtime error located at the expression that failed. `stack` gains a
+/// frame for each call it unwinds through, innermost first.
+#[derive(Debug, PartialEq)]
+pub struct RuntimeError {
+    pub message: String,
+    pub start: location::FileLocation,
+    pub end: location::FileLocation,
+    pub stack: Vec<Frame>,
+}
+
+/// A call that was active when a runtime error was raised.
+#[derive(Debug, PartialEq)]
+pub struct Frame {
+    pub function: String,
+    pub start: location::FileLocation,
+    pub end: location::FileLocation,
 }
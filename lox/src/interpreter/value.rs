@@ -0,0 +1,124 @@
+use crate::interpreter::lox_type;
+use std::fmt::{Display, Formatter};
+
+/// A Lox value as seen by an embedding application. Numbers, booleans,
+/// strings and `nil` convert to and from their Rust counterparts; any other
+/// value, such as an instance or a list, can be held and passed back to Lox
+/// unchanged.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Value(pub(crate) lox_type::LoxType);
+
+impl Value {
+    pub fn nil() -> Self {
+        Value(lox_type::LoxType::Nil)
+    }
+
+    pub fn is_nil(&self) -> bool {
+        matches!(self.0, lox_type::LoxType::Nil)
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self.0 {
+            lox_type::LoxType::Boolean(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_number(&self) -> Option<f64> {
+        match self.0 {
+            lox_type::LoxType::Number(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match &self.0 {
+            lox_type::LoxType::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The name `typeof` gives this value, such as "number" or "instance".
+    pub fn type_name(&self) -> &'static str {
+        self.0.type_name()
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<()> for Value {
+    fn from(_: ()) -> Self {
+        Value::nil()
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value(lox_type::LoxType::Boolean(value))
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value(lox_type::LoxType::Number(value))
+    }
+}
+
+impl From<i32> for Value {
+    fn from(value: i32) -> Self {
+        Value(lox_type::LoxType::Number(value.into()))
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value(lox_type::LoxType::String(value.to_string()))
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value(lox_type::LoxType::String(value))
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or_else(Value::nil, Into::into)
+    }
+}
+
+fn expected(expected: &str, value: &Value) -> String {
+    format!("Expected a {expected} but got {}", value.type_name())
+}
+
+impl TryFrom<Value> for bool {
+    type Error = String;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        value.as_bool().ok_or_else(|| expected("boolean", &value))
+    }
+}
+
+impl TryFrom<Value> for f64 {
+    type Error = String;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        value.as_number().ok_or_else(|| expected("number", &value))
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = String;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value.0 {
+            lox_type::LoxType::String(string) => Ok(string),
+            _ => Err(expected("string", &value)),
+        }
+    }
+}
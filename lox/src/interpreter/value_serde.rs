@@ -0,0 +1,746 @@
+use crate::interpreter::{instance, list, lox_type, map, unwind, value};
+use serde::de::{self, IntoDeserializer};
+use serde::ser::{self, SerializeMap, SerializeSeq};
+use std::cell::RefCell;
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+
+/// Why a value could not be converted to or from a Rust type.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Error(String);
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: Display>(message: T) -> Self {
+        Error(message.to_string())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: Display>(message: T) -> Self {
+        Error(message.to_string())
+    }
+}
+
+impl From<unwind::Unwind> for Error {
+    fn from(unwind: unwind::Unwind) -> Self {
+        match unwind {
+            unwind::Unwind::WithError(message) => Error(message),
+            unwind::Unwind::WithRuntimeError(error) => Error(error.message),
+            _ => Error("Unexpected control flow while reading a value".to_string()),
+        }
+    }
+}
+
+/// Deserializes a Rust type from a Lox value, such as a configuration
+/// struct from an instance or map.
+pub fn from_value<T: de::DeserializeOwned>(value: value::Value) -> Result<T, Error> {
+    T::deserialize(value)
+}
+
+/// Serializes a Rust value into a Lox value: structs and maps become maps,
+/// sequences and tuples become lists, and unit enum variants become their
+/// name. Other variants become a map from the variant name to their data.
+pub fn to_value<T: ser::Serialize + ?Sized>(value: &T) -> Result<value::Value, Error> {
+    value.serialize(Serializer).map(value::Value)
+}
+
+/// Public fields of an instance, in sorted order.
+fn fields(instance: &instance::Instance) -> Result<Vec<(String, lox_type::LoxType)>, Error> {
+    instance
+        .field_names()
+        .into_iter()
+        .map(|name| {
+            let field = instance.get(&name)?;
+            Ok((name, field))
+        })
+        .collect()
+}
+
+fn is_container(value: &lox_type::LoxType) -> bool {
+    matches!(
+        value,
+        lox_type::LoxType::List { .. }
+            | lox_type::LoxType::Map { .. }
+            | lox_type::LoxType::Instance { .. }
+    )
+}
+
+impl ser::Serialize for value::Value {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Serializing {
+            value: &self.0,
+            open: &RefCell::default(),
+        }
+        .serialize(serializer)
+    }
+}
+
+/// A value being serialized, along with the lists, maps and instances that
+/// contain it, to detect cycles.
+struct Serializing<'a> {
+    value: &'a lox_type::LoxType,
+    open: &'a RefCell<Vec<lox_type::LoxType>>,
+}
+
+impl<'a> Serializing<'a> {
+    fn child(&self, value: &'a lox_type::LoxType) -> Self {
+        Serializing {
+            value,
+            open: self.open,
+        }
+    }
+
+    fn serialize_container<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.value {
+            lox_type::LoxType::List { list } => {
+                let elements = list.elements();
+                let mut seq = serializer.serialize_seq(Some(elements.len()))?;
+                for element in &elements {
+                    seq.serialize_element(&self.child(element))?;
+                }
+                seq.end()
+            }
+            lox_type::LoxType::Map { map } => {
+                let entries = map.entries();
+                let mut out = serializer.serialize_map(Some(entries.len()))?;
+                for (key, entry) in &entries {
+                    out.serialize_entry(&self.child(key), &self.child(entry))?;
+                }
+                out.end()
+            }
+            lox_type::LoxType::Instance { instance } => {
+                let fields = fields(instance).map_err(ser::Error::custom)?;
+                let mut out = serializer.serialize_map(Some(fields.len()))?;
+                for (name, field) in &fields {
+                    out.serialize_entry(name, &self.child(field))?;
+                }
+                out.end()
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl ser::Serialize for Serializing<'_> {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.value {
+            lox_type::LoxType::Nil => serializer.serialize_unit(),
+            lox_type::LoxType::Boolean(value) => serializer.serialize_bool(*value),
+            lox_type::LoxType::Number(value) => serializer.serialize_f64(*value),
+            lox_type::LoxType::String(value) => serializer.serialize_str(value),
+            lox_type::LoxType::EnumMember { member } => serializer.serialize_str(member.name()),
+            container if is_container(container) => {
+                if self.open.borrow().contains(container) {
+                    return Err(ser::Error::custom("Cannot serialize a cyclic structure"));
+                }
+                self.open.borrow_mut().push(container.clone());
+                let result = self.serialize_container(serializer);
+                self.open.borrow_mut().pop();
+                result
+            }
+            other => Err(ser::Error::custom(format!(
+                "Cannot serialize a value of type '{}'",
+                other.type_name()
+            ))),
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for value::Value {
+    type Error = Error;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        Deserializing::new(self.0).deserialize_any(visitor)
+    }
+
+    fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        Deserializing::new(self.0).deserialize_option(visitor)
+    }
+
+    fn deserialize_enum<V: de::Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        Deserializing::new(self.0).deserialize_enum(name, variants, visitor)
+    }
+
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        Deserializing::new(self.0).deserialize_newtype_struct(name, visitor)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for value::Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// A value being deserialized, along with the lists, maps and instances that
+/// contain it, to detect cycles.
+struct Deserializing {
+    value: lox_type::LoxType,
+    open: Rc<RefCell<Vec<lox_type::LoxType>>>,
+}
+
+impl Deserializing {
+    fn new(value: lox_type::LoxType) -> Self {
+        Self {
+            value,
+            open: Rc::default(),
+        }
+    }
+
+    fn child(&self, value: lox_type::LoxType) -> Self {
+        Self {
+            value,
+            open: self.open.clone(),
+        }
+    }
+
+    fn deserialize_container<'de, V: de::Visitor<'de>>(
+        &self,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match &self.value {
+            lox_type::LoxType::List { list } => {
+                let elements = list
+                    .elements()
+                    .into_iter()
+                    .map(|element| self.child(element));
+                visitor.visit_seq(de::value::SeqDeserializer::new(elements))
+            }
+            lox_type::LoxType::Map { map } => {
+                let entries = map
+                    .entries()
+                    .into_iter()
+                    .map(|(key, entry)| (self.child(key), self.child(entry)));
+                visitor.visit_map(de::value::MapDeserializer::new(entries))
+            }
+            lox_type::LoxType::Instance { instance } => {
+                let fields = fields(instance)?
+                    .into_iter()
+                    .map(|(name, field)| (name, self.child(field)));
+                visitor.visit_map(de::value::MapDeserializer::new(fields))
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializing {
+    type Error = Error;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.value {
+            lox_type::LoxType::Nil => visitor.visit_unit(),
+            lox_type::LoxType::Boolean(value) => visitor.visit_bool(value),
+            // Whole numbers are offered as integers so they fit integer fields.
+            lox_type::LoxType::Number(value)
+                if value.fract() == 0.0 && value.abs() < i64::MAX as f64 =>
+            {
+                visitor.visit_i64(value as i64)
+            }
+            lox_type::LoxType::Number(value) => visitor.visit_f64(value),
+            lox_type::LoxType::String(value) => visitor.visit_string(value),
+            lox_type::LoxType::EnumMember { member } => visitor.visit_str(member.name()),
+            ref container if is_container(container) => {
+                if self.open.borrow().contains(container) {
+                    return Err(Error("Cannot deserialize a cyclic structure".to_string()));
+                }
+                self.open.borrow_mut().push(container.clone());
+                let result = self.deserialize_container(visitor);
+                self.open.borrow_mut().pop();
+                result
+            }
+            other => Err(de::Error::custom(format!(
+                "Cannot deserialize a value of type '{}'",
+                other.type_name()
+            ))),
+        }
+    }
+
+    fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if matches!(self.value, lox_type::LoxType::Nil) {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_enum<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.value {
+            lox_type::LoxType::String(value) => visitor.visit_enum(value.into_deserializer()),
+            lox_type::LoxType::EnumMember { member } => {
+                visitor.visit_enum(member.name().to_string().into_deserializer())
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Deserializing {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// Builds Lox values from Rust ones, for `to_value`.
+struct Serializer;
+
+fn variant(name: &str, value: lox_type::LoxType) -> Result<lox_type::LoxType, Error> {
+    let map = map::Map::new();
+    map.set(lox_type::LoxType::String(name.to_string()), value)?;
+    Ok(lox_type::LoxType::Map { map })
+}
+
+impl ser::Serializer for Serializer {
+    type Ok = lox_type::LoxType;
+    type Error = Error;
+    type SerializeSeq = ListBuilder;
+    type SerializeTuple = ListBuilder;
+    type SerializeTupleStruct = ListBuilder;
+    type SerializeTupleVariant = ListBuilder;
+    type SerializeMap = MapBuilder;
+    type SerializeStruct = MapBuilder;
+    type SerializeStructVariant = MapBuilder;
+
+    fn serialize_bool(self, value: bool) -> Result<lox_type::LoxType, Error> {
+        Ok(lox_type::LoxType::Boolean(value))
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<lox_type::LoxType, Error> {
+        self.serialize_f64(value.into())
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<lox_type::LoxType, Error> {
+        self.serialize_f64(value.into())
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<lox_type::LoxType, Error> {
+        self.serialize_f64(value.into())
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<lox_type::LoxType, Error> {
+        self.serialize_f64(value as f64)
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<lox_type::LoxType, Error> {
+        self.serialize_f64(value.into())
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<lox_type::LoxType, Error> {
+        self.serialize_f64(value.into())
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<lox_type::LoxType, Error> {
+        self.serialize_f64(value.into())
+    }
+
+    fn serialize_u64(self, value: u64) -> Result<lox_type::LoxType, Error> {
+        self.serialize_f64(value as f64)
+    }
+
+    fn serialize_f32(self, value: f32) -> Result<lox_type::LoxType, Error> {
+        self.serialize_f64(value.into())
+    }
+
+    fn serialize_f64(self, value: f64) -> Result<lox_type::LoxType, Error> {
+        Ok(lox_type::LoxType::Number(value))
+    }
+
+    fn serialize_char(self, value: char) -> Result<lox_type::LoxType, Error> {
+        Ok(lox_type::LoxType::String(value.to_string()))
+    }
+
+    fn serialize_str(self, value: &str) -> Result<lox_type::LoxType, Error> {
+        Ok(lox_type::LoxType::String(value.to_string()))
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<lox_type::LoxType, Error> {
+        let elements = value
+            .iter()
+            .map(|byte| lox_type::LoxType::Number((*byte).into()))
+            .collect();
+        Ok(lox_type::LoxType::List {
+            list: list::List::new(elements),
+        })
+    }
+
+    fn serialize_none(self) -> Result<lox_type::LoxType, Error> {
+        Ok(lox_type::LoxType::Nil)
+    }
+
+    fn serialize_some<T: ser::Serialize + ?Sized>(
+        self,
+        value: &T,
+    ) -> Result<lox_type::LoxType, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<lox_type::LoxType, Error> {
+        Ok(lox_type::LoxType::Nil)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<lox_type::LoxType, Error> {
+        Ok(lox_type::LoxType::Nil)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<lox_type::LoxType, Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: ser::Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<lox_type::LoxType, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ser::Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        name: &'static str,
+        value: &T,
+    ) -> Result<lox_type::LoxType, Error> {
+        variant(name, value.serialize(self)?)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<ListBuilder, Error> {
+        Ok(ListBuilder {
+            variant: None,
+            elements: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<ListBuilder, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<ListBuilder, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<ListBuilder, Error> {
+        Ok(ListBuilder {
+            variant: Some(variant),
+            elements: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapBuilder, Error> {
+        Ok(MapBuilder {
+            variant: None,
+            map: map::Map::new(),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<MapBuilder, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<MapBuilder, Error> {
+        Ok(MapBuilder {
+            variant: Some(variant),
+            map: map::Map::new(),
+            key: None,
+        })
+    }
+}
+
+/// The elements of a sequence or tuple being serialized, and the name of
+/// the enum variant holding them, if any.
+struct ListBuilder {
+    variant: Option<&'static str>,
+    elements: Vec<lox_type::LoxType>,
+}
+
+impl ListBuilder {
+    fn push<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.elements.push(value.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<lox_type::LoxType, Error> {
+        let list = lox_type::LoxType::List {
+            list: list::List::new(self.elements),
+        };
+        match self.variant {
+            Some(name) => variant(name, list),
+            None => Ok(list),
+        }
+    }
+}
+
+impl ser::SerializeSeq for ListBuilder {
+    type Ok = lox_type::LoxType;
+    type Error = Error;
+
+    fn serialize_element<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<lox_type::LoxType, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for ListBuilder {
+    type Ok = lox_type::LoxType;
+    type Error = Error;
+
+    fn serialize_element<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<lox_type::LoxType, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for ListBuilder {
+    type Ok = lox_type::LoxType;
+    type Error = Error;
+
+    fn serialize_field<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<lox_type::LoxType, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for ListBuilder {
+    type Ok = lox_type::LoxType;
+    type Error = Error;
+
+    fn serialize_field<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<lox_type::LoxType, Error> {
+        self.finish()
+    }
+}
+
+/// The entries of a map or struct being serialized, the key waiting for its
+/// value, and the name of the enum variant holding them, if any.
+struct MapBuilder {
+    variant: Option<&'static str>,
+    map: map::Map,
+    key: Option<lox_type::LoxType>,
+}
+
+impl MapBuilder {
+    fn insert<T: ser::Serialize + ?Sized>(
+        &mut self,
+        key: lox_type::LoxType,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.map.set(key, value.serialize(Serializer)?)?;
+        Ok(())
+    }
+
+    fn finish(self) -> Result<lox_type::LoxType, Error> {
+        let map = lox_type::LoxType::Map { map: self.map };
+        match self.variant {
+            Some(name) => variant(name, map),
+            None => Ok(map),
+        }
+    }
+}
+
+impl ser::SerializeMap for MapBuilder {
+    type Ok = lox_type::LoxType;
+    type Error = Error;
+
+    fn serialize_key<T: ser::Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.key = Some(key.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| Error("Map value serialized before its key".to_string()))?;
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<lox_type::LoxType, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for MapBuilder {
+    type Ok = lox_type::LoxType;
+    type Error = Error;
+
+    fn serialize_field<T: ser::Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.insert(lox_type::LoxType::String(key.to_string()), value)
+    }
+
+    fn end(self) -> Result<lox_type::LoxType, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for MapBuilder {
+    type Ok = lox_type::LoxType;
+    type Error = Error;
+
+    fn serialize_field<T: ser::Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.insert(lox_type::LoxType::String(key.to_string()), value)
+    }
+
+    fn end(self) -> Result<lox_type::LoxType, Error> {
+        self.finish()
+    }
+}
+
+impl<'de> de::Deserialize<'de> for value::Value {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+/// Builds a Lox value from any self-describing format: sequences become
+/// lists and maps become maps.
+struct ValueVisitor;
+
+impl<'de> de::Visitor<'de> for ValueVisitor {
+    type Value = value::Value;
+
+    fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "a value Lox can represent")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(value::Value::nil())
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(value::Value::nil())
+    }
+
+    fn visit_some<D: de::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        de::Deserialize::deserialize(deserializer)
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<Self::Value, E> {
+        Ok(value.into())
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Self::Value, E> {
+        Ok((value as f64).into())
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Self::Value, E> {
+        Ok((value as f64).into())
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Self::Value, E> {
+        Ok(value.into())
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        Ok(value.into())
+    }
+
+    fn visit_string<E: de::Error>(self, value: String) -> Result<Self::Value, E> {
+        Ok(value.into())
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut elements = Vec::new();
+        while let Some(element) = seq.next_element::<value::Value>()? {
+            elements.push(element.0);
+        }
+        Ok(value::Value(lox_type::LoxType::List {
+            list: list::List::new(elements),
+        }))
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut entries: A) -> Result<Self::Value, A::Error> {
+        let map = map::Map::new();
+        while let Some((key, entry)) = entries.next_entry::<value::Value, value::Value>()? {
+            map.set(key.0, entry.0)
+                .map_err(|unwind| de::Error::custom(Error::from(unwind)))?;
+        }
+        Ok(value::Value(lox_type::LoxType::Map { map }))
+    }
+}
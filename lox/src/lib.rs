@@ -1,7 +1,9 @@
 mod ast_printer;
+mod embedding;
 mod expr;
 mod interpreter;
 mod location;
+mod output;
 mod parser;
 mod reporter;
 mod resolver;
@@ -9,9 +11,21 @@ mod scanner;
 mod stmt;
 mod token;
 
+pub use crate::embedding::CallError;
+pub use crate::embedding::Interpreter;
+pub use crate::embedding::InterpreterBuilder;
+pub use crate::embedding::NativeClass;
+pub use crate::embedding::Session;
 pub use crate::expr::Expr;
+pub use crate::interpreter::Capability;
+pub use crate::interpreter::Frame;
+pub use crate::interpreter::Options;
+pub use crate::interpreter::Value;
+#[cfg(feature = "serde")]
+pub use crate::interpreter::{from_value, to_value, ValueError};
 pub use crate::location::FileLocation;
 pub use crate::location::ProvideLocation;
+pub use crate::output::Output;
 pub use crate::reporter::Reporter;
 pub use crate::stmt::function::Function;
 pub use crate::stmt::Stmt;
@@ -57,29 +71,18 @@ pub fn resolve(reporter: &dyn reporter::Reporter, source: &str) {
         return;
     }
 
-    let _ = resolver::resolve(reporter, &statements);
+    resolver::resolve(reporter, &statements);
 }
 
 pub fn interpret(reporter: &dyn reporter::Reporter, source: &str) {
-    let tokens = scanner::scan_tokens(reporter, source);
-    if reporter.has_diagnostics() {
-        reporter.add_message("[parser] not parsing due to scan errors");
-        return;
-    }
-
-    let statements = parser::parse(reporter, tokens);
-    if reporter.has_diagnostics() {
-        reporter.add_message("[interpreter] not interpreting due to parsing errors");
-        return;
-    }
-
-    let depths = resolver::resolve(reporter, &statements);
-    if reporter.has_diagnostics() {
-        reporter.add_message("[interpreter] not interpreting due to resolver errors");
-        return;
-    }
+    interpret_with_options(reporter, source, Options::default());
+}
 
-    interpreter::interpret(reporter, &depths, statements);
+pub fn interpret_with_options(reporter: &dyn reporter::Reporter, source: &str, options: Options) {
+    Interpreter::builder()
+        .options(options)
+        .build()
+        .interpret(reporter, source);
 }
 
 pub fn ast(reporter: &dyn reporter::Reporter, source: &str) -> LinkedList<stmt::Stmt> {
@@ -1,22 +1,43 @@
 use lox::Reporter;
 use std::cell::RefCell;
+use std::collections::HashSet;
 use std::env;
 use std::fs;
 use std::io;
 use std::io::Write;
 use std::process;
+use std::rc::Rc;
+use std::thread;
+
+/// Scripts run on a thread with this much stack, whatever limit the shell
+/// sets for the main thread, so that deep recursion fails with "Stack
+/// overflow" rather than aborting.
+const STACK_SIZE: usize = 64 * 1024 * 1024;
 
 fn main() {
     println!("Hello, Lox!");
 
-    let args: Vec<String> = env::args().collect();
-    match args.len() {
-        1 => run_prompt(),
-        2 => run_file(&args[1]),
-        _ => {
-            eprintln!("Usage: {} [script]", args[0]);
-            process::exit(64);
+    let mut args: Vec<String> = env::args().collect();
+    let mut max_call_depth = lox::Options::default().max_call_depth;
+    if args.get(1).map(String::as_str) == Some("--max-call-depth") {
+        match args.get(2).and_then(|depth| depth.parse().ok()) {
+            Some(depth) => max_call_depth = depth,
+            None => {
+                eprintln!("Usage: lox [--max-call-depth N] [script [args...]]");
+                process::exit(64);
+            }
         }
+        args.drain(1..3);
+    }
+    let run = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || match args.get(1) {
+            None => run_prompt(max_call_depth),
+            Some(script) => run_file(script, args[2..].to_vec(), max_call_depth),
+        })
+        .expect("failed to start the interpreter thread");
+    if run.join().is_err() {
+        process::exit(70);
     }
 }
 
@@ -52,8 +73,9 @@ impl lox::Reporter for MainReporter {
     }
 }
 
-fn run_prompt() {
+fn run_prompt(max_call_depth: usize) {
     let reporter = MainReporter::new();
+    let mut session = interpreter(Vec::new(), max_call_depth).session();
     loop {
         print!("> ");
         if io::stdout().flush().is_err() {
@@ -68,22 +90,41 @@ fn run_prompt() {
                     break;
                 }
                 reporter.reset();
-                lox::run(&reporter, trimmed_line);
+                session.evaluate(&reporter, trimmed_line);
             }
         }
     }
     println!("done");
 }
 
-fn run_file(filepath: &str) {
+fn run_file(filepath: &str, script_args: Vec<String>, max_call_depth: usize) {
     let contents = fs::read_to_string(filepath);
     if let Err(e) = contents {
         eprintln!("{e}");
         return;
     }
     let reporter = MainReporter::new();
-    lox::run(&reporter, &contents.unwrap());
+    interpreter(script_args, max_call_depth).interpret(&reporter, &contents.unwrap());
     if reporter.has_diagnostics() {
         process::exit(70);
     }
 }
+
+/// The command line trusts its scripts, so they get every capability.
+fn interpreter(script_args: Vec<String>, max_call_depth: usize) -> lox::Interpreter {
+    lox::Interpreter::builder()
+        .options(lox::Options {
+            output: Some(Rc::new(RefCell::new(io::stdout()))),
+            capabilities: HashSet::from([
+                lox::Capability::ReadFiles,
+                lox::Capability::WriteFiles,
+                lox::Capability::Environment,
+                lox::Capability::Stdin,
+            ]),
+            args: script_args,
+            max_call_depth,
+            max_stack_size: STACK_SIZE / 2,
+            ..lox::Options::default()
+        })
+        .build()
+}
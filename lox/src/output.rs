@@ -0,0 +1,15 @@
+use std::cell::RefCell;
+use std::io;
+
+/// Receives the text of each `print` statement, without a trailing newline.
+pub trait Output {
+    fn print(&self, text: &str);
+}
+
+/// Writes each printed line to `W`, such as `io::Stdout` or a `Vec<u8>`
+/// buffer.
+impl<W: io::Write> Output for RefCell<W> {
+    fn print(&self, text: &str) {
+        let _ = writeln!(self.borrow_mut(), "{text}");
+    }
+}
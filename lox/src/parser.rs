@@ -45,6 +45,7 @@ impl Data {
             token::TokenType::GreaterEqual,
             token::TokenType::Less,
             token::TokenType::LessEqual,
+            token::TokenType::Is,
         ];
         let factor_tokens = vec![token::TokenType::Slash, token::TokenType::Star];
         let term_tokens = vec![token::TokenType::Minus, token::TokenType::Plus];
@@ -73,6 +74,14 @@ struct Parser<'k> {
     reporter: &'k dyn reporter::Reporter,
     tokens: Peekable<IntoIter<token::Token>>,
     allow_invalid_call: bool,
+    function_bodies: Vec<FunctionBody>,
+}
+
+/// What has been seen so far in the body of a function being parsed.
+#[derive(Default)]
+struct FunctionBody {
+    yields: bool,
+    calls_inner: bool,
 }
 ///
 /// Parser stores the current token.
@@ -94,6 +103,7 @@ impl<'k> Parser<'k> {
             reporter,
             tokens: tokens.into_iter().peekable(),
             allow_invalid_call,
+            function_bodies: Vec::new(),
         }
     }
 
@@ -123,6 +133,8 @@ impl<'k> Parser<'k> {
     fn declaration(&mut self, data: &Data) -> Result<stmt::Stmt, ParseError> {
         let result = if self.consume_matching_token(&token::TokenType::Class) {
             self.class_declaration(data)
+        } else if self.consume_matching_token(&token::TokenType::Enum) {
+            self.enum_declaration()
         } else if self.consume_matching_token(&token::TokenType::Fun) {
             self.function_declaration(data, "function")
         } else if self.consume_matching_token(&token::TokenType::Var) {
@@ -166,11 +178,34 @@ impl<'k> Parser<'k> {
         })
     }
 
-    fn function_declaration(&mut self, data: &Data, kind: &str) -> Result<stmt::Stmt, ParseError> {
+    fn enum_declaration(&mut self) -> Result<stmt::Stmt, ParseError> {
+        self.consume_token(&token::TokenType::Identifier, "Expect enum name")?;
+        let name = self.take_current_token()?;
+
+        self.consume_token(&token::TokenType::LeftBrace, "Expect '{' before enum body")?;
+        let mut members = LinkedList::new();
+        while !self.check_next_token(&token::TokenType::RightBrace) {
+            self.consume_token(&token::TokenType::Identifier, "Expect enum member name")?;
+            members.push_back(self.take_current_token()?);
+            if !self.consume_matching_token(&token::TokenType::Comma) {
+                break;
+            }
+        }
         self.consume_token(
-            &token::TokenType::Identifier,
-            &format!("Expect {} name", kind),
+            &token::TokenType::RightBrace,
+            "Expect '}' after enum members",
         )?;
+        Ok(stmt::Stmt::Enum { name, members })
+    }
+
+    fn function_declaration(&mut self, data: &Data, kind: &str) -> Result<stmt::Stmt, ParseError> {
+        // Only methods can be private
+        if kind != "method" || !self.consume_matching_token(&token::TokenType::PrivateIdentifier) {
+            self.consume_token(
+                &token::TokenType::Identifier,
+                &format!("Expect {} name", kind),
+            )?;
+        }
         let name = self.take_current_token()?;
 
         self.consume_token(
@@ -205,8 +240,17 @@ impl<'k> Parser<'k> {
             &token::TokenType::LeftBrace,
             &format!("Expect '{{' before {} body", kind),
         )?;
-        if let stmt::Stmt::Block { statements } = self.block_statement(data)? {
-            Ok(stmt::Stmt::new_function(name, params, statements))
+        self.function_bodies.push(FunctionBody::default());
+        let body = self.block_statement(data);
+        let function_body = self.function_bodies.pop().unwrap_or_default();
+        if let stmt::Stmt::Block { statements } = body? {
+            Ok(stmt::Stmt::new_function(
+                name,
+                params,
+                statements,
+                function_body.yields,
+                function_body.calls_inner,
+            ))
         } else {
             Err(ParseError {
                 message: format!("Expect a block {} body", kind),
@@ -217,7 +261,14 @@ impl<'k> Parser<'k> {
     fn variable_declaration(&mut self, data: &Data) -> Result<stmt::Stmt, ParseError> {
         self.consume_token(&token::TokenType::Identifier, "Expect a variable name")?;
         let name = self.take_current_token()?;
+        self.finish_variable_declaration(data, name)
+    }
 
+    fn finish_variable_declaration(
+        &mut self,
+        data: &Data,
+        name: token::Token,
+    ) -> Result<stmt::Stmt, ParseError> {
         let initialiser = if self.consume_matching_token(&token::TokenType::Equal) {
             Some(self.expression(data)?)
         } else {
@@ -238,6 +289,8 @@ impl<'k> Parser<'k> {
             self.return_statement(data)
         } else if self.consume_matching_token(&token::TokenType::While) {
             self.while_statement(data)
+        } else if self.consume_matching_token(&token::TokenType::Yield) {
+            self.yield_statement(data)
         } else if self.consume_matching_token(&token::TokenType::LeftBrace) {
             self.block_statement(data)
         } else {
@@ -283,6 +336,20 @@ impl<'k> Parser<'k> {
         Ok(stmt::Stmt::Return { keyword, value })
     }
 
+    fn yield_statement(&mut self, data: &Data) -> Result<stmt::Stmt, ParseError> {
+        let keyword = self.take_current_token()?;
+        let value = if !self.check_next_token(&token::TokenType::Semicolon) {
+            Some(self.expression(data)?)
+        } else {
+            None
+        };
+        self.consume_semicolon("Expect ';' after yield value")?;
+        if let Some(function_body) = self.function_bodies.last_mut() {
+            function_body.yields = true;
+        }
+        Ok(stmt::Stmt::Yield { keyword, value })
+    }
+
     fn while_statement(&mut self, data: &Data) -> Result<stmt::Stmt, ParseError> {
         self.consume_token(&token::TokenType::LeftParen, "Expect '(' after 'while'")?;
         let condition = self.expression(data)?;
@@ -303,9 +370,21 @@ impl<'k> Parser<'k> {
         let initialiser = if self.consume_matching_token(&token::TokenType::Semicolon) {
             None
         } else if self.consume_matching_token(&token::TokenType::Var) {
-            Some(self.variable_declaration(data)?)
+            self.consume_token(&token::TokenType::Identifier, "Expect a variable name")?;
+            let name = self.take_current_token()?;
+            if self.consume_matching_token(&token::TokenType::In) {
+                return self.for_in_statement(data, name);
+            }
+            Some(self.finish_variable_declaration(data, name)?)
         } else {
-            Some(self.expression_statement(data)?)
+            let expression = self.expression(data)?;
+            if self.consume_matching_token(&token::TokenType::In) {
+                if let expr::Expr::Variable { name, .. } = expression {
+                    return self.for_in_statement(data, name);
+                }
+                self.add_diagnostic("Expect variable name before 'in'")?;
+            }
+            Some(self.finish_expression_statement(expression)?)
         };
 
         let condition = if !self.check_next_token(&token::TokenType::Semicolon) {
@@ -369,6 +448,24 @@ impl<'k> Parser<'k> {
         Ok(body)
     }
 
+    fn for_in_statement(
+        &mut self,
+        data: &Data,
+        name: token::Token,
+    ) -> Result<stmt::Stmt, ParseError> {
+        let iterable = self.expression(data)?;
+        self.consume_token(
+            &token::TokenType::RightParen,
+            "Expect ')' after 'for' iterable",
+        )?;
+        let body = self.statement(data)?;
+        Ok(stmt::Stmt::ForIn {
+            name,
+            iterable,
+            body: Box::new(body),
+        })
+    }
+
     fn block_statement(&mut self, data: &Data) -> Result<stmt::Stmt, ParseError> {
         let mut statements = LinkedList::new();
 
@@ -383,6 +480,13 @@ impl<'k> Parser<'k> {
 
     fn expression_statement(&mut self, data: &Data) -> Result<stmt::Stmt, ParseError> {
         let expression = self.expression(data)?;
+        self.finish_expression_statement(expression)
+    }
+
+    fn finish_expression_statement(
+        &mut self,
+        expression: expr::Expr,
+    ) -> Result<stmt::Stmt, ParseError> {
         let error = self.consume_semicolon("Expect ';' after expression");
         if error.is_ok() {
             Ok(stmt::Stmt::Expression { expression })
@@ -407,6 +511,14 @@ impl<'k> Parser<'k> {
                 return Ok(expr::Expr::new_assign(name, value));
             } else if let expr::Expr::Get { object, name, .. } = expr {
                 return Ok(expr::Expr::new_set(*object, name, value));
+            } else if let expr::Expr::Index {
+                object,
+                bracket,
+                index,
+                ..
+            } = expr
+            {
+                return Ok(expr::Expr::new_set_index(*object, bracket, *index, value));
             }
             let _ = self.add_diagnostic("Invalid assignment target");
         }
@@ -500,12 +612,21 @@ impl<'k> Parser<'k> {
         loop {
             if self.consume_matching_token(&token::TokenType::LeftParen) {
                 expr = self.finish_call(data, expr)?;
+            } else if self.consume_matching_token(&token::TokenType::LeftBracket) {
+                let index = self.expression(data)?;
+                self.consume_token(&token::TokenType::RightBracket, "Expect ']' after index")?;
+                let bracket = self.take_current_token()?;
+                expr = expr::Expr::new_index(expr, bracket, index);
             } else if self.consume_matching_token(&token::TokenType::Dot) {
                 let dot = self.take_current_token();
-                let error = self.consume_token(
-                    &token::TokenType::Identifier,
-                    "Expect property name after '.'",
-                );
+                let error = if self.consume_matching_token(&token::TokenType::PrivateIdentifier) {
+                    Ok(())
+                } else {
+                    self.consume_token(
+                        &token::TokenType::Identifier,
+                        "Expect property name after '.'",
+                    )
+                };
                 if error.is_ok() {
                     let name = self.take_current_token()?;
                     expr = expr::Expr::new_get(expr, name);
@@ -573,6 +694,14 @@ impl<'k> Parser<'k> {
             }
         }
 
+        if self.consume_matching_token(&token::TokenType::Inner) {
+            // Closures inside a method call the method's inner, so mark them all
+            self.function_bodies
+                .iter_mut()
+                .for_each(|function_body| function_body.calls_inner = true);
+            return Ok(expr::Expr::new_inner(self.take_current_token()?));
+        }
+
         if self.consume_matching_token(&token::TokenType::This) {
             return Ok(expr::Expr::new_this(self.take_current_token()?));
         }
@@ -587,9 +716,58 @@ impl<'k> Parser<'k> {
             return Ok(expr::Expr::new_grouping(expr));
         }
 
+        if self.consume_matching_token(&token::TokenType::LeftBracket) {
+            return self.list_expression(data);
+        }
+
+        if self.consume_matching_token(&token::TokenType::LeftBrace) {
+            return self.map_expression(data);
+        }
+
         self.add_diagnostic("Expect expression")
     }
 
+    fn list_expression(&mut self, data: &Data) -> Result<expr::Expr, ParseError> {
+        let left_bracket = self.take_current_token()?;
+        let mut elements = Vec::new();
+        if !self.check_next_token(&token::TokenType::RightBracket) {
+            loop {
+                elements.push(self.expression(data)?);
+                if !self.consume_matching_token(&token::TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+        self.consume_token(
+            &token::TokenType::RightBracket,
+            "Expect ']' after list elements",
+        )?;
+        let right_bracket = self.take_current_token()?;
+        Ok(expr::Expr::new_list(left_bracket, elements, right_bracket))
+    }
+
+    fn map_expression(&mut self, data: &Data) -> Result<expr::Expr, ParseError> {
+        let left_brace = self.take_current_token()?;
+        let mut entries = Vec::new();
+        if !self.check_next_token(&token::TokenType::RightBrace) {
+            loop {
+                let key = self.expression(data)?;
+                self.consume_token(&token::TokenType::Colon, "Expect ':' after map key")?;
+                let value = self.expression(data)?;
+                entries.push((key, value));
+                if !self.consume_matching_token(&token::TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+        self.consume_token(
+            &token::TokenType::RightBrace,
+            "Expect '}' after map entries",
+        )?;
+        let right_brace = self.take_current_token()?;
+        Ok(expr::Expr::new_map(left_brace, entries, right_brace))
+    }
+
     fn consume_token(
         &mut self,
         token_to_consume: &token::TokenType,
@@ -647,8 +825,8 @@ impl<'k> Parser<'k> {
 
     fn advance(&mut self) {
         self.current_token = self.tokens.next();
-        if self.current_token.is_some() {
-            self.last_location = Some(self.current_token.as_ref().unwrap().start);
+        if let Some(token) = &self.current_token {
+            self.last_location = Some(token.start);
         }
     }
 
@@ -680,18 +858,18 @@ impl<'k> Parser<'k> {
                     self.advance();
                     return;
                 }
-            }
 
-            if let Some(t) = &self.current_token {
-                match t.token_type {
+                match token.token_type {
                     token::TokenType::Class
+                    | token::TokenType::Enum
                     | token::TokenType::Fun
                     | token::TokenType::Var
                     | token::TokenType::For
                     | token::TokenType::If
                     | token::TokenType::While
                     | token::TokenType::Print
-                    | token::TokenType::Return => return,
+                    | token::TokenType::Return
+                    | token::TokenType::Yield => return,
                     _ => (),
                 }
             }
@@ -834,6 +1012,42 @@ mod test {
                 |)\n",
             ),
             ("super.method ; ", "(; (super method))\n"),
+            (
+                "for ( x in xs ) print x;",
+                "(for-in x xs
+                |    (print x)
+                |)\n",
+            ),
+            (
+                "for ( var x in [1, 2] ) print x;",
+                "(for-in x (list 1 2)
+                |    (print x)
+                |)\n",
+            ),
+            (
+                "var m = {\"a\": 1, 2: []};",
+                "(var m = (map (\"a\" 1) (2 (list))))\n",
+            ),
+            ("a[0] = a[1];", "(; (= (index a 0) (index a 1)))\n"),
+            ("a is B == true;", "(; (== (is a B) true))\n"),
+            ("inner(1);", "(; (call inner 1))\n"),
+            ("enum Color { Red, Green, }", "(enum Color Red Green)\n"),
+            ("enum Empty {}", "(enum Empty)\n"),
+            (
+                "class a_class { #secret() { return this.#value; } }",
+                "(class a_class
+                |    (fun #secret()
+                |        (return (this.#value))
+                |    )
+                |)\n",
+            ),
+            (
+                "fun numbers() { yield 1; yield; }",
+                "(fun* numbers()
+                |    (yield 1)
+                |    (yield)
+                |)\n",
+            ),
         ];
 
         for (src, expected_parse) in tests {
@@ -923,6 +1137,21 @@ mod test {
             ("super", "Expect '.' after 'super'"),
             ("super.10", "Expect superclass method name"),
             ("fred.10", "Expect property name after '.'"),
+            (
+                "for ( 10 in xs ) print x;",
+                "Expect variable name before 'in'",
+            ),
+            ("for ( x in xs print x;", "Expect ')' after 'for' iterable"),
+            ("print [1, 2;", "Expect ']' after list elements"),
+            ("print {\"a\" 1};", "Expect ':' after map key"),
+            ("print {\"a\": 1;", "Expect '}' after map entries"),
+            ("print a[0;", "Expect ']' after index"),
+            ("fun f() { yield 1 }", "Expect ';' after yield value"),
+            ("fun #f() {}", "Expect function name"),
+            ("enum { Red }", "Expect enum name"),
+            ("enum Color { Red Green }", "Expect '}' after enum members"),
+            ("enum Color { 1 }", "Expect enum member name"),
+            ("var #a = 1;", "Expect a variable name"),
         ];
 
         for (src, expected_message) in tests {
@@ -978,4 +1207,22 @@ mod test {
             );
         }
     }
+
+    #[test]
+    fn parsing_is_deterministic() {
+        let src = "class A < B { init() { this.x = super.x; } }
+            var a = A(); fun f(n) { fun g() { n = n + a.x; return n; } return g; }";
+        let reporter = TestReporter::new();
+        let parse = || {
+            format!(
+                "{:?}",
+                parse(&reporter, scanner::scan_tokens(&reporter, src))
+            )
+        };
+
+        let first = parse();
+        let second = parse();
+        assert!(!reporter.has_diagnostics());
+        assert_eq!(first, second);
+    }
 }
@@ -30,8 +30,25 @@ enum ClassType {
     Subclass,
 }
 
+/// Where a local variable lives: how many scopes out from where it is used,
+/// and its index among the variables of that scope.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Local {
+    pub depth: usize,
+    pub slot: usize,
+}
+
+/// A variable in a scope: whether it has been defined yet, and its slot.
+#[derive(Clone, Copy)]
+struct Variable {
+    defined: bool,
+    slot: usize,
+}
+
 struct Scopes {
-    scopes: LinkedList<HashMap<String, bool>>,
+    scopes: LinkedList<HashMap<String, Variable>>,
+    // Members of the enums in scope, keyed by nesting level (0 is global) and name
+    enums: HashMap<(usize, String), Vec<String>>,
 }
 
 impl Scopes {
@@ -39,6 +56,7 @@ impl Scopes {
         Self {
             // Initial scope is 'global' and not stored
             scopes: LinkedList::new(),
+            enums: HashMap::new(),
         }
     }
 
@@ -51,6 +69,7 @@ impl Scopes {
     }
 
     fn declare<'t>(&mut self, name: &'t token::Token) -> Result<(), ResolverError<'t>> {
+        self.enums.remove(&(self.scopes.len(), name.lexeme.clone()));
         if self
             .scopes
             .front()
@@ -64,52 +83,110 @@ impl Scopes {
                 ),
             ));
         }
-        self.scopes
-            .front_mut()
-            .and_then(|m| m.insert(name.lexeme.clone(), false));
+        if let Some(scope) = self.scopes.front_mut() {
+            let slot = scope.len();
+            scope.insert(
+                name.lexeme.clone(),
+                Variable {
+                    defined: false,
+                    slot,
+                },
+            );
+        }
         Ok(())
     }
 
+    /// Marks `name` as defined, giving it the next slot if it was not
+    /// declared first.
     fn define(&mut self, name: &str) {
-        self.scopes
-            .front_mut()
-            .and_then(|m| m.insert(name.to_string(), true));
+        if let Some(scope) = self.scopes.front_mut() {
+            let slot = scope.len();
+            scope
+                .entry(name.to_string())
+                .or_insert(Variable {
+                    defined: false,
+                    slot,
+                })
+                .defined = true;
+        }
     }
 
     fn is_declared_in_current_scope(&self, name: &str) -> bool {
         self.scopes
             .front()
             .iter()
-            .any(|m| m.get(name).is_some_and(|o| !(*o)))
+            .any(|m| m.get(name).is_some_and(|v| !v.defined))
+    }
+
+    fn define_enum(&mut self, name: &str, members: Vec<String>) {
+        self.enums
+            .insert((self.scopes.len(), name.to_string()), members);
+    }
+
+    fn enum_key(&self, name: &str) -> (usize, String) {
+        let level = self
+            .find_depth(name)
+            .map_or(0, |depth| self.scopes.len() - depth);
+        (level, name.to_string())
+    }
+
+    fn find_enum(&self, name: &str) -> Option<&Vec<String>> {
+        self.enums.get(&self.enum_key(name))
+    }
+
+    fn forget_enum(&mut self, name: &str) {
+        let key = self.enum_key(name);
+        self.enums.remove(&key);
     }
 
     fn find_depth(&self, name: &str) -> Option<usize> {
-        for (i, scope) in self.scopes.iter().enumerate() {
-            if scope.contains_key(name) {
-                return Some(i);
-            }
-        }
-        None
+        self.find(name).map(|local| local.depth)
+    }
+
+    fn find(&self, name: &str) -> Option<Local> {
+        self.scopes.iter().enumerate().find_map(|(depth, scope)| {
+            scope.get(name).map(|variable| Local {
+                depth,
+                slot: variable.slot,
+            })
+        })
     }
 }
 
 struct Resolver<'r> {
     reporter: &'r dyn reporter::Reporter,
     scopes: Scopes,
-    depths: HashMap<usize, usize>,
     current_function: FunctionType,
+    in_generator: bool,
     current_class: ClassType,
 }
 
-pub fn resolve(
+/// What the resolver learned about the globals declared by earlier input, so
+/// that a session can resolve its input one piece at a time.
+#[derive(Default)]
+pub struct Globals {
+    enums: HashMap<(usize, String), Vec<String>>,
+}
+
+pub fn resolve(reporter: &dyn reporter::Reporter, statements: &LinkedList<stmt::Stmt>) {
+    resolve_with_globals(reporter, &mut Globals::default(), statements)
+}
+
+/// Resolves `statements` after the input `globals` was built from. `globals`
+/// only takes in the new declarations when they resolve without errors.
+pub fn resolve_with_globals(
     reporter: &dyn reporter::Reporter,
+    globals: &mut Globals,
     statements: &LinkedList<stmt::Stmt>,
-) -> HashMap<usize, usize> {
+) {
     let mut resolver = Resolver::new(reporter);
+    resolver.scopes.enums = globals.enums.clone();
 
     resolver.resolve_stmts(statements);
 
-    resolver.depths
+    if !reporter.has_diagnostics() {
+        globals.enums = resolver.scopes.enums;
+    }
 }
 
 impl<'r> Resolver<'r> {
@@ -117,8 +194,8 @@ impl<'r> Resolver<'r> {
         Self {
             reporter,
             scopes: Scopes::new(),
-            depths: HashMap::new(),
             current_function: FunctionType::None,
+            in_generator: false,
             current_class: ClassType::None,
         }
     }
@@ -138,7 +215,13 @@ impl<'r> Resolver<'r> {
                 methods,
                 ..
             } => self.resolve_stmt_class(name, superclass, methods),
+            stmt::Stmt::Enum { name, members } => self.resolve_stmt_enum(name, members),
             stmt::Stmt::Expression { expression } => self.resolve_stmt_expression(expression),
+            stmt::Stmt::ForIn {
+                name,
+                iterable,
+                body,
+            } => self.resolve_stmt_for_in(name, iterable, body),
             stmt::Stmt::Function { function } => self.resolve_stmt_function(function),
             stmt::Stmt::If {
                 condition,
@@ -149,27 +232,45 @@ impl<'r> Resolver<'r> {
             stmt::Stmt::Return { keyword, value, .. } => self.resolve_stmt_return(keyword, value),
             stmt::Stmt::Var { name, initialiser } => self.resolve_stmt_var(name, initialiser),
             stmt::Stmt::While { condition, body } => self.resolve_stmt_while(condition, body),
+            stmt::Stmt::Yield { keyword, value } => self.resolve_stmt_yield(keyword, value),
         }
     }
 
     fn resolve_expr(&mut self, expression: &expr::Expr) {
         match expression {
-            expr::Expr::Assign { id, name, value } => self.resolve_expr_assign(id, name, value),
+            expr::Expr::Assign { local, name, value } => {
+                self.resolve_expr_assign(local, name, value)
+            }
             expr::Expr::Binary { left, right, .. } => self.resolve_expr_binary(left, right),
             expr::Expr::Call {
                 callee, arguments, ..
             } => self.resolve_expr_call(callee, arguments),
-            expr::Expr::Get { object, .. } => self.resolve_expr_get(object),
+            expr::Expr::Get { object, name, .. } => self.resolve_expr_get(object, name),
             expr::Expr::Grouping { expression, .. } => self.resolve_expr_grouping(expression),
+            expr::Expr::Index { object, index, .. } => self.resolve_expr_index(object, index),
+            expr::Expr::Inner { local, keyword } => self.resolve_expr_inner(local, keyword),
             expr::Expr::InvalidGet { .. } => (),
             expr::Expr::InvalidSuper { .. } => (),
+            expr::Expr::List { elements, .. } => self.resolve_expr_list(elements),
             expr::Expr::Literal { value, .. } => self.resolve_expr_literal(value),
             expr::Expr::Logical { left, right, .. } => self.resolve_expr_logical(left, right),
-            expr::Expr::Set { object, value, .. } => self.resolve_expr_set(object, value),
-            expr::Expr::Super { id, keyword, .. } => self.resolve_expr_super(id, keyword),
-            expr::Expr::This { id, keyword, .. } => self.resolve_expr_this(id, keyword),
+            expr::Expr::Map { entries, .. } => self.resolve_expr_map(entries),
+            expr::Expr::Set {
+                object,
+                name,
+                value,
+                ..
+            } => self.resolve_expr_set(object, name, value),
+            expr::Expr::SetIndex {
+                object,
+                index,
+                value,
+                ..
+            } => self.resolve_expr_set_index(object, index, value),
+            expr::Expr::Super { local, keyword, .. } => self.resolve_expr_super(local, keyword),
+            expr::Expr::This { local, keyword, .. } => self.resolve_expr_this(local, keyword),
             expr::Expr::Unary { right, .. } => self.resolve_expr_unary(right),
-            expr::Expr::Variable { id, name } => self.resolve_expr_variable(id, name),
+            expr::Expr::Variable { local, name } => self.resolve_expr_variable(local, name),
         }
     }
 
@@ -202,6 +303,9 @@ impl<'r> Resolver<'r> {
 
         self.scopes.begin();
         self.scopes.define("this");
+        self.scopes.define("inner");
+        // Where a bound method keeps the class that declared it.
+        self.scopes.define("#class");
 
         for method in methods {
             if let stmt::Stmt::Function { function, .. } = method {
@@ -237,10 +341,47 @@ impl<'r> Resolver<'r> {
         self.resolve_expr(superclass);
     }
 
+    fn resolve_stmt_enum(&mut self, name: &token::Token, members: &LinkedList<token::Token>) {
+        if let Err(e) = self.scopes.declare(name) {
+            self.reporter
+                .add_diagnostic(&e.token.start, &e.token.end, &e.message);
+        }
+        self.scopes.define(&name.lexeme);
+
+        let mut names = Vec::new();
+        for member in members {
+            if names.contains(&member.lexeme) {
+                self.add_diagnostic(
+                    member,
+                    &format!("Duplicate enum member '{}'", member.lexeme),
+                );
+            }
+            names.push(member.lexeme.clone());
+        }
+        self.scopes.define_enum(&name.lexeme, names);
+    }
+
     fn resolve_stmt_expression(&mut self, expression: &expr::Expr) {
         self.resolve_expr(expression);
     }
 
+    fn resolve_stmt_for_in(
+        &mut self,
+        name: &token::Token,
+        iterable: &expr::Expr,
+        body: &stmt::Stmt,
+    ) {
+        self.resolve_expr(iterable);
+        self.scopes.begin();
+        if let Err(e) = self.scopes.declare(name) {
+            self.reporter
+                .add_diagnostic(&e.token.start, &e.token.end, &e.message);
+        }
+        self.scopes.define(&name.lexeme);
+        self.resolve_stmt(body);
+        self.scopes.end();
+    }
+
     fn resolve_stmt_function(&mut self, function: &stmt::function::Function) {
         if let Err(e) = self.scopes.declare(function.name()) {
             self.reporter
@@ -281,6 +422,13 @@ impl<'r> Resolver<'r> {
                     "Cannot return a value from an initialiser",
                 );
             }
+            if self.in_generator {
+                self.reporter.add_diagnostic(
+                    &keyword.start,
+                    &keyword.end,
+                    "Cannot return a value from a generator",
+                );
+            }
             self.resolve_expr(expression);
         }
     }
@@ -299,9 +447,32 @@ impl<'r> Resolver<'r> {
         self.resolve_stmt(body);
     }
 
-    fn resolve_expr_assign(&mut self, id: &usize, name: &token::Token, value: &expr::Expr) {
+    fn resolve_stmt_yield(&mut self, keyword: &token::Token, value: &Option<expr::Expr>) {
+        match self.current_function {
+            FunctionType::None => self.reporter.add_diagnostic(
+                &keyword.start,
+                &keyword.end,
+                "Cannot yield from top-level code",
+            ),
+            FunctionType::Initialiser => self.reporter.add_diagnostic(
+                &keyword.start,
+                &keyword.end,
+                "Cannot yield from an initialiser",
+            ),
+            _ => (),
+        }
+        value.iter().for_each(|value| self.resolve_expr(value));
+    }
+
+    fn resolve_expr_assign(
+        &mut self,
+        local: &expr::Resolution,
+        name: &token::Token,
+        value: &expr::Expr,
+    ) {
         self.resolve_expr(value);
-        self.resolve_local(id, name);
+        self.resolve_local(local, name);
+        self.scopes.forget_enum(&name.lexeme);
     }
 
     fn resolve_expr_binary(&mut self, left: &expr::Expr, right: &expr::Expr) {
@@ -314,14 +485,51 @@ impl<'r> Resolver<'r> {
         arguments.iter().for_each(|a| self.resolve_expr(a));
     }
 
-    fn resolve_expr_get(&mut self, object: &expr::Expr) {
+    fn resolve_expr_get(&mut self, object: &expr::Expr, name: &token::Token) {
+        self.resolve_private_access(object, name);
+        self.resolve_enum_member(object, name);
         self.resolve_expr(object);
     }
 
+    fn resolve_enum_member(&mut self, object: &expr::Expr, name: &token::Token) {
+        if let expr::Expr::Variable {
+            name: enum_name, ..
+        } = object
+        {
+            if let Some(members) = self.scopes.find_enum(&enum_name.lexeme) {
+                if !members.contains(&name.lexeme) && name.lexeme != "iterator" {
+                    self.add_diagnostic(
+                        name,
+                        &format!(
+                            "Enum '{}' has no member '{}'",
+                            enum_name.lexeme, name.lexeme
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
     fn resolve_expr_grouping(&mut self, expression: &expr::Expr) {
         self.resolve_expr(expression);
     }
 
+    fn resolve_expr_inner(&mut self, local: &expr::Resolution, keyword: &token::Token) {
+        if self.current_class == ClassType::None {
+            self.add_diagnostic(keyword, "Cannot use 'inner' outside of a method");
+        }
+        self.resolve_local(local, keyword);
+    }
+
+    fn resolve_expr_index(&mut self, object: &expr::Expr, index: &expr::Expr) {
+        self.resolve_expr(object);
+        self.resolve_expr(index);
+    }
+
+    fn resolve_expr_list(&mut self, elements: &[expr::Expr]) {
+        elements.iter().for_each(|e| self.resolve_expr(e));
+    }
+
     fn resolve_expr_literal(&mut self, _: &token::Token) {}
 
     fn resolve_expr_logical(&mut self, left: &expr::Expr, right: &expr::Expr) {
@@ -329,43 +537,73 @@ impl<'r> Resolver<'r> {
         self.resolve_expr(right);
     }
 
-    fn resolve_expr_set(&mut self, object: &expr::Expr, value: &expr::Expr) {
+    fn resolve_expr_map(&mut self, entries: &[(expr::Expr, expr::Expr)]) {
+        entries.iter().for_each(|(key, value)| {
+            self.resolve_expr(key);
+            self.resolve_expr(value);
+        });
+    }
+
+    fn resolve_expr_set(&mut self, object: &expr::Expr, name: &token::Token, value: &expr::Expr) {
+        self.resolve_private_access(object, name);
+        self.resolve_expr(object);
+        self.resolve_expr(value);
+    }
+
+    fn resolve_private_access(&mut self, object: &expr::Expr, name: &token::Token) {
+        if name.token_type == token::TokenType::PrivateIdentifier
+            && (self.current_class == ClassType::None || !matches!(object, expr::Expr::This { .. }))
+        {
+            self.add_diagnostic(
+                name,
+                &format!(
+                    "Private member '{}' is only accessible through 'this' inside its class",
+                    name.lexeme
+                ),
+            );
+        }
+    }
+
+    fn resolve_expr_set_index(
+        &mut self,
+        object: &expr::Expr,
+        index: &expr::Expr,
+        value: &expr::Expr,
+    ) {
         self.resolve_expr(object);
+        self.resolve_expr(index);
         self.resolve_expr(value);
     }
 
-    fn resolve_expr_super(&mut self, id: &usize, keyword: &token::Token) {
+    fn resolve_expr_super(&mut self, local: &expr::Resolution, keyword: &token::Token) {
         if self.current_class == ClassType::None {
             self.add_diagnostic(keyword, "Cannot use 'super' outside of a class")
         } else if self.current_class != ClassType::Subclass {
             self.add_diagnostic(keyword, "Cannot use 'super' in a class with no superclass")
         }
-        self.resolve_local(id, keyword);
+        self.resolve_local(local, keyword);
     }
 
-    fn resolve_expr_this(&mut self, id: &usize, keyword: &token::Token) {
+    fn resolve_expr_this(&mut self, local: &expr::Resolution, keyword: &token::Token) {
         if self.current_class == ClassType::None {
             self.add_diagnostic(keyword, "Cannot use 'this' outside of a class");
         }
-        self.resolve_local(id, keyword);
+        self.resolve_local(local, keyword);
     }
 
     fn resolve_expr_unary(&mut self, right: &expr::Expr) {
         self.resolve_expr(right);
     }
 
-    fn resolve_expr_variable(&mut self, id: &usize, name: &token::Token) {
+    fn resolve_expr_variable(&mut self, local: &expr::Resolution, name: &token::Token) {
         if self.scopes.is_declared_in_current_scope(&name.lexeme) {
             self.add_diagnostic(name, "Cannot read local variable in its own initialiser");
         }
-        self.resolve_local(id, name);
+        self.resolve_local(local, name);
     }
 
-    fn resolve_local(&mut self, id: &usize, name: &token::Token) {
-        let depth = self.scopes.find_depth(&name.lexeme);
-        depth.iter().for_each(|d| {
-            self.depths.insert(*id, *d);
-        });
+    fn resolve_local(&mut self, local: &expr::Resolution, name: &token::Token) {
+        local.set(self.scopes.find(&name.lexeme));
     }
 
     fn resolve_function(
@@ -374,7 +612,9 @@ impl<'r> Resolver<'r> {
         function: &stmt::function::Function,
     ) {
         let enclosing_function = self.current_function;
+        let enclosing_generator = self.in_generator;
         self.current_function = function_type;
+        self.in_generator = function.is_generator();
 
         self.scopes.begin();
         for param in function.params() {
@@ -387,6 +627,7 @@ impl<'r> Resolver<'r> {
         self.resolve_stmts(function.body());
         self.scopes.end();
         self.current_function = enclosing_function;
+        self.in_generator = enclosing_generator;
     }
 
     fn add_diagnostic(&self, t: &token::Token, message: &str) {
@@ -568,11 +809,38 @@ mod test {
                 "class Example { init() { return 10; } }",
                 "Cannot return a value from an initialiser",
             ),
+            (
+                "class Example {} Example().#secret;",
+                "Private member '#secret' is only accessible through 'this' inside its class",
+            ),
+            (
+                "class Example { copy(other) { other.#secret = 1; } }",
+                "Private member '#secret' is only accessible through 'this' inside its class",
+            ),
+            ("yield 1;", "Cannot yield from top-level code"),
+            (
+                "class Example { init() { yield 1; } }",
+                "Cannot yield from an initialiser",
+            ),
+            (
+                "fun numbers() { yield 1; return 2; }",
+                "Cannot return a value from a generator",
+            ),
             (
                 "class Example < Example { }",
                 "A class cannot inherit from itself",
             ),
             ("print this;", "Cannot use 'this' outside of a class"),
+            ("inner();", "Cannot use 'inner' outside of a method"),
+            ("enum Color { Red, Red }", "Duplicate enum member 'Red'"),
+            (
+                "enum Color { Red } print Color.Blue;",
+                "Enum 'Color' has no member 'Blue'",
+            ),
+            (
+                "fun f() { enum Color { Red } { print Color.Blue; } }",
+                "Enum 'Color' has no member 'Blue'",
+            ),
             (
                 "print super.method;",
                 "Cannot use 'super' outside of a class",
@@ -589,11 +857,43 @@ mod test {
             let tokens = scanner::scan_tokens(&reporter, src);
             let statements = parser::parse(&reporter, tokens);
             reporter.reset();
-            let _ = resolve(&reporter, &statements);
+            resolve(&reporter, &statements);
             if !reporter.has_diagnostic(expected_diagnostic) {
                 reporter.print_contents();
                 panic!("Missing diagnostic '{}' for '{}'", expected_diagnostic, src);
             }
         }
     }
+
+    #[test]
+    fn test_resolutions_are_stored_in_the_ast() {
+        let src = "var g = 1; fun f(a) { var b = a; { print b + g; } }";
+        let reporter = TestReporter::new();
+        let parse = || {
+            let tokens = scanner::scan_tokens(&reporter, src);
+            let statements = parser::parse(&reporter, tokens);
+            resolve(&reporter, &statements);
+            statements
+        };
+
+        let first = parse();
+        let second = parse();
+        assert!(!reporter::Reporter::has_diagnostics(&reporter));
+        assert_eq!(format!("{:?}", first), format!("{:?}", second));
+
+        let debug = format!("{:?}", first);
+        let resolutions: Vec<&str> = debug
+            .split("local: Cell { value: ")
+            .skip(1)
+            .filter_map(|part| part.split(" }, ").next())
+            .collect();
+        assert_eq!(
+            resolutions,
+            vec![
+                "Some(Local { depth: 0, slot: 0 })",
+                "Some(Local { depth: 1, slot: 1 })",
+                "None",
+            ]
+        );
+    }
 }
@@ -62,6 +62,9 @@ impl<'k> Scanner<'k> {
             ')' => Some(self.new_token(token::TokenType::RightParen, source)),
             '{' => Some(self.new_token(token::TokenType::LeftBrace, source)),
             '}' => Some(self.new_token(token::TokenType::RightBrace, source)),
+            '[' => Some(self.new_token(token::TokenType::LeftBracket, source)),
+            ']' => Some(self.new_token(token::TokenType::RightBracket, source)),
+            ':' => Some(self.new_token(token::TokenType::Colon, source)),
             ',' => Some(self.new_token(token::TokenType::Comma, source)),
             '.' => Some(self.new_token(token::TokenType::Dot, source)),
             '-' => Some(self.new_token(token::TokenType::Minus, source)),
@@ -116,6 +119,10 @@ impl<'k> Scanner<'k> {
                     Some(self.new_number(source))
                 } else if c.is_alphabetic() || c == '_' {
                     Some(self.new_identifier(source))
+                } else if c == '#'
+                    && matches!(self.char_indices.peek(), Some((_, c)) if c.is_alphabetic() || *c == '_')
+                {
+                    Some(self.new_private_identifier(source))
                 } else {
                     self.reporter.add_diagnostic(
                         &location::FileLocation::new(
@@ -263,7 +270,7 @@ impl<'k> Scanner<'k> {
         }
     }
 
-    fn new_identifier(&mut self, source: &str) -> token::Token {
+    fn scan_identifier(&mut self) {
         while let Some((_i, c)) = self.char_indices.peek() {
             if c.is_alphabetic() || c.is_ascii_digit() || *c == '_' {
                 self.advance();
@@ -271,6 +278,15 @@ impl<'k> Scanner<'k> {
                 break;
             }
         }
+    }
+
+    fn new_private_identifier(&mut self, source: &str) -> token::Token {
+        self.scan_identifier();
+        self.new_token(token::TokenType::PrivateIdentifier, source)
+    }
+
+    fn new_identifier(&mut self, source: &str) -> token::Token {
+        self.scan_identifier();
         let token = self.new_token(token::TokenType::Identifier, source);
         if let Some(identifier_token) = self.keywords.get_keyword(&token.lexeme) {
             let literal = token::get_keyword_literal(&identifier_token);
@@ -389,25 +405,53 @@ mod test {
 
     #[test]
     fn multiple_tokens() {
-        let tests = vec![(
-            "( )",
-            vec![
-                token::Token::new(
-                    token::TokenType::LeftParen,
-                    "(",
-                    location::FileLocation::new(0, 0),
-                    location::FileLocation::new(0, 1),
-                    None,
-                ),
-                token::Token::new(
-                    token::TokenType::RightParen,
-                    ")",
-                    location::FileLocation::new(0, 2),
-                    location::FileLocation::new(0, 3),
-                    None,
-                ),
-            ],
-        )];
+        let tests = vec![
+            (
+                "( )",
+                vec![
+                    token::Token::new(
+                        token::TokenType::LeftParen,
+                        "(",
+                        location::FileLocation::new(0, 0),
+                        location::FileLocation::new(0, 1),
+                        None,
+                    ),
+                    token::Token::new(
+                        token::TokenType::RightParen,
+                        ")",
+                        location::FileLocation::new(0, 2),
+                        location::FileLocation::new(0, 3),
+                        None,
+                    ),
+                ],
+            ),
+            (
+                "[ : ]",
+                vec![
+                    token::Token::new(
+                        token::TokenType::LeftBracket,
+                        "[",
+                        location::FileLocation::new(0, 0),
+                        location::FileLocation::new(0, 1),
+                        None,
+                    ),
+                    token::Token::new(
+                        token::TokenType::Colon,
+                        ":",
+                        location::FileLocation::new(0, 2),
+                        location::FileLocation::new(0, 3),
+                        None,
+                    ),
+                    token::Token::new(
+                        token::TokenType::RightBracket,
+                        "]",
+                        location::FileLocation::new(0, 4),
+                        location::FileLocation::new(0, 5),
+                        None,
+                    ),
+                ],
+            ),
+        ];
 
         execute_tests(&tests);
     }
@@ -582,6 +626,16 @@ mod test {
                     None,
                 )],
             ),
+            (
+                "#secret",
+                vec![token::Token::new(
+                    token::TokenType::PrivateIdentifier,
+                    "#secret",
+                    location::FileLocation::new(0, 0),
+                    location::FileLocation::new(0, 7),
+                    None,
+                )],
+            ),
             (
                 "with123digits",
                 vec![token::Token::new(
@@ -735,6 +789,14 @@ mod test {
                     message: "Unexpected character".to_string(),
                 }],
             ),
+            (
+                "# 1",
+                vec![Diagnostic {
+                    start: location::FileLocation::new(0, 0),
+                    end: location::FileLocation::new(0, 1),
+                    message: "Unexpected character".to_string(),
+                }],
+            ),
         ];
 
         for (source, expected_diagnostics) in tests {
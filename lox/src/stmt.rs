@@ -12,9 +12,18 @@ pub enum Stmt {
         superclass: Option<expr::Expr>,
         methods: LinkedList<Stmt>,
     },
+    Enum {
+        name: token::Token,
+        members: LinkedList<token::Token>,
+    },
     Expression {
         expression: expr::Expr,
     },
+    ForIn {
+        name: token::Token,
+        iterable: expr::Expr,
+        body: Box<Stmt>,
+    },
     Function {
         function: function::Function,
     },
@@ -38,6 +47,10 @@ pub enum Stmt {
         condition: expr::Expr,
         body: Box<Stmt>,
     },
+    Yield {
+        keyword: token::Token,
+        value: Option<expr::Expr>,
+    },
 }
 
 impl Stmt {
@@ -45,9 +58,11 @@ impl Stmt {
         name: token::Token,
         params: LinkedList<token::Token>,
         body: LinkedList<Stmt>,
+        is_generator: bool,
+        calls_inner: bool,
     ) -> Self {
         stmt::Stmt::Function {
-            function: function::Function::new(name, params, body),
+            function: function::Function::new(name, params, body, is_generator, calls_inner),
         }
     }
 }
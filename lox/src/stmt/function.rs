@@ -7,6 +7,8 @@ struct InternalFunction {
     pub name: token::Token,
     pub params: LinkedList<token::Token>,
     pub body: LinkedList<stmt::Stmt>,
+    pub is_generator: bool,
+    pub calls_inner: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -19,9 +21,17 @@ impl Function {
         name: token::Token,
         params: LinkedList<token::Token>,
         body: LinkedList<stmt::Stmt>,
+        is_generator: bool,
+        calls_inner: bool,
     ) -> Self {
         Self {
-            function: Rc::new(InternalFunction { name, params, body }),
+            function: Rc::new(InternalFunction {
+                name,
+                params,
+                body,
+                is_generator,
+                calls_inner,
+            }),
         }
     }
 
@@ -36,4 +46,15 @@ impl Function {
     pub fn body(&self) -> &LinkedList<stmt::Stmt> {
         &self.function.body
     }
+
+    /// A function whose body contains `yield`; calling it returns an iterator.
+    pub fn is_generator(&self) -> bool {
+        self.function.is_generator
+    }
+
+    /// A method whose body calls `inner()`. It takes precedence over the
+    /// subclass methods it defers to.
+    pub fn calls_inner(&self) -> bool {
+        self.function.calls_inner
+    }
 }
@@ -8,6 +8,9 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
+    Colon,
     Comma,
     Dot,
     Minus,
@@ -28,6 +31,7 @@ pub enum TokenType {
 
     // Literals.
     Identifier,
+    PrivateIdentifier,
     String,
     Number,
 
@@ -35,10 +39,14 @@ pub enum TokenType {
     And,
     Class,
     Else,
+    Enum,
     False,
     Fun,
     For,
     If,
+    In,
+    Inner,
+    Is,
     Nil,
     Or,
     Print,
@@ -48,6 +56,7 @@ pub enum TokenType {
     True,
     Var,
     While,
+    Yield,
 
     Eof,
 }
@@ -62,10 +71,14 @@ impl<'a> Keywords<'a> {
         keywords.insert("and", TokenType::And);
         keywords.insert("class", TokenType::Class);
         keywords.insert("else", TokenType::Else);
+        keywords.insert("enum", TokenType::Enum);
         keywords.insert("false", TokenType::False);
         keywords.insert("for", TokenType::For);
         keywords.insert("fun", TokenType::Fun);
         keywords.insert("if", TokenType::If);
+        keywords.insert("in", TokenType::In);
+        keywords.insert("inner", TokenType::Inner);
+        keywords.insert("is", TokenType::Is);
         keywords.insert("nil", TokenType::Nil);
         keywords.insert("or", TokenType::Or);
         keywords.insert("print", TokenType::Print);
@@ -75,6 +88,7 @@ impl<'a> Keywords<'a> {
         keywords.insert("true", TokenType::True);
         keywords.insert("var", TokenType::Var);
         keywords.insert("while", TokenType::While);
+        keywords.insert("yield", TokenType::Yield);
 
         Keywords { keywords }
     }
@@ -144,7 +158,7 @@ impl location::ProvideLocation for Token {
     }
 }
 
-impl<'a> location::ProvideLocation for &'a Token {
+impl location::ProvideLocation for &Token {
     fn start(&self) -> &location::FileLocation {
         &self.start
     }
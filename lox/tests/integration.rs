@@ -1,12 +1,15 @@
 mod common;
 
 use lox::Reporter;
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::rc::Rc;
 
 #[test]
 fn test_statements() {
     let mut reporter = common::TestReporter::new();
     let tests = vec![
-        //("var a = clock(); print a;", "[print] 10"),
+        ("var a = clock(); print a;", vec!["[print] 10"]),
         (
             "class Person {
                 sayName() {
@@ -17,18 +20,18 @@ fn test_statements() {
             jane.name = \"jane\";
             var m = jane.sayName;
             m();",
-            vec!["[print] \"jane\""],
+            vec!["[print] jane"],
         ),
         (
             "print \"hello,\" + \" world\";",
-            vec!["[print] \"hello, world\""],
+            vec!["[print] hello, world"],
         ),
         ("print 10 + 10;", vec!["[print] 20"]),
         ("print 10 - 5;", vec!["[print] 5"]),
         ("print 10 > 5;", vec!["[print] true"]),
         ("print 5 > 5;", vec!["[print] false"]),
         ("print 5 >= 5;", vec!["[print] true"]),
-        ("print \"a string\";", vec!["[print] \"a string\""]),
+        ("print \"a string\";", vec!["[print] a string"]),
         ("print 10.5 ;", vec!["[print] 10.5"]),
         ("print true ;", vec!["[print] true"]),
         ("print false ;", vec!["[print] false"]),
@@ -104,7 +107,7 @@ fn test_statements() {
             var outer = \"outer str\";
             counter();
             print outer;",
-            vec!["[print] 1", "[print] \"outer str\""],
+            vec!["[print] 1", "[print] outer str"],
         ),
         (
             "class Thing {
@@ -118,7 +121,7 @@ fn test_statements() {
             }
             var callback = Thing().getCallback();
             callback();",
-            vec!["[print] \"Hello\""],
+            vec!["[print] Hello"],
         ),
         (
             "class Thing {
@@ -132,7 +135,7 @@ fn test_statements() {
             var val = Thing(\"Bob\");
             var i = val.get();
             print i;",
-            vec!["[print] \"Hello Bob\""],
+            vec!["[print] Hello Bob"],
         ),
         (
             "class Thing {
@@ -144,7 +147,7 @@ fn test_statements() {
             var val = Thing(\"Bob\");
             var i = val.init(\"John\");
             print i.message;",
-            vec!["[print] \"John\""],
+            vec!["[print] John"],
         ),
         (
             "class Doughnut {
@@ -154,7 +157,7 @@ fn test_statements() {
             }
             class BostonCream < Doughnut {}
             BostonCream().cook();",
-            vec!["[print] \"Fry until golden brown\""],
+            vec!["[print] Fry until golden brown"],
         ),
         (
             "class Doughnut {
@@ -170,8 +173,8 @@ fn test_statements() {
             }
             BostonCream().cook();",
             vec![
-                "[print] \"Fry until golden brown\"",
-                "[print] \"Pipe full of custard and coat with chocolate\"",
+                "[print] Fry until golden brown",
+                "[print] Pipe full of custard and coat with chocolate",
             ],
         ),
         (
@@ -184,13 +187,482 @@ fn test_statements() {
             jane.name = \"jane\";
             var m = jane.sayName;
             m();",
-            vec!["[print] \"jane\""],
+            vec!["[print] jane"],
+        ),
+        (
+            "for (i in range(0, 3)) print i;",
+            vec!["[print] 0", "[print] 1", "[print] 2"],
+        ),
+        (
+            "var values = [\"a\", \"b\"];
+            values.push(\"c\");
+            for (var value in values) print value;
+            print values.length();",
+            vec!["[print] a", "[print] b", "[print] c", "[print] 3"],
+        ),
+        (
+            "var ages = {\"jane\": 30, \"john\": 40};
+            for (name in ages) print ages[name];",
+            vec!["[print] 30", "[print] 40"],
+        ),
+        ("for (c in \"hi\") print c;", vec!["[print] h", "[print] i"]),
+        (
+            "var values = [1, [2, 3]];
+            values[0] = values[1][1];
+            print values;",
+            vec!["[print] [3, [2, 3]]"],
+        ),
+        (
+            "class Countdown {
+                init(from) {
+                    this.from = from;
+                }
+                iterator() {
+                    return this;
+                }
+                hasNext() {
+                    return this.from > 0;
+                }
+                next() {
+                    this.from = this.from - 1;
+                    return this.from + 1;
+                }
+            }
+            for (n in Countdown(2)) print n;",
+            vec!["[print] 2", "[print] 1"],
+        ),
+        (
+            "var it = [1].iterator();
+            print it.hasNext();
+            print it.next();
+            print it.hasNext();",
+            vec!["[print] true", "[print] 1", "[print] false"],
+        ),
+        (
+            "fun count(to) {
+                var i = 0;
+                while (i < to) {
+                    yield i;
+                    i = i + 1;
+                }
+            }
+            for (n in count(3)) print n;",
+            vec!["[print] 0", "[print] 1", "[print] 2"],
+        ),
+        (
+            "fun describe(values) {
+                for (value in values) {
+                    if (value == 0) {
+                        yield \"even\";
+                    } else {
+                        { var odd = value; yield odd; }
+                    }
+                }
+                yield \"done\";
+            }
+            var it = describe([0, 5]);
+            print it.next();
+            print it.next();
+            print it.next();
+            print it.hasNext();",
+            vec!["[print] even", "[print] 5", "[print] done", "[print] false"],
+        ),
+        (
+            "fun naturals() {
+                var n = 0;
+                while (true) {
+                    n = n + 1;
+                    yield n;
+                }
+            }
+            var it = naturals();
+            it.next();
+            it.next();
+            print it.next();",
+            vec!["[print] 3"],
+        ),
+        (
+            "fun first(values) {
+                for (value in values) {
+                    yield value;
+                    return;
+                }
+            }
+            var result = [];
+            for (value in first([\"a\", \"b\"])) result.push(value);
+            print result;",
+            vec!["[print] [\"a\"]"],
+        ),
+        (
+            "class Tree {
+                init(value, children) {
+                    this.value = value;
+                    this.children = children;
+                }
+                iterator() {
+                    return this.walk();
+                }
+                walk() {
+                    yield this.value;
+                    for (child in this.children) {
+                        for (value in child) yield value;
+                    }
+                }
+            }
+            var tree = Tree(1, [Tree(2, []), Tree(3, [Tree(4, [])])]);
+            var values = [];
+            for (value in tree) values.push(value);
+            print values;",
+            vec!["[print] [1, 2, 3, 4]"],
+        ),
+        (
+            "class Counter {
+                init() {
+                    this.#count = 0;
+                }
+                increment() {
+                    this.#count = this.#bump(this.#count);
+                    var read = this.#read;
+                    return read();
+                }
+                #bump(value) {
+                    return value + 1;
+                }
+                #read() {
+                    fun read() {
+                        return this.#count;
+                    }
+                    return read();
+                }
+            }
+            var counter = Counter();
+            counter.increment();
+            print counter.increment();",
+            vec!["[print] 2"],
+        ),
+        (
+            "class Base {
+                init() {
+                    this.#name = \"base\";
+                }
+                baseName() {
+                    return this.#name;
+                }
+            }
+            class Derived < Base {
+                init() {
+                    super.init();
+                    this.#name = \"derived\";
+                }
+                derivedName() {
+                    return this.#name;
+                }
+            }
+            var d = Derived();
+            print d.baseName();
+            print d.derivedName();",
+            vec!["[print] base", "[print] derived"],
+        ),
+        (
+            "class Animal {}
+            class Dog < Animal {}
+            class Cat < Animal {}
+            var dog = Dog();
+            print dog is Dog;
+            print dog is Animal;
+            print dog is Cat;
+            print 1 is Animal;",
+            vec![
+                "[print] true",
+                "[print] true",
+                "[print] false",
+                "[print] false",
+            ],
+        ),
+        (
+            "class Animal { speak() {} }
+            class Dog < Animal { init() { this.name = \"rex\"; this.#age = 3; } fetch() {} }
+            var dog = Dog();
+            print typeof(dog);
+            print typeof(Dog);
+            print typeof(1);
+            print typeof(\"a\");
+            print typeof(nil);
+            print typeof(true);
+            print typeof([]);
+            print typeof({});
+            print typeof(clock);
+            print classOf(dog) == Dog;
+            print classOf(1);
+            print fields(dog);
+            print methods(Dog);
+            print superclassOf(Dog) == Animal;
+            print superclassOf(Animal);",
+            vec![
+                "[print] instance",
+                "[print] class",
+                "[print] number",
+                "[print] string",
+                "[print] nil",
+                "[print] boolean",
+                "[print] list",
+                "[print] map",
+                "[print] function",
+                "[print] true",
+                "[print] nil",
+                "[print] [\"name\"]",
+                "[print] [\"fetch\", \"init\", \"speak\"]",
+                "[print] true",
+                "[print] nil",
+            ],
+        ),
+        (
+            "class Doughnut {
+                cook() {
+                    print \"Fry until golden brown.\";
+                    inner();
+                    print \"Place in a nice box.\";
+                }
+            }
+            class BostonCream < Doughnut {
+                cook() {
+                    print \"Pipe full of custard and coat with chocolate.\";
+                }
+            }
+            BostonCream().cook();",
+            vec![
+                "[print] Fry until golden brown.",
+                "[print] Pipe full of custard and coat with chocolate.",
+                "[print] Place in a nice box.",
+            ],
+        ),
+        (
+            "class A {
+                wrap(value) {
+                    return \"A(\" + inner(value) + \")\";
+                }
+            }
+            class B < A {
+                wrap(value) {
+                    fun later() {
+                        return inner(value);
+                    }
+                    return \"B(\" + later() + \")\";
+                }
+            }
+            class C < B {
+                wrap(value) {
+                    return value;
+                }
+            }
+            print C().wrap(\"c\");",
+            vec!["[print] A(B(c))"],
+        ),
+        (
+            "class Base {
+                describe() {
+                    return inner();
+                }
+            }
+            print Base().describe();",
+            vec!["[print] nil"],
+        ),
+        (
+            "enum Color { Red, Green, Blue }
+            print Color;
+            print Color.Red;
+            print Color.Red == Color.Red;
+            print Color.Red == Color.Green;
+            print Color.Red != Color.Blue;
+            print typeof(Color.Green);",
+            vec![
+                "[print] enum Color",
+                "[print] Color.Red",
+                "[print] true",
+                "[print] false",
+                "[print] true",
+                "[print] enum member",
+            ],
+        ),
+        (
+            "enum Light { Red, Amber, Green, }
+            for (light in Light) {
+                print light;
+            }",
+            vec![
+                "[print] Light.Red",
+                "[print] Light.Amber",
+                "[print] Light.Green",
+            ],
+        ),
+        (
+            "enum State { Idle, Running }
+            fun next(state) {
+                if (state == State.Idle) return State.Running;
+                return State.Idle;
+            }
+            print next(State.Idle);
+            print next(next(State.Idle));",
+            vec!["[print] State.Running", "[print] State.Idle"],
+        ),
+        (
+            "enum A { X }
+            enum B { X }
+            print A.X == B.X;",
+            vec!["[print] false"],
+        ),
+        (
+            "var s = \"  Hello, World  \";
+            print s.length();
+            print s.trim();
+            print s.trim().upper();
+            print s.trim().lower();
+            print s.trim().substring(7, 12);
+            print s.indexOf(\"World\");
+            print s.indexOf(\"missing\");
+            print s.replace(\"l\", \"L\");
+            print s.trim().startsWith(\"Hello\");
+            print s.trim().startsWith(\"World\");",
+            vec![
+                "[print] 16",
+                "[print] Hello, World",
+                "[print] HELLO, WORLD",
+                "[print] hello, world",
+                "[print] World",
+                "[print] 9",
+                "[print] -1",
+                "[print]   HeLLo, WorLd  ",
+                "[print] true",
+                "[print] false",
+            ],
+        ),
+        (
+            "print \"a,b,,c\".split(\",\");
+            print \"abc\".split(\"\");
+            print \"héllo\".chars();
+            print \"héllo\".substring(1, 3);
+            print \"héllo\".indexOf(\"l\");",
+            vec![
+                "[print] [\"a\", \"b\", \"\", \"c\"]",
+                "[print] [\"a\", \"b\", \"c\"]",
+                "[print] [\"h\", \"é\", \"l\", \"l\", \"o\"]",
+                "[print] él",
+                "[print] 2",
+            ],
+        ),
+        (
+            "var n = 3.14159;
+            print n.floor();
+            print n.round();
+            print (2.5).round();
+            print (-2.7).floor();
+            print n.toFixed(2);
+            print n.toFixed(0);
+            print n.toString() + \"!\";
+            print 42.toString();",
+            vec![
+                "[print] 3",
+                "[print] 3",
+                "[print] 3",
+                "[print] -3",
+                "[print] 3.14",
+                "[print] 3",
+                "[print] 3.14159!",
+                "[print] 42",
+            ],
+        ),
+        (
+            "print (1 / 0).toFixed(2) == str(1 / 0);
+            print (-1 / 0).toFixed(2);
+            print (0 / 0).toFixed(2);",
+            vec!["[print] true", "[print] -inf", "[print] NaN"],
+        ),
+        (
+            "class Point {
+                init(x, y) {
+                    this.x = x;
+                    this.y = y;
+                }
+                toString() {
+                    return \"(\" + this.x.toString() + \", \" + this.y.toString() + \")\";
+                }
+                equals(other) {
+                    return other is Point and this.x == other.x and this.y == other.y;
+                }
+            }
+            var p = Point(1, 2);
+            print p;
+            print \"p = \" + p;
+            print p + \"!\";
+            print [p, {\"origin\": Point(0, 0)}];
+            print p == Point(1, 2);
+            print p != Point(2, 1);
+            print p == 1;",
+            vec![
+                "[print] (1, 2)",
+                "[print] p = (1, 2)",
+                "[print] (1, 2)!",
+                "[print] [\"(1, 2)\", {\"origin\": \"(0, 0)\"}]",
+                "[print] true",
+                "[print] true",
+                "[print] false",
+            ],
+        ),
+        (
+            "var l = [1]; var m = {}; print l == l; print m == m; print l == [1]; print m != {};",
+            vec!["[print] true", "[print] true", "[print] false", "[print] true"],
+        ),
+        (
+            "var l = [1]; l[0] = l; print l;
+            var m = {}; m[\"self\"] = m; print m;
+            var n = [1]; print [n, n];
+            class Node { init() { this.next = [this]; } toString() { print this.next; return \"node\"; } }
+            print [Node()];",
+            vec![
+                "[print] [[...]]",
+                "[print] {\"self\": {...}}",
+                "[print] [[1], [1]]",
+                "[print] [\"instance of Node\"]",
+                "[print] [\"node\"]",
+            ],
+        ),
+        (
+            "class Plain {}
+            var a = Plain();
+            print a == a;
+            print a == Plain();
+            print a;",
+            vec!["[print] true", "[print] false", "[print] Plain instance"],
+        ),
+        (
+            "class Loud {
+                toString() {
+                    print this;
+                    return \"Loud \" + this;
+                }
+                equals(other) {
+                    return this == other;
+                }
+            }
+            var loud = Loud();
+            print loud;
+            print loud == loud;",
+            vec![
+                "[print] Loud instance",
+                "[print] Loud Loud instance",
+                "[print] true",
+            ],
         ),
     ];
 
+    // A fixed clock, so that scripts reading it print the same every run.
+    let options = lox::Options {
+        clock: Some(Rc::new(|| 10.0)),
+        ..lox::Options::default()
+    };
     for (source, expected_messages) in tests {
         reporter.reset();
-        lox::interpret(&reporter, source);
+        lox::interpret_with_options(&reporter, source, options.clone());
         if reporter.has_diagnostics() {
             println!("Unexpected errors for : {}", source,);
             reporter.print_contents();
@@ -351,15 +823,15 @@ fn test_failures() {
             },
         ),
         (
-            "var value = 1; print value.field;",
+            "var value = true; print value.field;",
             common::Diagnostic {
                 start: lox::FileLocation {
                     line_number: 0,
-                    line_offset: 21,
+                    line_offset: 24,
                 },
                 end: lox::FileLocation {
                     line_number: 0,
-                    line_offset: 26,
+                    line_offset: 29,
                 },
                 message: "Only instances have fields".to_string(),
             },
@@ -420,18 +892,1455 @@ fn test_failures() {
                 message: "Superclass must be a class".to_string(),
             },
         ),
-    ];
-
-    for (expression, expected_diagnostic) in &tests {
-        reporter.reset();
-        lox::interpret(&reporter, expression);
-        if !reporter.has_diagnostic(expected_diagnostic) {
-            println!(
-                "Missing diagnostic: {} != {:?}",
-                expression, expected_diagnostic
-            );
-            reporter.print_contents();
-            panic!("Missing diagnostic");
-        }
-    }
+        (
+            "for (x in 10) print x;",
+            common::Diagnostic {
+                start: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 10,
+                },
+                end: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 12,
+                },
+                message: "Can only iterate over iterables".to_string(),
+            },
+        ),
+        (
+            "class Empty {} for (x in Empty()) print x;",
+            common::Diagnostic {
+                start: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 25,
+                },
+                end: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 32,
+                },
+                message: "Undefined property 'iterator'".to_string(),
+            },
+        ),
+        (
+            "var values = [1]; print values[1];",
+            common::Diagnostic {
+                start: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 24,
+                },
+                end: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 33,
+                },
+                message: "Index out of range".to_string(),
+            },
+        ),
+        (
+            "var r = range(0, \"10\");",
+            common::Diagnostic {
+                start: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 8,
+                },
+                end: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 13,
+                },
+                message: "Arguments to 'range' must be numbers".to_string(),
+            },
+        ),
+        (
+            "fun one() { yield 1; } var it = one(); it.next(); it.next();",
+            common::Diagnostic {
+                start: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 50,
+                },
+                end: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 57,
+                },
+                message: "Iterator is exhausted".to_string(),
+            },
+        ),
+        (
+            "fun selfish() { yield it.next(); } var it = selfish(); it.next();",
+            common::Diagnostic {
+                start: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 22,
+                },
+                end: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 29,
+                },
+                message: "Iterator is already running".to_string(),
+            },
+        ),
+        (
+            "class Base { #secret() {} } class Derived < Base { peek() { this.#secret(); } } Derived().peek();",
+            common::Diagnostic {
+                start: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 60,
+                },
+                end: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 64,
+                },
+                message: "Undefined property '#secret'".to_string(),
+            },
+        ),
+        (
+            "print 1 is 2;",
+            common::Diagnostic {
+                start: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 6,
+                },
+                end: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 12,
+                },
+                message: "Right operand of 'is' must be a class".to_string(),
+            },
+        ),
+        (
+            "print fields(1);",
+            common::Diagnostic {
+                start: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 6,
+                },
+                end: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 12,
+                },
+                message: "Argument to 'fields' must be an instance".to_string(),
+            },
+        ),
+        (
+            "print methods(1);",
+            common::Diagnostic {
+                start: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 6,
+                },
+                end: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 13,
+                },
+                message: "Argument to 'methods' must be a class".to_string(),
+            },
+        ),
+        (
+            "print superclassOf(nil);",
+            common::Diagnostic {
+                start: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 6,
+                },
+                end: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 18,
+                },
+                message: "Argument to 'superclassOf' must be a class".to_string(),
+            },
+        ),
+        (
+            "enum Color { Red } print Color.Blue;",
+            common::Diagnostic {
+                start: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 31,
+                },
+                end: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 35,
+                },
+                message: "Enum 'Color' has no member 'Blue'".to_string(),
+            },
+        ),
+        (
+            "enum Color { Red } var c = Color; print c.Blue;",
+            common::Diagnostic {
+                start: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 40,
+                },
+                end: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 41,
+                },
+                message: "Undefined property 'Blue'".to_string(),
+            },
+        ),
+        (
+            "print \"abc\".substring(2, 5);",
+            common::Diagnostic {
+                start: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 6,
+                },
+                end: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 21,
+                },
+                message: "Substring range out of bounds".to_string(),
+            },
+        ),
+        (
+            "print \"abc\".indexOf(1);",
+            common::Diagnostic {
+                start: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 6,
+                },
+                end: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 19,
+                },
+                message: "Argument to 'indexOf' must be a string".to_string(),
+            },
+        ),
+        (
+            "print (1.5).toFixed(-1);",
+            common::Diagnostic {
+                start: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 7,
+                },
+                end: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 19,
+                },
+                message: "Argument to 'toFixed' must be a non-negative integer".to_string(),
+            },
+        ),
+        (
+            "print (1.5).toFixed(70000);",
+            common::Diagnostic {
+                start: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 7,
+                },
+                end: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 19,
+                },
+                message: "Argument to 'toFixed' must be at most 100".to_string(),
+            },
+        ),
+        (
+            "print \"abc\".length;",
+            common::Diagnostic {
+                start: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 12,
+                },
+                end: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 18,
+                },
+                message: "Method 'length' must be called".to_string(),
+            },
+        ),
+        (
+            "print 1.size();",
+            common::Diagnostic {
+                start: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 6,
+                },
+                end: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 7,
+                },
+                message: "Undefined property 'size'".to_string(),
+            },
+        ),
+        (
+            "class A { toString() { return 1; } } print A();",
+            common::Diagnostic {
+                start: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 43,
+                },
+                end: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 46,
+                },
+                message: "'toString' must return a string".to_string(),
+            },
+        ),
+        (
+            "fun leaf() { return nil + 1; } fun branch() { return leaf(); } branch();",
+            common::Diagnostic {
+                start: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 20,
+                },
+                end: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 27,
+                },
+                message: "Operands must be two numbers or two strings".to_string(),
+            },
+        ),
+        (
+            "fun leaf() { return nil + 1; } fun branch() { return leaf(); } branch();",
+            common::Diagnostic {
+                start: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 53,
+                },
+                end: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 57,
+                },
+                message: "in call to 'leaf'".to_string(),
+            },
+        ),
+        (
+            "fun leaf() { return nil + 1; } fun branch() { return leaf(); } branch();",
+            common::Diagnostic {
+                start: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 63,
+                },
+                end: lox::FileLocation {
+                    line_number: 0,
+                    line_offset: 69,
+                },
+                message: "in call to 'branch'".to_string(),
+            },
+        ),
+    ];
+
+    for (expression, expected_diagnostic) in &tests {
+        reporter.reset();
+        lox::interpret(&reporter, expression);
+        if !reporter.has_diagnostic(expected_diagnostic) {
+            println!(
+                "Missing diagnostic: {} != {:?}",
+                expression, expected_diagnostic
+            );
+            reporter.print_contents();
+            panic!("Missing diagnostic");
+        }
+    }
+}
+
+#[test]
+fn test_call_depth_limit() {
+    let mut reporter = common::TestReporter::new();
+    let options = lox::Options {
+        max_call_depth: 20,
+        ..lox::Options::default()
+    };
+
+    lox::interpret_with_options(
+        &reporter,
+        "fun count(n) { if (n == 0) return 0; return 1 + count(n - 1); } print count(19);",
+        options.clone(),
+    );
+    assert!(!reporter.has_diagnostics(), "Unexpected errors");
+    assert!(reporter.has_message("[print] 19"));
+
+    reporter.reset();
+    let source = "fun recurse(n) { return recurse(n + 1); } recurse(0);";
+    lox::interpret_with_options(&reporter, source, options);
+    let expected_diagnostics = vec![
+        common::Diagnostic {
+            start: lox::FileLocation {
+                line_number: 0,
+                line_offset: 24,
+            },
+            end: lox::FileLocation {
+                line_number: 0,
+                line_offset: 31,
+            },
+            message: "Stack overflow".to_string(),
+        },
+        common::Diagnostic {
+            start: lox::FileLocation {
+                line_number: 0,
+                line_offset: 24,
+            },
+            end: lox::FileLocation {
+                line_number: 0,
+                line_offset: 31,
+            },
+            message: "in call to 'recurse' (repeated 18 more times)".to_string(),
+        },
+        common::Diagnostic {
+            start: lox::FileLocation {
+                line_number: 0,
+                line_offset: 42,
+            },
+            end: lox::FileLocation {
+                line_number: 0,
+                line_offset: 49,
+            },
+            message: "in call to 'recurse'".to_string(),
+        },
+    ];
+    for expected_diagnostic in &expected_diagnostics {
+        if !reporter.has_diagnostic(expected_diagnostic) {
+            println!(
+                "Missing diagnostic: {} != {:?}",
+                source, expected_diagnostic
+            );
+            reporter.print_contents();
+            panic!("Missing diagnostic");
+        }
+    }
+}
+
+#[test]
+fn test_default_call_depth() {
+    let tests = vec![
+        (
+            "class A { m(n) { if (n > 0) { var x = n; { var y = x; return this.m(y - 1) + 1; } } return 0; } } print A().m(100000);",
+            61,
+            67,
+        ),
+        (
+            "fun f(n) { while (true) { for (var i = 0; i < 1; i = i + 1) { if (true) { { var l = [n]; for (var x in l) { return f(x + 1); } } } } } } f(0);",
+            103,
+            104,
+        ),
+    ];
+
+    for (source, start, end) in tests {
+        let reporter = common::TestReporter::new();
+        lox::interpret(&reporter, source);
+        let expected_diagnostic = common::Diagnostic {
+            start: lox::FileLocation {
+                line_number: 0,
+                line_offset: start,
+            },
+            end: lox::FileLocation {
+                line_number: 0,
+                line_offset: end,
+            },
+            message: "Stack overflow".to_string(),
+        };
+        if !reporter.has_diagnostic(&expected_diagnostic) {
+            println!(
+                "Missing diagnostic: {} != {:?}",
+                source, expected_diagnostic
+            );
+            reporter.print_contents();
+            panic!("Missing diagnostic");
+        }
+    }
+}
+
+#[test]
+fn test_execution_limits() {
+    let mut reporter = common::TestReporter::new();
+    let step_limit = lox::Options {
+        max_steps: Some(1000),
+        ..lox::Options::default()
+    };
+
+    lox::interpret_with_options(
+        &reporter,
+        "var i = 0; while (i < 10) i = i + 1; print i;",
+        step_limit.clone(),
+    );
+    assert!(!reporter.has_diagnostics(), "Unexpected errors");
+    assert!(reporter.has_message("[print] 10"));
+
+    reporter.reset();
+    lox::interpret_with_options(&reporter, "while (true) {}", step_limit);
+    let expected_diagnostic = common::Diagnostic {
+        start: lox::FileLocation {
+            line_number: 0,
+            line_offset: 7,
+        },
+        end: lox::FileLocation {
+            line_number: 0,
+            line_offset: 11,
+        },
+        message: "Step limit exceeded".to_string(),
+    };
+    if !reporter.has_diagnostic(&expected_diagnostic) {
+        reporter.print_contents();
+        panic!("Missing diagnostic: {:?}", expected_diagnostic);
+    }
+
+    reporter.reset();
+    let polls = Rc::new(Cell::new(0));
+    let counted_polls = polls.clone();
+    let cancellable = lox::Options {
+        is_cancelled: Some(Rc::new(move || {
+            counted_polls.set(counted_polls.get() + 1);
+            counted_polls.get() == 3
+        })),
+        ..lox::Options::default()
+    };
+    lox::interpret_with_options(&reporter, "while (true) {}", cancellable);
+    let expected_diagnostic = common::Diagnostic {
+        message: "Execution cancelled".to_string(),
+        ..expected_diagnostic
+    };
+    if !reporter.has_diagnostic(&expected_diagnostic) {
+        reporter.print_contents();
+        panic!("Missing diagnostic: {:?}", expected_diagnostic);
+    }
+    assert_eq!(polls.get(), 3);
+}
+
+#[test]
+fn test_output() {
+    let reporter = common::TestReporter::new();
+    let buffer = Rc::new(RefCell::new(Vec::<u8>::new()));
+    let options = lox::Options {
+        output: Some(buffer.clone()),
+        ..lox::Options::default()
+    };
+
+    lox::interpret_with_options(
+        &reporter,
+        "class Bagel {}
+        fun eat() {}
+        print \"hello\";
+        print 2.5 + 0.5;
+        print nil;
+        print Bagel;
+        print Bagel();
+        print eat;
+        print clock;
+        print [\"a\", 1];",
+        options,
+    );
+    assert!(!reporter.has_diagnostics(), "Unexpected errors");
+    assert_eq!(
+        String::from_utf8(buffer.borrow().clone()).unwrap(),
+        "hello\n3\nnil\nBagel\nBagel instance\n<fn eat>\n<native fn>\n[\"a\", 1]\n"
+    );
+}
+
+#[test]
+fn test_reproducible_runs() {
+    let run = || {
+        let reporter = common::TestReporter::new();
+        let buffer = Rc::new(RefCell::new(Vec::<u8>::new()));
+        let now = Rc::new(Cell::new(10.0));
+        let options = lox::Options {
+            output: Some(buffer.clone()),
+            clock: Some(Rc::new(move || {
+                now.set(now.get() + 0.5);
+                now.get() - 0.5
+            })),
+            random_seed: Some(42),
+            ..lox::Options::default()
+        };
+        lox::interpret_with_options(
+            &reporter,
+            "var start = clock();
+            print start;
+            print clock() - start;
+            print random();
+            print random() == random();",
+            options,
+        );
+        assert!(!reporter.has_diagnostics(), "Unexpected errors");
+        let output = buffer.borrow().clone();
+        String::from_utf8(output).unwrap()
+    };
+
+    let first = run();
+    assert!(first.starts_with("10\n0.5\n0."), "{first}");
+    assert_eq!(first, run());
+}
+
+#[test]
+fn test_host_natives() {
+    let mut reporter = common::TestReporter::new();
+    let buffer = Rc::new(RefCell::new(Vec::<u8>::new()));
+    let interpreter = lox::Interpreter::builder()
+        .options(lox::Options {
+            output: Some(buffer.clone()),
+            ..lox::Options::default()
+        })
+        .native("add", 2, |arguments| {
+            let [a, b]: [lox::Value; 2] = arguments.try_into().unwrap();
+            Ok((f64::try_from(a)? + f64::try_from(b)?).into())
+        })
+        .native("greet", 1, |arguments| {
+            let name = String::try_from(arguments[0].clone())?;
+            Ok(format!("hello, {name}").into())
+        })
+        .native("clock", 0, |_| Ok(42.into()))
+        .build();
+
+    interpreter.interpret(
+        &reporter,
+        "print add(1, 2);
+        print greet(\"lox\");
+        print clock();",
+    );
+    assert!(!reporter.has_diagnostics(), "Unexpected errors");
+    assert_eq!(
+        String::from_utf8(buffer.borrow().clone()).unwrap(),
+        "3\nhello, lox\n42\n"
+    );
+
+    let failures = vec![
+        ("add(1, \"2\");", 0, 3, "Expected a number but got string"),
+        ("greet();", 0, 5, "Expected 1 arguments but got 0"),
+    ];
+    for (source, start, end, message) in failures {
+        reporter.reset();
+        interpreter.interpret(&reporter, source);
+        let expected_diagnostic = common::Diagnostic {
+            start: lox::FileLocation {
+                line_number: 0,
+                line_offset: start,
+            },
+            end: lox::FileLocation {
+                line_number: 0,
+                line_offset: end,
+            },
+            message: message.to_string(),
+        };
+        if !reporter.has_diagnostic(&expected_diagnostic) {
+            reporter.print_contents();
+            panic!("Missing diagnostic: {:?}", expected_diagnostic);
+        }
+    }
+}
+
+#[test]
+fn test_value_conversions() {
+    assert_eq!(f64::try_from(lox::Value::from(1.5)), Ok(1.5));
+    assert_eq!(bool::try_from(lox::Value::from(true)), Ok(true));
+    assert_eq!(
+        String::try_from(lox::Value::from("text")),
+        Ok("text".to_string())
+    );
+    assert!(lox::Value::from(()).is_nil());
+    assert!(lox::Value::from(None::<f64>).is_nil());
+    assert_eq!(lox::Value::from(Some(3)).as_number(), Some(3.0));
+    assert_eq!(
+        bool::try_from(lox::Value::nil()),
+        Err("Expected a boolean but got nil".to_string())
+    );
+    assert_eq!(lox::Value::from("text").type_name(), "string");
+}
+
+#[test]
+fn test_session() {
+    let mut reporter = common::TestReporter::new();
+    let mut session = lox::Session::new();
+
+    let inputs = vec![
+        ("var a = 1;", None),
+        ("print a;", Some("[print] 1")),
+        (
+            "fun counter() { var count = 0; fun next() { count = count + 1; return count; } return next; }",
+            None,
+        ),
+        ("var next = counter(); next();", None),
+        ("print next();", Some("[print] 2")),
+        ("class Point { init(x) { this.x = x; } }", None),
+        ("print Point(3).x;", Some("[print] 3")),
+        ("enum Color { Red, Green }", None),
+        ("print Color.Green;", Some("[print] Color.Green")),
+        ("print missing;", None),
+        ("a = a + 1; print a;", Some("[print] 2")),
+    ];
+    for (source, expected_message) in inputs {
+        reporter.reset();
+        session.evaluate(&reporter, source);
+        if let Some(expected_message) = expected_message {
+            if !reporter.has_message(expected_message) {
+                reporter.print_contents();
+                panic!("Missing message: {source} != {expected_message}");
+            }
+        }
+    }
+
+    reporter.reset();
+    session.evaluate(&reporter, "print Color.Blue;");
+    let expected_diagnostic = common::Diagnostic {
+        start: lox::FileLocation {
+            line_number: 0,
+            line_offset: 12,
+        },
+        end: lox::FileLocation {
+            line_number: 0,
+            line_offset: 16,
+        },
+        message: "Enum 'Color' has no member 'Blue'".to_string(),
+    };
+    if !reporter.has_diagnostic(&expected_diagnostic) {
+        reporter.print_contents();
+        panic!("Missing diagnostic: {:?}", expected_diagnostic);
+    }
+}
+
+#[test]
+fn test_standard_library() {
+    let mut reporter = common::TestReporter::new();
+    let tests = vec![
+        ("print sqrt(16);", "[print] 4"),
+        ("print pow(2, 10);", "[print] 1024"),
+        ("print floor(-1.5);", "[print] -2"),
+        ("print abs(-3);", "[print] 3"),
+        ("print min(3, -1);", "[print] -1"),
+        ("print max(3, -1);", "[print] 3"),
+        ("print num(\" 2.5 \") + 1;", "[print] 3.5"),
+        ("print num(\"two\");", "[print] nil"),
+        ("print str(12) + str(true) + str(nil);", "[print] 12truenil"),
+        (
+            "class Bagel {} print str(Bagel());",
+            "[print] Bagel instance",
+        ),
+        (
+            "var l = [1]; l[0] = l; print \"x\" + str(l);",
+            "[print] x[[...]]",
+        ),
+        (
+            "var m = {}; m[\"self\"] = m; print str(m);",
+            "[print] {\"self\": {...}}",
+        ),
+        ("print len(\"héllo\");", "[print] 5"),
+        ("print len([1, 2, 3]);", "[print] 3"),
+        ("print len({\"a\": 1});", "[print] 1"),
+        ("print chr(65) + chr(233);", "[print] Aé"),
+        ("print ord(\"A\");", "[print] 65"),
+        ("var r = random(); print r >= 0 and r < 1;", "[print] true"),
+        (
+            "seed(7); var a = random(); seed(7); print a == random();",
+            "[print] true",
+        ),
+        ("print assert(1 < 2, \"ordered\");", "[print] nil"),
+    ];
+    for (source, expected_message) in tests {
+        reporter.reset();
+        lox::run(&reporter, source);
+        if reporter.has_diagnostics() || !reporter.has_message(expected_message) {
+            reporter.print_contents();
+            panic!("Missing message: {source} != {expected_message}");
+        }
+    }
+
+    let failures = vec![
+        ("sqrt(\"4\");", "Argument to 'sqrt' must be a number"),
+        ("pow(2, nil);", "Arguments to 'pow' must be numbers"),
+        ("floor(true);", "Argument to 'floor' must be a number"),
+        ("abs(\"-1\");", "Argument to 'abs' must be a number"),
+        ("min(1, \"2\");", "Arguments to 'min' must be numbers"),
+        ("max(nil, 2);", "Arguments to 'max' must be numbers"),
+        ("num(1);", "Argument to 'num' must be a string"),
+        ("len(1);", "Argument to 'len' must be a string, list or map"),
+        ("chr(-1);", "Argument to 'chr' must be a character code"),
+        ("chr(55296);", "Argument to 'chr' must be a character code"),
+        (
+            "ord(\"ab\");",
+            "Argument to 'ord' must be a single character",
+        ),
+        ("seed(\"1\");", "Argument to 'seed' must be a number"),
+        ("assert(true, 1);", "Argument to 'assert' must be a string"),
+        ("assert(1 > 2, \"ordered\");", "Assertion failed: ordered"),
+    ];
+    for (source, message) in failures {
+        reporter.reset();
+        lox::run(&reporter, source);
+        let expected_diagnostic = common::Diagnostic {
+            start: lox::FileLocation {
+                line_number: 0,
+                line_offset: 0,
+            },
+            end: lox::FileLocation {
+                line_number: 0,
+                line_offset: source.find('(').unwrap() as u32,
+            },
+            message: message.to_string(),
+        };
+        if !reporter.has_diagnostic(&expected_diagnostic) {
+            reporter.print_contents();
+            panic!("Missing diagnostic: {:?}", expected_diagnostic);
+        }
+    }
+}
+
+#[test]
+fn test_capabilities() {
+    let mut reporter = common::TestReporter::new();
+    let denied = vec![
+        (
+            "readFile(\"in.txt\");",
+            "'readFile' needs the ReadFiles capability",
+        ),
+        (
+            "writeFile(\"out.txt\", \"\");",
+            "'writeFile' needs the WriteFiles capability",
+        ),
+        (
+            "listDir(\".\");",
+            "'listDir' needs the ReadFiles capability",
+        ),
+        (
+            "getEnv(\"HOME\");",
+            "'getEnv' needs the Environment capability",
+        ),
+        ("args();", "'args' needs the Environment capability"),
+        ("readLine();", "'readLine' needs the Stdin capability"),
+    ];
+    for (source, message) in denied {
+        reporter.reset();
+        lox::run(&reporter, source);
+        let expected_diagnostic = common::Diagnostic {
+            start: lox::FileLocation {
+                line_number: 0,
+                line_offset: 0,
+            },
+            end: lox::FileLocation {
+                line_number: 0,
+                line_offset: source.find('(').unwrap() as u32,
+            },
+            message: message.to_string(),
+        };
+        if !reporter.has_diagnostic(&expected_diagnostic) {
+            reporter.print_contents();
+            panic!("Missing diagnostic: {:?}", expected_diagnostic);
+        }
+    }
+
+    let directory = std::env::temp_dir().join(format!("lox-capabilities-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let path = directory.join("report.txt");
+    let options = lox::Options {
+        capabilities: HashSet::from([
+            lox::Capability::ReadFiles,
+            lox::Capability::WriteFiles,
+            lox::Capability::Environment,
+        ]),
+        args: vec!["--verbose".to_string()],
+        ..lox::Options::default()
+    };
+    let allowed = vec![
+        (
+            format!(
+                "writeFile({0:?}, \"done\"); print readFile({0:?});",
+                path.display()
+            ),
+            "[print] done",
+        ),
+        (
+            format!("print listDir({:?});", directory.display()),
+            "[print] [\"report.txt\"]",
+        ),
+        (
+            "print getEnv(\"CARGO_PKG_NAME\");".to_string(),
+            "[print] lox",
+        ),
+        (
+            "print getEnv(\"LOX_UNSET_VARIABLE\");".to_string(),
+            "[print] nil",
+        ),
+        ("print args();".to_string(), "[print] [\"--verbose\"]"),
+    ];
+    for (source, expected_message) in allowed {
+        reporter.reset();
+        lox::interpret_with_options(&reporter, &source, options.clone());
+        if reporter.has_diagnostics() || !reporter.has_message(expected_message) {
+            reporter.print_contents();
+            panic!("Missing message: {source} != {expected_message}");
+        }
+    }
+
+    reporter.reset();
+    let missing = directory.join("missing.txt");
+    lox::interpret_with_options(
+        &reporter,
+        &format!("readFile({:?});", missing.display()),
+        options,
+    );
+    std::fs::remove_dir_all(&directory).unwrap();
+    assert!(
+        reporter.has_diagnostics(),
+        "Missing error for an unreadable file"
+    );
+}
+
+#[test]
+fn test_higher_order_natives() {
+    let mut reporter = common::TestReporter::new();
+    let tests = vec![
+        (
+            "fun double(x) { return x * 2; } print map([1, 2, 3], double);",
+            "[print] [2, 4, 6]",
+        ),
+        ("print map([\"1\", \"x\"], num);", "[print] [1, nil]"),
+        (
+            "class Box { init(value) { this.value = value; } }
+            print map([1], Box)[0].value;",
+            "[print] 1",
+        ),
+        (
+            "fun odd(x) { return x - floor(x / 2) * 2 == 1; } print filter([1, 2, 3, 4, 5], odd);",
+            "[print] [1, 3, 5]",
+        ),
+        (
+            "fun add(total, x) { return total + x; } print reduce([1, 2, 3], add, 10);",
+            "[print] 16",
+        ),
+        (
+            "var seen = \"\"; fun see(x) { seen = seen + x; } forEach([\"a\", \"b\"], see); print seen;",
+            "[print] ab",
+        ),
+        (
+            "fun byLength(a, b) { return len(a) - len(b); }
+            print sort([\"ccc\", \"a\", \"bb\", \"b\"], byLength);",
+            "[print] [\"a\", \"b\", \"bb\", \"ccc\"]",
+        ),
+        (
+            "fun descending(a, b) { return b - a; } print len(sort([], descending)) + len(sort([3, 1, 2], descending)); print sort([3, 1, 2], descending);",
+            "[print] [3, 2, 1]",
+        ),
+        (
+            "class Point { init(x) { this.x = x; } toString() { return \"P\" + str(this.x); } }
+            print str(Point(1));",
+            "[print] P1",
+        ),
+    ];
+    for (source, expected_message) in tests {
+        reporter.reset();
+        lox::run(&reporter, source);
+        if reporter.has_diagnostics() || !reporter.has_message(expected_message) {
+            reporter.print_contents();
+            panic!("Missing message: {source} != {expected_message}");
+        }
+    }
+
+    let failures = vec![
+        (
+            "fun bad(x) { return x + nil; } map([1], bad);",
+            20,
+            27,
+            "Operands must be two numbers or two strings",
+        ),
+        (
+            "fun bad(x) { return x + nil; } map([1], bad);",
+            31,
+            34,
+            "in call to 'map'",
+        ),
+        (
+            "fun bad(x) { return x + nil; } map([1], bad);",
+            4,
+            7,
+            "in call to 'bad'",
+        ),
+        (
+            "class P { init(x) { this.x = x + nil; } } map([1], P);",
+            10,
+            14,
+            "in call to 'P'",
+        ),
+        ("map(1, str);", 0, 3, "Argument to 'map' must be a list"),
+        (
+            "fun pair(a, b) {} forEach([1], pair);",
+            18,
+            25,
+            "Expected 2 arguments but got 1",
+        ),
+        (
+            "filter([1], 2);",
+            0,
+            6,
+            "Can only call functions and classes",
+        ),
+        (
+            "fun same(a, b) { return nil; } sort([1, 2], same);",
+            31,
+            35,
+            "Comparator for 'sort' must return a number",
+        ),
+    ];
+    for (source, start, end, message) in failures {
+        reporter.reset();
+        lox::run(&reporter, source);
+        let expected_diagnostic = common::Diagnostic {
+            start: lox::FileLocation {
+                line_number: 0,
+                line_offset: start,
+            },
+            end: lox::FileLocation {
+                line_number: 0,
+                line_offset: end,
+            },
+            message: message.to_string(),
+        };
+        if !reporter.has_diagnostic(&expected_diagnostic) {
+            reporter.print_contents();
+            panic!("Missing diagnostic: {:?}", expected_diagnostic);
+        }
+    }
+}
+
+#[test]
+fn test_json() {
+    let mut reporter = common::TestReporter::new();
+    let tests = vec![
+        (
+            "var data = jsonParse(\"[1, 2.5, -3e2, true, null, []]\");
+            print str(data[1]) + \" \" + str(data[2]) + \" \" + str(data[3]) + \" \" + str(data[4]) + \" \" + typeof(data[5]);",
+            "[print] 2.5 -300 true nil list",
+        ),
+        (
+            "var q = chr(34);
+            var data = jsonParse(\"{\" + q + \"name\" + q + \": \" + q + \"a\\n\\u00e9\\ud83d\\ude00\" + q + \"}\");
+            print typeof(data) + \" \" + data[\"name\"];",
+            "[print] map a\né😀",
+        ),
+        (
+            "var q = chr(34); print jsonStringify({\"a\": [1, 2.5, nil], \"b\": {\"c\": q}, 3: false});",
+            "[print] {\"a\":[1,2.5,null],\"b\":{\"c\":\"\\\"\"},\"3\":false}",
+        ),
+        (
+            "print jsonStringify({\"a\": [1], \"b\": {}}, 2);",
+            "[print] {\n  \"a\": [\n    1\n  ],\n  \"b\": {}\n}",
+        ),
+        (
+            "enum Level { Low, High }
+            class Point { init(x, y) { this.y = y; this.x = x; this.level = Level.High; } }
+            print jsonStringify(Point(1, 2));",
+            "[print] {\"level\":\"High\",\"x\":1,\"y\":2}",
+        ),
+        (
+            "var text = jsonStringify({\"x\": [true, \"y\"]}, nil); print jsonStringify(jsonParse(text)) == text;",
+            "[print] true",
+        ),
+        (
+            "print jsonStringify([1, [2]], 1000000000000000000);",
+            "[print] [\n          1,\n          [\n                    2\n          ]\n]",
+        ),
+        (
+            "var s = \"\"; for (var i = 0; i < 512; i = i + 1) s = \"[\" + s + \"]\";
+            var l = jsonParse(s); print len(jsonStringify(l));",
+            "[print] 1024",
+        ),
+    ];
+    for (source, expected_message) in tests {
+        reporter.reset();
+        lox::run(&reporter, source);
+        if reporter.has_diagnostics() || !reporter.has_message(expected_message) {
+            reporter.print_contents();
+            panic!("Missing message: {source} != {expected_message}");
+        }
+    }
+
+    let failures = vec![
+        (
+            "jsonParse(\"[1, 2\");",
+            0,
+            9,
+            "Invalid JSON at line 1, column 6: expected ',' or ']'",
+        ),
+        (
+            "jsonParse(\"[
+  tru]\");",
+            0,
+            9,
+            "Invalid JSON at line 2, column 3: expected a value",
+        ),
+        (
+            "jsonParse(\"1 2\");",
+            0,
+            9,
+            "Invalid JSON at line 1, column 3: unexpected text after the value",
+        ),
+        (
+            "var list = [1]; list.push(list); jsonStringify(list);",
+            33,
+            46,
+            "Cannot convert a cyclic structure to JSON",
+        ),
+        (
+            "fun f() {} jsonStringify({\"f\": f});",
+            11,
+            24,
+            "Cannot convert a value of type 'function' to JSON",
+        ),
+        (
+            "jsonStringify(1, -1);",
+            0,
+            13,
+            "Indent for 'jsonStringify' must be a whole number",
+        ),
+        ("jsonStringify();", 0, 13, "Expected 2 arguments but got 0"),
+        (
+            "var s = \"\"; for (var i = 0; i < 20000; i = i + 1) s = s + \"[\"; jsonParse(s);",
+            63,
+            72,
+            "Invalid JSON at line 1, column 513: nesting too deep",
+        ),
+        (
+            "var l = []; for (var i = 0; i < 600; i = i + 1) l = [l]; jsonStringify(l);",
+            57,
+            70,
+            "Cannot convert a structure nested more than 512 deep to JSON",
+        ),
+    ];
+    for (source, start, end, message) in failures {
+        reporter.reset();
+        lox::run(&reporter, source);
+        let expected_diagnostic = common::Diagnostic {
+            start: lox::FileLocation {
+                line_number: 0,
+                line_offset: start,
+            },
+            end: lox::FileLocation {
+                line_number: 0,
+                line_offset: end,
+            },
+            message: message.to_string(),
+        };
+        if !reporter.has_diagnostic(&expected_diagnostic) {
+            reporter.print_contents();
+            panic!("Missing diagnostic: {:?}", expected_diagnostic);
+        }
+    }
+}
+
+struct Counter {
+    count: f64,
+    step: f64,
+}
+
+#[test]
+fn test_native_classes() {
+    let mut reporter = common::TestReporter::new();
+    let counter = lox::NativeClass::<Counter>::new("Counter")
+        .constructor(1, |arguments| {
+            let start = f64::try_from(arguments[0].clone())?;
+            Ok(Counter {
+                count: start,
+                step: 1.0,
+            })
+        })
+        .method("increment", 0, |counter, _| {
+            counter.count += counter.step;
+            Ok(counter.count.into())
+        })
+        .getter("count", |counter| counter.count.into())
+        .getter("step", |counter| counter.step.into())
+        .setter("step", |counter, value| {
+            counter.step = f64::try_from(value)?;
+            Ok(())
+        });
+    let row = lox::NativeClass::<Counter>::new("Row").method("size", 0, |_, _| Ok(0.0.into()));
+    let interpreter = lox::Interpreter::builder()
+        .class(counter)
+        .class(row)
+        .build();
+
+    let tests =
+        vec![
+        (
+            "var c = Counter(10); c.increment(); print c.increment();",
+            "[print] 12",
+        ),
+        (
+            "var c = Counter(0); c.step = 5; c.increment(); print c.count;",
+            "[print] 5",
+        ),
+        (
+            "var c = Counter(0); var increment = c.increment; increment(); print c.count;",
+            "[print] 1",
+        ),
+        ("print Counter(0);", "[print] Counter instance"),
+        ("print methods(Counter);", "[print] [\"increment\", \"init\"]"),
+        (
+            "class Stepper < Counter {
+                init(step) { super.init(0); this.step = step; }
+                twice() { this.increment(); return this.increment(); }
+            }
+            print Stepper(3).twice();",
+            "[print] 6",
+        ),
+        (
+            "class Plain < Counter { label() { return \"at \" + str(this.count); } }
+            var p = Plain(4); p.increment(); print p.label();",
+            "[print] at 5",
+        ),
+        (
+            "class Noisy < Counter { increment() { print \"tick\"; return super.increment(); } }
+            print Noisy(1).increment();",
+            "[print] 2",
+        ),
+        (
+            "var c = Counter(0); c.label = \"fields still work\"; print c.label;",
+            "[print] fields still work",
+        ),
+    ];
+    for (source, expected_message) in tests {
+        reporter.reset();
+        interpreter.interpret(&reporter, source);
+        if reporter.has_diagnostics() || !reporter.has_message(expected_message) {
+            reporter.print_contents();
+            panic!("Missing message: {source} != {expected_message}");
+        }
+    }
+
+    let failures = vec![
+        (
+            "Counter(\"ten\");",
+            0,
+            7,
+            "Expected a number but got string",
+        ),
+        (
+            "var c = Counter(0); c.count = 1;",
+            20,
+            21,
+            "Property 'count' is read-only",
+        ),
+        (
+            "var c = Counter(0); c.step = nil;",
+            20,
+            21,
+            "Expected a number but got nil",
+        ),
+        (
+            "class Lazy < Counter { init() {} } Lazy().increment();",
+            35,
+            51,
+            "Instance of 'Counter' has not been constructed",
+        ),
+        ("Row();", 0, 3, "'Row' has no constructor"),
+    ];
+    for (source, start, end, message) in failures {
+        reporter.reset();
+        interpreter.interpret(&reporter, source);
+        let expected_diagnostic = common::Diagnostic {
+            start: lox::FileLocation {
+                line_number: 0,
+                line_offset: start,
+            },
+            end: lox::FileLocation {
+                line_number: 0,
+                line_offset: end,
+            },
+            message: message.to_string(),
+        };
+        if !reporter.has_diagnostic(&expected_diagnostic) {
+            reporter.print_contents();
+            panic!("Missing diagnostic: {:?}", expected_diagnostic);
+        }
+    }
+}
+
+#[test]
+fn test_calling_lox_from_rust() {
+    let reporter = common::TestReporter::new();
+    let mut session = lox::Session::new();
+    session.evaluate(
+        &reporter,
+        "var greeting = \"hello\";
+        var calls = 0;
+        fun onEvent(name, count) { calls = calls + 1; return name + \":\" + str(count); }
+        fun fail() { return nil + 1; }
+        fun outer() { return fail(); }
+        class Greeter { init(name) { this.name = name; } greet(other) { return this.name + \" greets \" + other; } }",
+    );
+    assert!(!reporter.has_diagnostics(), "Unexpected errors");
+
+    assert_eq!(
+        session
+            .global("greeting")
+            .and_then(|value| value.as_str().map(str::to_string)),
+        Some("hello".to_string())
+    );
+    assert_eq!(session.global("missing"), None);
+
+    let hook = session.global("onEvent").unwrap();
+    for count in 1..=3 {
+        let result = session.call(&reporter, &hook, vec!["save".into(), count.into()]);
+        assert_eq!(result, Ok(format!("save:{count}").into()));
+    }
+    assert_eq!(session.global("calls").unwrap().as_number(), Some(3.0));
+
+    let greeter = session.global("Greeter").unwrap();
+    let greeter = session
+        .call(&reporter, &greeter, vec!["ann".into()])
+        .unwrap();
+    assert_eq!(
+        session.call_method(&reporter, &greeter, "greet", vec!["bob".into()]),
+        Ok("ann greets bob".into())
+    );
+
+    let location = |start, end| {
+        Some((
+            lox::FileLocation {
+                line_number: 3,
+                line_offset: start,
+            },
+            lox::FileLocation {
+                line_number: 3,
+                line_offset: end,
+            },
+        ))
+    };
+    let error = session
+        .call(&reporter, &session.global("outer").unwrap(), vec![])
+        .unwrap_err();
+    assert_eq!(error.message, "Operands must be two numbers or two strings");
+    assert_eq!(error.location, location(28, 35));
+    assert_eq!(error.stack.len(), 1);
+    assert_eq!(error.stack[0].function, "fail");
+
+    assert_eq!(
+        session.call(&reporter, &hook, vec![]),
+        Err(lox::CallError {
+            message: "Expected 2 arguments but got 0".to_string(),
+            location: None,
+            stack: Vec::new(),
+        })
+    );
+    assert_eq!(
+        session
+            .call_method(&reporter, &greeter, "wave", vec![])
+            .unwrap_err()
+            .message,
+        "Undefined property 'wave'"
+    );
+    assert!(
+        !reporter.has_diagnostics(),
+        "Calls from Rust should not report"
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde() {
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    enum Level {
+        Debug,
+        Info,
+    }
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Server {
+        host: String,
+        port: u16,
+        weight: f64,
+    }
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Config {
+        name: String,
+        level: Level,
+        verbose: bool,
+        timeout: Option<u32>,
+        servers: Vec<Server>,
+        labels: std::collections::BTreeMap<String, String>,
+    }
+
+    let reporter = common::TestReporter::new();
+    let mut session = lox::Session::new();
+    session.evaluate(
+        &reporter,
+        "enum Level { Debug, Info }
+        class Server { init(host, port) { this.host = host; this.port = port; this.weight = 0.5; } }
+        class Config {
+            init() {
+                this.name = \"prod\";
+                this.level = Level.Info;
+                this.verbose = false;
+                this.timeout = nil;
+                this.servers = [];
+                for (var i = 0; i < 2; i = i + 1) this.servers.push(Server(\"10.0.0.\" + str(i), 8000 + i));
+                this.labels = {\"team\": \"core\"};
+            }
+        }
+        var config = Config();
+        var bad = {\"name\": 1};",
+    );
+    assert!(!reporter.has_diagnostics(), "Unexpected errors");
+
+    let config: Config = lox::from_value(session.global("config").unwrap()).unwrap();
+    assert_eq!(
+        config,
+        Config {
+            name: "prod".to_string(),
+            level: Level::Info,
+            verbose: false,
+            timeout: None,
+            servers: vec![
+                Server {
+                    host: "10.0.0.0".to_string(),
+                    port: 8000,
+                    weight: 0.5,
+                },
+                Server {
+                    host: "10.0.0.1".to_string(),
+                    port: 8001,
+                    weight: 0.5,
+                },
+            ],
+            labels: [("team".to_string(), "core".to_string())].into(),
+        }
+    );
+    assert_ne!(config.level, Level::Debug);
+
+    let error = lox::from_value::<Config>(session.global("bad").unwrap()).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "invalid type: integer `1`, expected a string"
+    );
+
+    let json = serde_json::to_string(&session.global("config").unwrap()).unwrap();
+    assert!(
+        json.starts_with("{\"labels\":{\"team\":\"core\"},\"level\":\"Info\",\"name\":\"prod\"")
+    );
+    let value: lox::Value = serde_json::from_str("[1, \"two\", null, {\"x\": true}]").unwrap();
+    assert_eq!(value.to_string(), "[1, \"two\", nil, {\"x\": true}]");
+
+    session.evaluate(
+        &reporter,
+        "class C {} var c = C(); c.me = c; var a = [1]; a.push(a);",
+    );
+    assert!(!reporter.has_diagnostics(), "Unexpected errors");
+    for name in ["c", "a"] {
+        let cyclic = session.global(name).unwrap();
+        let error = serde_json::to_string(&cyclic).unwrap_err();
+        assert_eq!(error.to_string(), "Cannot serialize a cyclic structure");
+        let error = lox::from_value::<serde_json::Value>(cyclic).unwrap_err();
+        assert_eq!(error.to_string(), "Cannot deserialize a cyclic structure");
+    }
+
+    #[derive(serde::Serialize)]
+    enum Shape {
+        Point,
+        Circle { radius: f64 },
+    }
+
+    #[derive(serde::Serialize)]
+    struct Scene {
+        name: &'static str,
+        shapes: Vec<Shape>,
+        origin: (i32, i32),
+        tag: Option<char>,
+    }
+
+    let scene = lox::to_value(&Scene {
+        name: "demo",
+        shapes: vec![Shape::Point, Shape::Circle { radius: 1.5 }],
+        origin: (0, -1),
+        tag: None,
+    })
+    .unwrap();
+    assert_eq!(
+        scene.to_string(),
+        "{\"name\": \"demo\", \"shapes\": [\"Point\", {\"Circle\": {\"radius\": 1.5}}], \"origin\": [0, -1], \"tag\": nil}"
+    );
+    let error = lox::to_value(&std::collections::HashMap::from([(vec![1], 2)])).unwrap_err();
+    assert_eq!(error.to_string(), "Map key must be a string or a number");
 }